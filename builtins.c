@@ -1,5 +1,6 @@
 #include <stdint.h>
 #include <stdio.h>
+#include <stdlib.h>
 #include <inttypes.h>
 
 // A collection of C functions that are linked to Wellick
@@ -31,6 +32,20 @@ int32_t println(int64_t inp) {
     return printf("%" PRId64 "\n", inp);
 }
 
+// Print a float to stdout with a newline
+int32_t println_f64(double inp) {
+    return printf("%g\n", inp);
+}
+
+int32_t println_f32(float inp) {
+    return printf("%g\n", inp);
+}
+
+// Print a float to stdout
+int32_t print_f64(double inp) {
+    return printf("%g", inp);
+}
+
 // Add two integers, return the result
 int32_t iadd(int32_t a, int32_t b) {
     return a + b;
@@ -73,3 +88,58 @@ int32_t ilteq (int32_t a, int32_t b) {
 int32_t igt (int32_t a, int32_t b) {
     return a > b;
 }
+
+// Add two floats, return the result
+double fadd(double a, double b) {
+    return a + b;
+}
+
+// Subtract two floats, return the result
+double fsub(double a, double b) {
+    return a - b;
+}
+
+// Multiply two floats, return the result
+double fmul(double a, double b) {
+    return a * b;
+}
+
+// Divide two floats, return the result
+double fdiv(double a, double b) {
+    return a / b;
+}
+
+// Check the equality of two floats, return 1 if true, 0 if false
+int32_t feq(double a, double b) {
+    return a == b;
+}
+
+// Check if the first float is less than the second, return 1 if true, 0 if false
+int32_t flt(double a, double b) {
+    return a < b;
+}
+
+int32_t flteq(double a, double b) {
+    return a <= b;
+}
+
+// Check if the first float is greater than the second, return 1 if true, 0 if false
+int32_t fgt(double a, double b) {
+    return a > b;
+}
+
+// Convert between single and double precision floats
+double f32_to_f64(float a) {
+    return a;
+}
+
+float f64_to_f32(double a) {
+    return a;
+}
+
+// Called by the checks inserted before dereferencing a pointer in a debug
+// build, when the pointer is null. The program is stopped after the message.
+void null_pointer_dereference(void) {
+    fflush(stdout);
+    fprintf(stderr, "error: attempted to dereference a null pointer\n");
+}
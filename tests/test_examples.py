@@ -35,6 +35,27 @@ def run_example(file: PathLike) -> tuple[bytes, bytes, int]:
     "isub_builtin.wellick",
     "imul_builtin.wellick",
     "ptr_type.wellick",
+    "mut_param.wellick",
+    "scopes.wellick",
+    "type_inference.wellick",
+    "generics.wellick",
+    "methods.wellick",
+    "traits.wellick",
+    "modules.wellick",
+    "comments.wellick",
+    "if_expressions.wellick",
+    "compound_assign.wellick",
+    "floats.wellick",
+    "size_of.wellick",
+    "null_pointers.wellick",
+    "const_fn.wellick",
+    "type_alias.wellick",
+    "deferred_init.wellick",
+    "unreachable_code.wellick",
+    "lints.wellick",
+    "diverging_let.wellick",
+    "shadowed_const.wellick",
+    "diverging_args.wellick",
 ])
 def test_pass_examples(file: str) -> None:
     """Test that the builtins example compiles."""
@@ -45,7 +66,50 @@ def test_pass_examples(file: str) -> None:
     assert retcode == 0
 
 
-@pytest.mark.parametrize("file", ["default_const.wellick"])
+@pytest.mark.parametrize("file", [
+    "default_const.wellick",
+    "immutable_param.wellick",
+    "out_of_scope.wellick",
+    "self_referential_init.wellick",
+    "generic_type_args.wellick",
+    "immutable_receiver.wellick",
+    "missing_trait_method.wellick",
+    "unsatisfied_bound.wellick",
+    "private_function.wellick",
+    "if_without_else.wellick",
+    "immutable_compound_assign.wellick",
+    "mismatched_pointer.wellick",
+    "size_of_unknown_type.wellick",
+    "null_non_pointer.wellick",
+    "non_const_fn_call.wellick",
+    "const_overflow.wellick",
+    "assign_to_const.wellick",
+    "alias_mismatch.wellick",
+    "alias_cycle.wellick",
+    "missing_return_type.wellick",
+    "syntax_errors.wellick",
+    "mismatched_argument.wellick",
+    "undefined_names.wellick",
+    "uninitialized.wellick",
+    "missing_return.wellick",
+    "denied_lints.wellick",
+    "pointer_mutability.wellick",
+    "private_method.wellick",
+    "duplicate_function.wellick",
+    "literal_overflow.wellick",
+    "reserved_name.wellick",
+    "const_type_mismatch.wellick",
+    "unbounded_method.wellick",
+    "unused_generic.wellick",
+    "unresolved_paths.wellick",
+    "unused_result.wellick",
+    "non_integer_condition.wellick",
+    "instantiation_limit.wellick",
+    "pointer_laundering.wellick",
+    "builtin_name.wellick",
+    "self_import.wellick",
+    "trait_method_conflict.wellick",
+])
 def test_fail_examples(file: str) -> None:
     """Test that the default_const example compiles."""
     root = Path(__file__).parent
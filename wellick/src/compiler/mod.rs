@@ -1,17 +1,14 @@
-mod functions;
 mod translate;
 mod variables;
 
-use std::collections::HashMap;
-
-use crate::parser::ast;
-use functions::build_fn_map;
+use crate::diagnostics::{CompileError, CompileResult};
+use crate::sema::structs::{struct_layout, Structs};
+use crate::sema::typed;
 
 use cranelift::codegen;
-use cranelift::prelude::AbiParam;
 use cranelift::prelude::Configurable;
 use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext};
-use cranelift_module::{DataContext, Linkage, Module};
+use cranelift_module::{Linkage, Module};
 use cranelift_native::builder as host_isa_builder;
 use cranelift_object::{ObjectBuilder, ObjectModule};
 use std::fs::File;
@@ -20,8 +17,9 @@ use std::io::prelude::Write;
 pub struct Compiler {
     builder_context: FunctionBuilderContext,
     codegen_context: codegen::Context,
-    data_context: DataContext,
     module: cranelift_object::ObjectModule,
+    /// Whether dereferences are checked for null pointers, as in a debug build.
+    null_checks: bool,
 }
 
 impl Default for Compiler {
@@ -51,61 +49,45 @@ impl Default for Compiler {
         Self {
             builder_context: FunctionBuilderContext::new(),
             codegen_context: module.make_context(),
-            data_context: DataContext::new(),
             module,
+            null_checks: true,
         }
     }
 }
 
 impl Compiler {
-    /// Compile a parsed AST
-    pub fn compile(mut self, code: Vec<ast::FnDecl>) -> Result<(), String> {
-        self.translate(code);
+    /// Enable or disable the null pointer checks inserted before dereferences.
+    pub fn with_null_checks(mut self, null_checks: bool) -> Self {
+        self.null_checks = null_checks;
+        self
+    }
 
-        // Finish
-        match self.finish() {
-            Ok(_) => {}
-            Err(_) => return Err("Failed to emit bytecode".to_string()),
+    /// Compile a checked program.
+    pub fn compile(mut self, program: typed::Program) -> CompileResult<()> {
+        for func in &program.functions {
+            self.translate_decl(&program.structs, func)?;
         }
 
-        Ok(())
+        // Finish
+        self.finish()
     }
 
-    pub fn finish(self) -> Result<(), std::io::Error> {
+    pub fn finish(self) -> CompileResult<()> {
         let product = self.module.finish();
-        let code = product.emit().unwrap();
+        let code = product.emit().map_err(|err| {
+            CompileError::without_span(format!("Failed to emit object code: {err}"))
+        })?;
         println!("writing object file");
-        let mut file = File::create("a.out")?;
-        file.write_all(&code)?;
-
-        Ok(())
-    }
-
-    fn translate(&mut self, code: Vec<ast::FnDecl>) {
-        let fn_map = build_fn_map(&code);
-        for func in code {
-            self.translate_decl(&fn_map, func);
-        }
+        File::create("a.out")
+            .and_then(|mut file| file.write_all(&code))
+            .map_err(|err| CompileError::without_span(format!("Failed to emit bytecode: {err}")))
     }
 
-    fn translate_decl(&mut self, fn_map: &HashMap<String, ast::FnDecl>, node: ast::FnDecl) {
-        // Define function arguments
-        for arg in &node.args {
-            let t = variables::to_cranelift_type(&arg.t);
-            self.codegen_context
-                .func
-                .signature
-                .params
-                .push(AbiParam::new(t))
-        }
-
-        // Define the function return type.
-        let t = variables::to_cranelift_type(&node.ret_type);
-        self.codegen_context
-            .func
-            .signature
-            .returns
-            .push(AbiParam::new(t));
+    fn translate_decl(&mut self, structs: &Structs, node: &typed::Function) -> CompileResult<()> {
+        // Define the function arguments and return type.
+        self.codegen_context.func.signature =
+            translate::cranelift_signature(node, structs, &self.module);
+        let config = self.module.target_config();
 
         let mut function_builder =
             FunctionBuilder::new(&mut self.codegen_context.func, &mut self.builder_context);
@@ -114,14 +96,24 @@ impl Compiler {
         function_builder.switch_to_block(entry_block);
         function_builder.append_block_params_for_function_params(entry_block);
 
-        let vars = variables::declare_variables(&node, &mut function_builder, entry_block);
+        // A function returning a struct is passed a pointer to write it to first.
+        let mut params = function_builder.block_params(entry_block).to_vec();
+        let ret_ptr = struct_layout(&node.ret_type, structs).map(|_| params.remove(0));
 
-        let mut translator =
-            translate::FunctionTranslator::new(&fn_map, function_builder, vars, &mut self.module);
+        let vars =
+            variables::declare_variables(node, &mut function_builder, &params, structs, config);
 
-        for stmt in node.body {
-            translator.translate_stmt(&stmt);
-        }
+        let mut translator = translate::FunctionTranslator::new(
+            structs,
+            function_builder,
+            vars,
+            node,
+            &mut self.module,
+            ret_ptr,
+        )
+        .with_null_checks(self.null_checks);
+
+        translator.translate_stmts(&node.body);
 
         translator.builder.seal_all_blocks();
         translator.builder.finalize();
@@ -135,12 +127,16 @@ impl Compiler {
                 Linkage::Export,
                 &self.codegen_context.func.signature,
             )
-            .expect("Failed to create function_id");
+            .map_err(|err| {
+                CompileError::new(node.span, format!("Cannot declare `{}`: {err}", node.name))
+            })?;
 
         // Define the function on the module
         self.module
             .define_function(function_id, &mut self.codegen_context)
-            .expect("Failed to define module function");
+            .map_err(|err| {
+                CompileError::new(node.span, format!("Cannot define `{}`: {err}", node.name))
+            })?;
 
         match codegen::verify_function(&self.codegen_context.func, self.module.isa()) {
             Ok(result) => println!("Successfully verified function: {:?}", result),
@@ -148,5 +144,6 @@ impl Compiler {
         }
         // Clear the function context ready for the next function
         self.module.clear_context(&mut self.codegen_context);
+        Ok(())
     }
 }
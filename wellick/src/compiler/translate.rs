@@ -1,392 +1,518 @@
+use crate::parser::ast;
 use crate::parser::ast::EmptyType;
+use crate::sema::layout::size_of;
+use crate::sema::structs::{struct_layout, Structs};
+use crate::sema::typed::{self, Expr, ExprKind, LocalId, NULL_CHECK_HOOK};
 
-use super::ast;
-use super::variables;
-use super::variables::{to_cranelift_type, Variable};
+use super::variables::{copy_struct, to_cranelift_type, Variable};
 
 use cranelift::prelude::AbiParam;
 use cranelift::prelude::InstBuilder;
 use cranelift::prelude::MemFlags;
 use cranelift::prelude::Signature;
+use cranelift::prelude::{FloatCC, IntCC, TrapCode};
+use cranelift::prelude::{StackSlotData, StackSlotKind};
 use cranelift_codegen::ir::{entities::Value, types};
 use cranelift_frontend::FunctionBuilder;
 use cranelift_module::{Linkage, Module};
 use cranelift_object::ObjectModule;
-use std::collections::HashMap;
-use std::process;
 
-/// Module to translate AST into Cranelift IR constructs.
+/// Module to translate the typed tree into Cranelift IR constructs. The
+/// tree has been checked, so translation cannot fail.
 pub struct FunctionTranslator<'a, 'b: 'a> {
-    functions: &'a HashMap<String, ast::FnDecl>,
+    structs: &'a Structs,
     pub(crate) builder: FunctionBuilder<'b>,
-    pub(crate) variables: HashMap<String, variables::Variable>,
+    /// The storage of each local variable, by [`LocalId`].
+    variables: Vec<Variable>,
+    locals: &'a [typed::Local],
     pub(crate) module: &'a mut ObjectModule,
+    ret_type: EmptyType,
+    /// Where to write the return value of a function returning a struct.
+    ret_ptr: Option<Value>,
+    /// Whether to check pointers are not null before dereferencing them.
+    null_checks: bool,
 }
 
 impl<'a, 'b> FunctionTranslator<'a, 'b> {
     pub fn new(
-        functions: &'a HashMap<String, ast::FnDecl>,
+        structs: &'a Structs,
         builder: FunctionBuilder<'b>,
-        variables: HashMap<String, variables::Variable>,
+        variables: Vec<Variable>,
+        func: &'a typed::Function,
         module: &'b mut ObjectModule,
+        ret_ptr: Option<Value>,
     ) -> Self {
         Self {
-            functions,
+            structs,
             builder,
             variables,
+            locals: &func.locals,
             module,
+            ret_type: func.ret_type.clone(),
+            ret_ptr,
+            null_checks: true,
         }
     }
 
-    pub fn translate_if(&mut self, condition: &ast::Expression, if_body: &Vec<ast::Stmt>) -> Value {
+    /// Enable or disable the null pointer checks inserted before dereferences.
+    pub fn with_null_checks(mut self, null_checks: bool) -> Self {
+        self.null_checks = null_checks;
+        self
+    }
+
+    /// Translate an `if`, producing a value of type `ty` when it has one.
+    /// Each branch passes its value to the block following the `if` as a
+    /// block parameter. A branch which returns does not continue to that
+    /// block, and if neither branch does, the code after the `if` is
+    /// unreachable.
+    pub fn translate_if(&mut self, if_expr: &typed::If, ty: Option<&EmptyType>) -> Option<Value> {
         let then_block = self.builder.create_block();
         let merge_block = self.builder.create_block();
-        let cond = self.translate_expr(condition);
+        let else_block = match if_expr.else_body {
+            Some(_) => self.builder.create_block(),
+            None => merge_block,
+        };
+        let result = ty.map(|ty| {
+            self.builder
+                .append_block_param(merge_block, to_cranelift_type(ty))
+        });
+
+        let cond = self.translate_expr(&if_expr.condition);
         self.builder
             .ins()
-            .brif(cond, then_block, &[], merge_block, &[]);
+            .brif(cond, then_block, &[], else_block, &[]);
+
         self.builder.switch_to_block(then_block);
         self.builder.seal_block(then_block);
+        let value = self.translate_block(&if_expr.then_body);
+        if !if_expr.then_body.diverges() {
+            self.builder.ins().jump(merge_block, value.as_slice());
+        }
 
-        for stmt in if_body {
-            match stmt {
-                // If a terminator expression, like return, is encountered, we don't
-                // need to process the rest of the instructions since they're automatically
-                // unreachable code.
-                ast::Stmt::Return(_) => {
-                    self.translate_stmt(&stmt);
-                    self.builder.switch_to_block(merge_block);
-                    self.builder.seal_block(merge_block);
-                    return self.builder.ins().iconst(types::I32, 0);
-                }
-                _ => {
-                    self.translate_stmt(&stmt);
-                }
+        if let Some(else_body) = &if_expr.else_body {
+            self.builder.switch_to_block(else_block);
+            self.builder.seal_block(else_block);
+            let value = self.translate_block(else_body);
+            if !else_body.diverges() {
+                self.builder.ins().jump(merge_block, value.as_slice());
             }
         }
 
-        self.builder.ins().jump(merge_block, &[]);
         self.builder.switch_to_block(merge_block);
         self.builder.seal_block(merge_block);
-        self.builder.ins().iconst(types::I32, 0)
+        result
     }
 
-    pub fn translate_stmt(&mut self, stmt: &ast::Stmt) -> Value {
-        match stmt {
-            ast::Stmt::Assign(expr) => self.translate_assign(expr),
-            ast::Stmt::If(condition, body) => self.translate_if(condition, body),
-            ast::Stmt::Return(expr) => self.translate_return(expr),
-            ast::Stmt::Call(expr) => self.translate_call(expr),
-            ast::Stmt::ReAssign(expr) => self.translate_reassign(expr),
+    /// Translate a block, producing its value when it has one. A block which
+    /// returns has no value, as the code after a `return` has been removed
+    /// during semantic analysis.
+    pub fn translate_block(&mut self, block: &typed::Block) -> Option<Value> {
+        self.translate_stmts(&block.stmts);
+        match &block.value {
+            Some(value) if value.diverges() => {
+                self.translate_diverging(value);
+                None
+            }
+            Some(value) => Some(self.translate_expr(value)),
+            None => None,
         }
     }
 
-    pub fn translate_expr(&mut self, expr: &ast::Expression) -> Value {
-        match expr {
-            ast::Expression::Call(val) => self.translate_call(val),
-            ast::Expression::Literal(val) => match val {
-                ast::Literal::Float(value) => self
-                    .builder
-                    .ins()
-                    .f32const(value.base10_parse::<f32>().unwrap()),
-                ast::Literal::Integer(value) => self
-                    .builder
-                    .ins()
-                    .iconst(types::I32, value.base10_parse::<i64>().unwrap()),
-            },
-            ast::Expression::Identifier(value) => {
-                let var = self
-                    .variables
-                    .get(value)
-                    .expect("No variable with that name could be found");
-
-                match var {
-                    Variable::Stack(var) => {
-                        self.builder
-                            .ins()
-                            .stack_load(to_cranelift_type(&var.ty), var.base, 0)
-                    }
-                    Variable::Register(var) => self.builder.use_var(var.base),
-                }
+    /// Translate an expression which returns on every path, so never
+    /// produces the value it is used as. Its operands are evaluated up to
+    /// the first which returns, the rest are never reached.
+    fn translate_diverging(&mut self, value: &Expr) {
+        match &value.kind {
+            ExprKind::If(if_expr) if if_expr.condition.diverges() => {
+                self.translate_diverging(&if_expr.condition)
             }
-            // Address-Of a value, returns a pointer pointing to the stack slot
-            // of the variable.
-            ast::Expression::AddressOf(value) => {
-                let var = self
-                    .variables
-                    .get(&value.name)
-                    .expect("No variable with that name could be found");
-
-                match var {
-                    Variable::Stack(var) => self.builder.ins().stack_addr(
-                        self.module.target_config().pointer_type(),
-                        var.base,
-                        0,
-                    ),
-                    Variable::Register(_) => panic!("Pointer to register variables unsupported"),
-                }
+            ExprKind::If(if_expr) => {
+                self.translate_if(if_expr, None);
             }
-            // Dereference a pointer and return the value at that address.
-            ast::Expression::DeRef(value) => {
-                let var = self
-                    .variables
-                    .get(value)
-                    .expect("No variable with that name could be found");
-
-                match var {
-                    Variable::Stack(var) => {
-                        let var_type = match &var.ty {
-                            EmptyType::Pointer(ty) => to_cranelift_type(&ty.ty),
-                            ty => {
-                                unimplemented!(
-                                    "unsupported operation, dereferencing type {:?}",
-                                    ty
-                                );
-                            }
-                        };
-
-                        let stack_ptr = self.builder.ins().stack_load(
-                            self.module.target_config().pointer_type(),
-                            var.base,
-                            0,
-                        );
-
-                        self.builder
-                            .ins()
-                            .load(var_type, MemFlags::new(), stack_ptr, 0)
-                    }
-                    Variable::Register(_) => {
-                        panic!("Unsupported operation, dereferencing register variable");
+            ExprKind::Block(block) => {
+                self.translate_block(block);
+            }
+            _ => {
+                for operand in value.operands() {
+                    if operand.diverges() {
+                        return self.translate_diverging(operand);
                     }
+                    self.translate_expr(operand);
                 }
             }
         }
     }
 
-    fn translate_call(&mut self, expr: &ast::Call) -> Value {
-        let expected_sig = match expr.func.as_str() {
-            "iadd" | "isub" | "idiv" | "imul" | "ieq" | "ilteq" | "ilt" | "imod" => Signature {
-                params: vec![AbiParam::new(types::I32), AbiParam::new(types::I32)],
-                returns: vec![AbiParam::new(types::I32)],
-                call_conv: self.module.isa().default_call_conv(),
-            },
-            "println" | "print" => Signature {
-                params: vec![AbiParam::new(types::I32)],
-                returns: vec![AbiParam::new(types::I32)],
-                call_conv: self.module.isa().default_call_conv(),
+    pub fn translate_stmts(&mut self, stmts: &[typed::Stmt]) {
+        for stmt in stmts {
+            self.translate_stmt(stmt);
+        }
+    }
+
+    pub fn translate_stmt(&mut self, stmt: &typed::Stmt) {
+        match &stmt.kind {
+            // There is nothing to store, as the statement is never finished.
+            typed::StmtKind::Let(_, Some(value))
+            | typed::StmtKind::Assign(_, value)
+            | typed::StmtKind::CompoundAssign(_, _, value)
+            | typed::StmtKind::Return(value)
+            | typed::StmtKind::Expr(value)
+                if value.diverges() =>
+            {
+                self.translate_diverging(value)
+            }
+            typed::StmtKind::Let(id, Some(value)) => {
+                let value = self.translate_expr(value);
+                self.store_variable(*id, value);
+            }
+            // The variable is stored to when it is first assigned.
+            typed::StmtKind::Let(_, None) => {}
+            typed::StmtKind::Assign(place, value) => {
+                let value = self.translate_expr(value);
+                self.store_place(place, value);
+            }
+            typed::StmtKind::CompoundAssign(place, op, value) => {
+                self.translate_compound_assign(place, *op, value)
+            }
+            typed::StmtKind::Return(value) => self.translate_return(value),
+            typed::StmtKind::Expr(expr) => {
+                self.translate_expr(expr);
+            }
+            typed::StmtKind::If(if_stmt) => {
+                self.translate_if(if_stmt, None);
+            }
+            typed::StmtKind::Block(block) => {
+                self.translate_block(block);
+            }
+        }
+    }
+
+    pub fn translate_expr(&mut self, expr: &Expr) -> Value {
+        match &expr.kind {
+            ExprKind::Int(value) => {
+                let ty = to_cranelift_type(&expr.ty);
+                self.builder.ins().iconst(ty, *value)
+            }
+            ExprKind::Float(value) => match to_cranelift_type(&expr.ty) {
+                types::F32 => self.builder.ins().f32const(*value as f32),
+                _ => self.builder.ins().f64const(*value),
             },
-            "print_addr" => Signature {
-                params: vec![AbiParam::new(types::I64)],
-                returns: vec![AbiParam::new(types::I64)],
-                call_conv: self.module.isa().default_call_conv(),
+            ExprKind::Null => {
+                let pointer_type = self.pointer_type();
+                self.builder.ins().iconst(pointer_type, 0)
+            }
+            ExprKind::Local(id) => self.load_variable(*id),
+            // Address-Of a value, returns a pointer pointing to the stack slot
+            // of the variable.
+            ExprKind::AddressOf(id) => match self.variables[id.0] {
+                Variable::Stack(slot) => {
+                    let pointer_type = self.pointer_type();
+                    self.builder.ins().stack_addr(pointer_type, slot, 0)
+                }
+                Variable::Register(_) => {
+                    unreachable!("variables which have their address taken live on the stack")
+                }
             },
-            _ => {
-                let func = self
-                    .functions
-                    .get(&expr.func)
-                    .expect(format!("function {} not found", expr.func).as_str());
-
-                for (i, param) in expr.args.iter().enumerate() {
-                    match param {
-                        ast::Expression::AddressOf(param_expr) => {
-                            let arg_mutable = match &func.args.get(i).unwrap().t {
-                                ast::EmptyType::Pointer(ptr) => ptr.mutable,
-                                _ => continue,
-                            };
-
-                            if param_expr.mutable && !arg_mutable {
-                                println!(
-                                    "Expected &{}, got &mut {}",
-                                    param_expr.name, param_expr.name
-                                );
-                                process::exit(1);
-                            }
-
-                            if arg_mutable && !param_expr.mutable {
-                                println!(
-                                    "Expected &mut {}, got &{}",
-                                    param_expr.name, param_expr.name
-                                );
-                                process::exit(1);
-                            }
-                        }
-                        _ => {}
-                    }
+            // Dereference a pointer and return the value at that address.
+            ExprKind::Deref(ptr) => {
+                let ptr = self.translate_expr(ptr);
+                self.check_not_null(ptr);
+                // Structs are represented by their address, which is the pointer itself.
+                if struct_layout(&expr.ty, self.structs).is_some() {
+                    return ptr;
                 }
-
-                Signature {
-                    params: func
-                        .args
-                        .iter()
-                        .map(|arg| AbiParam::new(to_cranelift_type(&arg.t)))
-                        .collect(),
-                    returns: vec![AbiParam::new(to_cranelift_type(&func.ret_type))],
-                    call_conv: self.module.isa().default_call_conv(),
+                self.builder
+                    .ins()
+                    .load(to_cranelift_type(&expr.ty), MemFlags::new(), ptr, 0)
+            }
+            ExprKind::Field(base, offset) => {
+                let addr = self.field_addr(base, *offset);
+                if struct_layout(&expr.ty, self.structs).is_some() {
+                    addr
+                } else {
+                    self.builder
+                        .ins()
+                        .load(to_cranelift_type(&expr.ty), MemFlags::new(), addr, 0)
                 }
             }
-        };
+            ExprKind::StructLiteral(fields) => self.translate_struct_literal(&expr.ty, fields),
+            ExprKind::Call(func, args) => self.translate_call(func, args, &expr.ty),
+            ExprKind::IsNull(ptr) => {
+                let ptr = self.translate_expr(ptr);
+                let is_null = self.builder.ins().icmp_imm(IntCC::Equal, ptr, 0);
+                self.builder.ins().uextend(types::I32, is_null)
+            }
+            ExprKind::Comparison(op, lhs, rhs) => self.translate_comparison(*op, lhs, rhs),
+            ExprKind::If(if_expr) => self
+                .translate_if(if_expr, Some(&expr.ty))
+                .expect("an `if` with a type has a value"),
+            ExprKind::Block(block) => match self.translate_block(block) {
+                Some(value) => value,
+                // The block returns, so its value is never used.
+                None => {
+                    let ty = to_cranelift_type(&expr.ty);
+                    self.zero(ty)
+                }
+            },
+        }
+    }
 
-        let (params, arg_values): (Vec<AbiParam>, Vec<Value>) = expr
-            .args
-            .iter()
-            .map(|arg| {
-                let func_arg = self.translate_expr(arg);
-                let arg_type = self.builder.func.dfg.value_type(func_arg);
-                (AbiParam::new(arg_type), func_arg)
-            })
-            .unzip();
+    /// A placeholder value of type `ty`, for code which can never run.
+    fn zero(&mut self, ty: types::Type) -> Value {
+        match ty {
+            types::F32 => self.builder.ins().f32const(0.0),
+            types::F64 => self.builder.ins().f64const(0.0),
+            ty => self.builder.ins().iconst(ty, 0),
+        }
+    }
 
-        let sig = Signature {
-            params,
-            returns: expected_sig.returns.clone(),
-            call_conv: self.module.isa().default_call_conv(),
+    /// Compare two values of the same type, giving an `i32` which is 1 if
+    /// the comparison holds and 0 if not.
+    fn translate_comparison(&mut self, op: ast::ComparisonOp, lhs: &Expr, rhs: &Expr) -> Value {
+        let is_float = matches!(lhs.ty.resolved(), EmptyType::Float(_));
+        let lhs = self.translate_expr(lhs);
+        let rhs = self.translate_expr(rhs);
+
+        let result = if is_float {
+            let cond = match op {
+                ast::ComparisonOp::Eq => FloatCC::Equal,
+                ast::ComparisonOp::Ne => FloatCC::NotEqual,
+            };
+            self.builder.ins().fcmp(cond, lhs, rhs)
+        } else {
+            let cond = match op {
+                ast::ComparisonOp::Eq => IntCC::Equal,
+                ast::ComparisonOp::Ne => IntCC::NotEqual,
+            };
+            self.builder.ins().icmp(cond, lhs, rhs)
         };
+        self.builder.ins().uextend(types::I32, result)
+    }
 
-        if sig != expected_sig {
-            println!(
-                "Mismatched types for fn \"{}\", expected \"{}\", got \"{}\"",
-                expr.func, expected_sig, sig
-            );
-            process::exit(1);
+    /// Trap if `ptr` is null, when null checks are enabled. The runtime
+    /// reports the null dereference before the program is stopped.
+    fn check_not_null(&mut self, ptr: Value) {
+        if !self.null_checks {
+            return;
         }
 
+        let null_block = self.builder.create_block();
+        let ok_block = self.builder.create_block();
+        self.builder.ins().brif(ptr, ok_block, &[], null_block, &[]);
+
+        self.builder.switch_to_block(null_block);
+        self.builder.seal_block(null_block);
+        let sig = Signature {
+            params: Vec::new(),
+            returns: Vec::new(),
+            call_conv: self.module.isa().default_call_conv(),
+        };
         let callee = self
             .module
-            .declare_function(&expr.func, Linkage::Import, &sig)
-            .expect("Unable to declare function");
+            .declare_function(NULL_CHECK_HOOK, Linkage::Import, &sig)
+            .expect("no function of the program has the name of the null check hook");
         let local_callee = self.module.declare_func_in_func(callee, self.builder.func);
-        let call = self.builder.ins().call(local_callee, &arg_values);
-        self.builder.inst_results(call)[0]
-    }
+        self.builder.ins().call(local_callee, &[]);
+        self.builder.ins().trap(TrapCode::UnreachableCodeReached);
 
-    fn translate_return(&mut self, expr: &ast::Expression) -> Value {
-        let value = self.translate_expr(expr);
-        self.builder.ins().return_(&[value]);
-        value
+        self.builder.switch_to_block(ok_block);
+        self.builder.seal_block(ok_block);
     }
 
-    fn translate_reassign(&mut self, expr: &ast::Local) -> Value {
-        let name = &expr.target.ident;
-        let value = self.translate_expr(&expr.value);
-
-        let var = self.variables.get(name);
-        if var.is_none() {
-            println!("Cannot find value `{name}` in this scope");
-            process::exit(1);
+    /// Read the current value of a variable, wherever it lives. The value of
+    /// a struct is its address.
+    fn load_variable(&mut self, id: LocalId) -> Value {
+        let ty = &self.locals[id.0].ty;
+        match self.variables[id.0] {
+            Variable::Stack(slot) if struct_layout(ty, self.structs).is_some() => {
+                let pointer_type = self.pointer_type();
+                self.builder.ins().stack_addr(pointer_type, slot, 0)
+            }
+            Variable::Stack(slot) => self
+                .builder
+                .ins()
+                .stack_load(to_cranelift_type(ty), slot, 0),
+            Variable::Register(var) => self.builder.use_var(var),
         }
+    }
 
-        match var.unwrap() {
-            Variable::Register(var) => {
-                if !var.mutable {
-                    println!("Cannot mutate immutable variable {name}");
-                    process::exit(1);
+    /// Overwrite the value of a variable, wherever it lives. Structs are
+    /// copied from the address in `value`.
+    fn store_variable(&mut self, id: LocalId, value: Value) {
+        match self.variables[id.0] {
+            Variable::Stack(slot) => match struct_layout(&self.locals[id.0].ty, self.structs) {
+                Some(layout) => {
+                    let pointer_type = self.pointer_type();
+                    let dest = self.builder.ins().stack_addr(pointer_type, slot, 0);
+                    copy_struct(
+                        &mut self.builder,
+                        self.module.target_config(),
+                        dest,
+                        value,
+                        layout,
+                    );
                 }
-                self.builder.def_var(var.base, value);
-            }
-            Variable::Stack(var) => {
-                if !var.mutable {
-                    println!("Cannot mutate immutable variable {name}");
-                    process::exit(1);
+                None => {
+                    self.builder.ins().stack_store(value, slot, 0);
                 }
-                self.builder.ins().stack_store(value, var.base, 0);
+            },
+            Variable::Register(var) => self.builder.def_var(var, value),
+        }
+    }
+
+    /// Write `value` of type `ty` to memory at `addr`, copying structs.
+    fn store_value(&mut self, ty: &EmptyType, value: Value, addr: Value) {
+        match struct_layout(ty, self.structs) {
+            Some(layout) => copy_struct(
+                &mut self.builder,
+                self.module.target_config(),
+                addr,
+                value,
+                layout,
+            ),
+            None => {
+                self.builder.ins().store(MemFlags::new(), value, addr, 0);
             }
         }
+    }
 
-        value
+    fn pointer_type(&self) -> types::Type {
+        self.module.target_config().pointer_type()
     }
 
-    fn translate_assign(&mut self, expr: &ast::Assignment) -> Value {
-        let name = &expr.target.ident;
+    /// Allocate a stack slot for a temporary value of type `ty`, returning its address.
+    fn alloc_temporary(&mut self, ty: &EmptyType) -> Value {
+        let slot = self.builder.create_sized_stack_slot(StackSlotData::new(
+            StackSlotKind::ExplicitSlot,
+            size_of(ty, self.structs),
+        ));
+        let pointer_type = self.pointer_type();
+        self.builder.ins().stack_addr(pointer_type, slot, 0)
+    }
 
-        let var = &self
-            .variables
-            .get(name)
-            .expect(format!("No variable named {}", name).as_str())
-            .clone();
+    /// The address of a field of a struct, or of a struct behind a pointer.
+    fn field_addr(&mut self, base: &Expr, offset: u32) -> Value {
+        // Both a struct value and a pointer to a struct are represented by an address.
+        let addr = self.translate_expr(base);
+        if matches!(base.ty.resolved(), EmptyType::Pointer(_)) {
+            self.check_not_null(addr);
+        }
+        self.builder.ins().iadd_imm(addr, i64::from(offset))
+    }
 
-        let value = match &expr.value {
-            ast::Expression::Literal(literal) => match literal {
-                ast::Literal::Float(val) => match var.ty() {
-                    ast::EmptyType::Float(ast::FloatType::F32) => self
-                        .builder
-                        .ins()
-                        .f32const(val.base10_parse::<f32>().unwrap()),
-                    ast::EmptyType::Float(ast::FloatType::F64) => self
-                        .builder
-                        .ins()
-                        .f64const(val.base10_parse::<f64>().unwrap()),
-                    _ => {
-                        println!("Cannot convert {:?} to {:?}", val, var.ty());
-                        process::exit(1);
-                    }
-                },
-                ast::Literal::Integer(val) => match var.ty() {
-                    ast::EmptyType::Integer(ast::IntegerType::I32) => self
-                        .builder
-                        .ins()
-                        .iconst(types::I32, val.base10_parse::<i64>().unwrap()),
-                    ast::EmptyType::Integer(ast::IntegerType::I64) => self
-                        .builder
-                        .ins()
-                        .iconst(types::I64, val.base10_parse::<i64>().unwrap()),
-                    ast::EmptyType::Integer(ast::IntegerType::PointerSize) => self
-                        .builder
-                        .ins()
-                        .iconst(types::I64, val.base10_parse::<i64>().unwrap()),
-                    ast::EmptyType::Pointer(_) => self
-                        .builder
-                        .ins()
-                        .iconst(types::I64, val.base10_parse::<i64>().unwrap()),
-                    _ => {
-                        println!("Cannot convert {:?} to {:?}", val, var.ty());
-                        process::exit(1);
-                    }
-                },
-            },
-            ast::Expression::AddressOf(addr_of) => {
-                // Check that the mutability of the pointer matches the mutability of the data.
-
-                let assign_target = &self
-                    .variables
-                    .get(&addr_of.name)
-                    .expect(format!("No variable named {}", &addr_of.name).as_str())
-                    .clone();
-
-                let var_mutable = match assign_target {
-                    Variable::Stack(stack_var) => stack_var.mutable,
-                    Variable::Register(reg_var) => reg_var.mutable,
-                };
-
-                if let ast::EmptyType::Pointer(ptr) = &expr.var_type {
-                    if !var_mutable && ptr.mutable {
-                        println!("Cannot declare mutable pointer to {}, as it has not been declared mutable", &addr_of.name);
-                        process::exit(1);
-                    }
-                }
-                self.translate_expr(&expr.value)
-            }
-            value => self.translate_expr(value),
+    /// Build a struct in a temporary stack slot, returning its address.
+    fn translate_struct_literal(&mut self, ty: &EmptyType, fields: &[(u32, Expr)]) -> Value {
+        let addr = self.alloc_temporary(ty);
+        for (offset, value) in fields {
+            let field_value = self.translate_expr(value);
+            let field_addr = self.builder.ins().iadd_imm(addr, i64::from(*offset));
+            self.store_value(&value.ty, field_value, field_addr);
+        }
+        addr
+    }
+
+    /// Emit a call to a function. A struct is returned through a hidden
+    /// pointer to memory allocated by the caller, passed as the first
+    /// argument.
+    fn translate_call(&mut self, name: &str, args: &[Expr], ret_type: &EmptyType) -> Value {
+        let mut arg_values: Vec<Value> = args.iter().map(|arg| self.translate_expr(arg)).collect();
+        if struct_layout(ret_type, self.structs).is_some() {
+            let ret_ptr = self.alloc_temporary(ret_type);
+            arg_values.insert(0, ret_ptr);
+        }
+
+        let sig = Signature {
+            params: arg_values
+                .iter()
+                .map(|value| AbiParam::new(self.builder.func.dfg.value_type(*value)))
+                .collect(),
+            returns: vec![AbiParam::new(to_cranelift_type(ret_type))],
+            call_conv: self.module.isa().default_call_conv(),
         };
+        let callee = self
+            .module
+            .declare_function(name, Linkage::Import, &sig)
+            .expect("calls are checked against the signature during semantic analysis");
+        let local_callee = self.module.declare_func_in_func(callee, self.builder.func);
+        let call = self.builder.ins().call(local_callee, &arg_values);
+        self.builder.inst_results(call)[0]
+    }
 
-        match var {
-            Variable::Register(_) => {
-                unimplemented!("unsupported operation, assignment to register variable")
-            }
-            Variable::Stack(var) => {
-                let value_type = self.builder.func.dfg.value_type(value);
-                if value_type != to_cranelift_type(&var.ty) {
-                    println!(
-                        "Cannot convert type from {} to {}",
-                        value_type,
-                        to_cranelift_type(&var.ty)
-                    );
-                    process::exit(1);
-                };
+    fn translate_return(&mut self, value: &Expr) {
+        let mut value = self.translate_expr(value);
+        // Structs are copied into the memory provided by the caller, and the
+        // address is handed back.
+        if let Some(ret_ptr) = self.ret_ptr {
+            let ret_type = self.ret_type.clone();
+            self.store_value(&ret_type, value, ret_ptr);
+            value = ret_ptr;
+        }
+        self.builder.ins().return_(&[value]);
+    }
+
+    /// Translate an assignment such as `x += 1`, which reads the place, applies
+    /// the operator and writes the result back.
+    fn translate_compound_assign(&mut self, place: &Expr, op: ast::CompoundOp, value: &Expr) {
+        let value = self.translate_expr(value);
+        let current = self.translate_expr(place);
+        let ins = self.builder.ins();
+        let result = match (place.ty.resolved(), op) {
+            (EmptyType::Float(_), ast::CompoundOp::Add) => ins.fadd(current, value),
+            (EmptyType::Float(_), ast::CompoundOp::Sub) => ins.fsub(current, value),
+            (EmptyType::Float(_), ast::CompoundOp::Mul) => ins.fmul(current, value),
+            (EmptyType::Float(_), _) => ins.fdiv(current, value),
+            (_, ast::CompoundOp::Add) => ins.iadd(current, value),
+            (_, ast::CompoundOp::Sub) => ins.isub(current, value),
+            (_, ast::CompoundOp::Mul) => ins.imul(current, value),
+            (_, ast::CompoundOp::Div) => ins.sdiv(current, value),
+            (_, ast::CompoundOp::Rem) => ins.srem(current, value),
+            (_, ast::CompoundOp::BitAnd) => ins.band(current, value),
+            (_, ast::CompoundOp::BitOr) => ins.bor(current, value),
+            (_, ast::CompoundOp::BitXor) => ins.bxor(current, value),
+            (_, ast::CompoundOp::Shl) => ins.ishl(current, value),
+            (_, ast::CompoundOp::Shr) => ins.sshr(current, value),
+        };
+        self.store_place(place, result);
+    }
 
-                self.builder.ins().stack_store(value, var.base, 0);
+    /// Write `value` to a place.
+    fn store_place(&mut self, place: &Expr, value: Value) {
+        match &place.kind {
+            ExprKind::Local(id) => self.store_variable(*id, value),
+            ExprKind::Field(base, offset) => {
+                let addr = self.field_addr(base, *offset);
+                self.store_value(&place.ty, value, addr);
             }
+            ExprKind::Deref(ptr) => {
+                let addr = self.translate_expr(ptr);
+                self.check_not_null(addr);
+                self.store_value(&place.ty, value, addr);
+            }
+            kind => unreachable!("{:?} is not a place expression", kind),
         }
+    }
+}
+
+/// The Cranelift signature of a function. Functions returning a struct take
+/// a pointer to write it to as their first parameter, and return that
+/// pointer.
+pub(crate) fn cranelift_signature(
+    func: &typed::Function,
+    structs: &Structs,
+    module: &ObjectModule,
+) -> Signature {
+    let mut params: Vec<AbiParam> = func
+        .params
+        .iter()
+        .map(|id| AbiParam::new(to_cranelift_type(&func.local(*id).ty)))
+        .collect();
+    if struct_layout(&func.ret_type, structs).is_some() {
+        params.insert(0, AbiParam::new(module.target_config().pointer_type()));
+    }
 
-        value
+    Signature {
+        params,
+        returns: vec![AbiParam::new(to_cranelift_type(&func.ret_type))],
+        call_conv: module.isa().default_call_conv(),
     }
 }
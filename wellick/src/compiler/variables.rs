@@ -1,16 +1,21 @@
-use super::ast;
-use super::ast::{FloatType, IntegerType};
+use crate::parser::ast;
+use crate::parser::ast::{FloatType, IntegerType};
+use crate::sema::layout::size_of;
+use crate::sema::structs::{struct_layout, StructLayout, Structs};
+use crate::sema::typed;
+use cranelift::prelude::InstBuilder;
+use cranelift::prelude::MemFlags;
 use cranelift::prelude::StackSlotData;
 use cranelift_codegen::ir::StackSlot;
-use cranelift_codegen::ir::{types, Block};
+use cranelift_codegen::ir::{types, Value};
+use cranelift_codegen::isa::TargetFrontendConfig;
 use cranelift_frontend::FunctionBuilder;
 use cranelift_frontend::Variable as cranelift_Variable;
-use std::collections::HashMap;
 
 /// Helper function to convert the EmptyType AST node to
 /// a valid Cranelift IR type.
 pub(crate) fn to_cranelift_type(t: &ast::EmptyType) -> types::Type {
-    match t {
+    match t.resolved() {
         ast::EmptyType::Float(FloatType::F32) => types::F32,
         ast::EmptyType::Float(FloatType::F64) => types::F64,
         ast::EmptyType::Integer(IntegerType::I32) => types::I32,
@@ -19,159 +24,89 @@ pub(crate) fn to_cranelift_type(t: &ast::EmptyType) -> types::Type {
         ast::EmptyType::Integer(IntegerType::PointerSize) => types::I64,
         // TODO: This also only works for platforms with a 64bit pointer size.
         ast::EmptyType::Pointer(_) => types::I64,
+        // Structs are passed around by their address.
+        ast::EmptyType::Named(_) => types::I64,
+        ast::EmptyType::Alias(_) => unreachable!("aliases are resolved"),
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct StackVar {
-    pub name: String,
-    pub ty: ast::EmptyType,
-    pub base: StackSlot,
-    pub mutable: bool,
-}
-
-#[derive(Debug, Clone)]
-pub struct RegVar {
-    pub name: String,
-    pub ty: ast::EmptyType,
-    pub base: cranelift_Variable,
-    pub mutable: bool,
-}
-
-#[derive(Debug, Clone)]
+/// Where a local variable lives.
+#[derive(Debug, Clone, Copy)]
 pub enum Variable {
-    Stack(StackVar),
-    Register(RegVar),
-}
-
-impl StackVar {
-    fn new(name: String, ty: ast::EmptyType, base: StackSlot, mutable: bool) -> Self {
-        Self {
-            name,
-            ty,
-            mutable,
-            base,
-        }
-    }
-
-    fn alloc(
-        name: String,
-        ty: ast::EmptyType,
-        mutable: bool,
-        builder: &mut FunctionBuilder,
-        index: &mut usize,
-        variables: &mut HashMap<String, Variable>,
-    ) -> Self {
-        if variables.contains_key(&name) {
-            panic!("Cannot re-declare variable {}", name);
-        }
-
-        let stack_slot = builder.create_sized_stack_slot(StackSlotData::new(
-            cranelift::prelude::StackSlotKind::ExplicitSlot,
-            to_cranelift_type(&ty).bytes(),
-        ));
-
-        let var = Self::new(name.clone(), ty, stack_slot, mutable);
-        variables.insert(name, Variable::Stack(var.clone()));
-        *index += 1;
-        var
-    }
-}
-
-impl RegVar {
-    fn new(name: String, ty: ast::EmptyType, base: cranelift_Variable, mutable: bool) -> Self {
-        Self {
-            name,
-            ty,
-            mutable,
-            base,
-        }
-    }
-
-    fn alloc(
-        name: String,
-        ty: ast::EmptyType,
-        mutable: bool,
-        builder: &mut FunctionBuilder,
-        index: &mut usize,
-        variables: &mut HashMap<String, Variable>,
-    ) -> Self {
-        if variables.contains_key(&name) {
-            panic!("Cannot re-declare variable {}", name);
-        }
-
-        let var_ref = cranelift_Variable::from_u32(*index as u32);
-        builder.declare_var(var_ref, to_cranelift_type(&ty));
-
-        let var = Self::new(name.clone(), ty, var_ref, mutable);
-        variables.insert(name, Variable::Register(var.clone()));
-        *index += 1;
-        var
-    }
-}
-
-impl Variable {
-    pub fn ty(&self) -> ast::EmptyType {
-        match self {
-            Variable::Stack(var) => var.ty.clone(),
-            Variable::Register(var) => var.ty.clone(),
-        }
-    }
+    /// In memory, for structs and variables which have their address taken.
+    Stack(StackSlot),
+    Register(cranelift_Variable),
 }
 
+/// Create the storage of every local variable of a function, indexed by
+/// [`typed::LocalId`], and store the value of each parameter in it.
+///
+/// A variable is spilled to the stack if its address is taken, or if it is
+/// a struct, otherwise it is kept in a register. `params` holds the value of
+/// each parameter. Structs are passed by address, and are copied so that the
+/// callee cannot modify the caller's value.
 pub fn declare_variables(
-    node: &ast::FnDecl,
+    func: &typed::Function,
     builder: &mut FunctionBuilder,
-    entry_block: Block,
-) -> HashMap<String, Variable> {
-    let args = &node.args;
-    let mut variables = HashMap::<String, Variable>::new();
-    let mut index: usize = 0;
-
-    let params = builder.block_params(entry_block).to_vec();
-    for (i, arg) in args.iter().enumerate() {
-        let val = params[i];
-        let var = RegVar::alloc(
-            arg.name.clone(),
-            arg.t.clone(),
-            false,
-            builder,
-            &mut index,
-            &mut variables,
-        );
-        builder.def_var(var.base, val);
+    params: &[Value],
+    structs: &Structs,
+    config: TargetFrontendConfig,
+) -> Vec<Variable> {
+    let variables: Vec<Variable> = func
+        .locals
+        .iter()
+        .enumerate()
+        .map(|(index, local)| {
+            let is_struct = struct_layout(&local.ty, structs).is_some();
+            if is_struct || local.address_taken {
+                Variable::Stack(builder.create_sized_stack_slot(StackSlotData::new(
+                    cranelift::prelude::StackSlotKind::ExplicitSlot,
+                    size_of(&local.ty, structs),
+                )))
+            } else {
+                let var = cranelift_Variable::from_u32(index as u32);
+                builder.declare_var(var, to_cranelift_type(&local.ty));
+                Variable::Register(var)
+            }
+        })
+        .collect();
+
+    for (id, val) in func.params.iter().zip(params) {
+        match variables[id.0] {
+            Variable::Stack(slot) => match struct_layout(&func.local(*id).ty, structs) {
+                Some(layout) => {
+                    let dest = builder.ins().stack_addr(config.pointer_type(), slot, 0);
+                    copy_struct(builder, config, dest, *val, layout);
+                }
+                None => {
+                    builder.ins().stack_store(*val, slot, 0);
+                }
+            },
+            Variable::Register(var) => builder.def_var(var, *val),
+        }
     }
 
-    for expr in &node.body {
-        declare_variables_in_stmt(expr, builder, &mut index, &mut variables);
-    }
     variables
 }
 
-/// Recursively descend through the AST, translating all implicit
-/// variable declarations.
-fn declare_variables_in_stmt(
-    expr: &ast::Stmt,
+/// Copy a struct from the memory at `src` to the memory at `dest`.
+pub(crate) fn copy_struct(
     builder: &mut FunctionBuilder,
-    index: &mut usize,
-    variables: &mut HashMap<String, Variable>,
+    config: TargetFrontendConfig,
+    dest: Value,
+    src: Value,
+    layout: &StructLayout,
 ) {
-    match expr {
-        ast::Stmt::Assign(ref assignment) => {
-            StackVar::alloc(
-                assignment.target.ident.clone(),
-                assignment.var_type.clone(),
-                assignment.mutable,
-                builder,
-                index,
-                variables,
-            );
-        }
-        ast::Stmt::If(ref _condition, ref if_body) => {
-            for stmt in if_body {
-                declare_variables_in_stmt(stmt, builder, index, variables);
-            }
-        }
-        _ => {}
-    }
+    let align = layout.align as u8;
+    builder.emit_small_memory_copy(
+        config,
+        dest,
+        src,
+        layout.size as u64,
+        align,
+        align,
+        // The source and destination are the same when a variable is assigned to itself.
+        false,
+        MemFlags::new(),
+    );
 }
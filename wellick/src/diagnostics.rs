@@ -0,0 +1,187 @@
+use crate::parser::ast::Span;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+// Errors are reported the way rustc does, pointing at the code responsible:
+//
+// error: Cannot mutate immutable variable y
+//  --> examples/main.wellick:4:5
+//   |
+// 4 |     y = 2;
+//   |     ^^^^^
+
+/// An error in the program being compiled. Every phase of the compiler
+/// reports errors with this type, rather than stopping the process, so the
+/// caller decides how to show them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompileError {
+    pub message: String,
+    /// The code responsible, if the error is caused by any particular code.
+    pub span: Option<Span>,
+    /// Further explanation, shown below the code.
+    pub notes: Vec<String>,
+}
+
+/// The result of a phase of the compiler.
+pub type CompileResult<T> = Result<T, CompileError>;
+
+impl CompileError {
+    pub fn new(span: Span, message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            span: Some(span),
+            notes: Vec::new(),
+        }
+    }
+
+    /// An error which is not caused by any particular code, such as being
+    /// unable to write the object file.
+    pub fn without_span(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            span: None,
+            notes: Vec::new(),
+        }
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    /// Format the error along with the location and line of source code it
+    /// points at.
+    pub fn render(&self, sources: &SourceMap) -> String {
+        render("error", &self.message, self.span, &self.notes, sources)
+    }
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for CompileError {}
+
+/// Fail with an error at `span`.
+pub fn error<T>(span: Span, message: impl Into<String>) -> CompileResult<T> {
+    Err(CompileError::new(span, message))
+}
+
+/// A likely mistake in the program, which does not stop it from being
+/// compiled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Warning {
+    pub message: String,
+    pub span: Span,
+    /// Further explanation, shown below the code.
+    pub notes: Vec<String>,
+}
+
+impl Warning {
+    pub fn new(span: Span, message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            span,
+            notes: Vec::new(),
+        }
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    /// Format the warning the same way as an error.
+    pub fn render(&self, sources: &SourceMap) -> String {
+        render(
+            "warning",
+            &self.message,
+            Some(self.span),
+            &self.notes,
+            sources,
+        )
+    }
+}
+
+/// Format a diagnostic, labelled e.g. `error`, along with the location and
+/// line of source code it points at and any notes.
+fn render(
+    label: &str,
+    message: &str,
+    span: Option<Span>,
+    notes: &[String],
+    sources: &SourceMap,
+) -> String {
+    let (span, file) = match span.and_then(|span| Some((span, sources.get(span.file)?))) {
+        Some(found) => found,
+        None => {
+            let notes: String = notes
+                .iter()
+                .map(|note| format!("\n  = note: {note}"))
+                .collect();
+            return format!("{label}: {message}{notes}");
+        }
+    };
+
+    let contents = &file.contents;
+    let start = span.start.min(contents.len());
+    let line_start = contents[..start].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = contents[start..]
+        .find('\n')
+        .map_or(contents.len(), |i| start + i);
+    let line = contents[..start].matches('\n').count() + 1;
+    let source_line = contents[line_start..line_end].trim_end_matches('\r');
+
+    // Columns count characters rather than bytes. A span running over
+    // several lines is underlined up to the end of its first line.
+    let column = contents[line_start..start].chars().count() + 1;
+    let end = span.end.clamp(start, line_end);
+    let width = contents[start..end].chars().count().max(1);
+
+    let gutter = " ".repeat(line.to_string().len());
+    let notes: String = notes
+        .iter()
+        .map(|note| format!("\n{gutter} = note: {note}"))
+        .collect();
+    format!(
+        "{label}: {message}\n{gutter}--> {}:{line}:{column}\n{gutter} |\n{line} | {source_line}\n{gutter} | {}{}{notes}",
+        file.path.to_string_lossy(),
+        " ".repeat(column - 1),
+        "^".repeat(width),
+    )
+}
+
+/// A source file of the program being compiled.
+struct SourceFile {
+    path: PathBuf,
+    contents: String,
+}
+
+/// Every source file of a program. The `file` of a span is an index into
+/// this list.
+#[derive(Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+}
+
+impl SourceMap {
+    /// Add a source file, returning the index its spans refer to it by.
+    pub fn add_file(&mut self, path: &Path, contents: String) -> usize {
+        self.files.push(SourceFile {
+            path: path.to_path_buf(),
+            contents,
+        });
+        self.files.len() - 1
+    }
+
+    /// The text of a source file.
+    pub fn contents(&self, file: usize) -> &str {
+        &self.files[file].contents
+    }
+
+    fn get(&self, file: usize) -> Option<&SourceFile> {
+        self.files.get(file)
+    }
+}
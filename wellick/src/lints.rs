@@ -0,0 +1,249 @@
+use crate::diagnostics::{CompileError, Warning};
+use crate::parser::ast::{Attribute, Span};
+use std::collections::HashMap;
+use std::fmt;
+
+// Lints are checks for code which is valid but likely to be a mistake. Each
+// lint has a level deciding whether it is reported as a warning, as an error
+// or not at all. The level is set for the whole program on the command line,
+// e.g. `-D unused_variables`, and for a function by an attribute on it, e.g.
+// `#[allow(unused_variables)]`.
+
+/// How a lint is reported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Allow,
+    Warn,
+    Deny,
+}
+
+impl Level {
+    /// The level set by the attribute with the given name, e.g. `allow`.
+    fn from_attribute(name: &str) -> Option<Level> {
+        match name {
+            "allow" => Some(Level::Allow),
+            "warn" => Some(Level::Warn),
+            "deny" => Some(Level::Deny),
+            _ => None,
+        }
+    }
+
+    /// The command line flag which sets this level.
+    fn flag(self) -> &'static str {
+        match self {
+            Level::Allow => "-A",
+            Level::Warn => "-W",
+            Level::Deny => "-D",
+        }
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Level::Allow => "allow",
+            Level::Warn => "warn",
+            Level::Deny => "deny",
+        })
+    }
+}
+
+/// A lint, named by attributes and command line flags.
+#[derive(Debug)]
+pub struct Lint {
+    pub name: &'static str,
+    /// The level of the lint where it is not set otherwise.
+    pub default: Level,
+}
+
+/// `let` bindings which are never read.
+pub static UNUSED_VARIABLES: Lint = Lint {
+    name: "unused_variables",
+    default: Level::Warn,
+};
+
+/// Variables declared `mut` which are never modified.
+pub static UNUSED_MUT: Lint = Lint {
+    name: "unused_mut",
+    default: Level::Warn,
+};
+
+/// Functions which are never called, other than those whose names start
+/// with an underscore.
+pub static DEAD_CODE: Lint = Lint {
+    name: "dead_code",
+    default: Level::Warn,
+};
+
+/// Calls whose result is discarded, other than those to the printing
+/// builtins.
+pub static UNUSED_RESULTS: Lint = Lint {
+    name: "unused_results",
+    default: Level::Warn,
+};
+
+/// Code following a `return`.
+pub static UNREACHABLE_CODE: Lint = Lint {
+    name: "unreachable_code",
+    default: Level::Warn,
+};
+
+/// Every lint. A new lint only has to be declared above and listed here to
+/// be controlled by attributes and on the command line.
+pub static LINTS: &[&Lint] = &[
+    &UNUSED_VARIABLES,
+    &UNUSED_MUT,
+    &DEAD_CODE,
+    &UNUSED_RESULTS,
+    &UNREACHABLE_CODE,
+];
+
+/// The name which sets the level of every lint that would otherwise warn,
+/// e.g. `-D warnings`.
+const WARNINGS: &str = "warnings";
+
+fn is_lint_name(name: &str) -> bool {
+    name == WARNINGS || LINTS.iter().any(|lint| lint.name == name)
+}
+
+/// Check the attributes of a function, which may only set the level of
+/// known lints.
+pub fn check_attributes(attributes: &[Attribute]) -> Vec<CompileError> {
+    let mut errors = Vec::new();
+    for attr in attributes {
+        if Level::from_attribute(&attr.name.ident).is_none() {
+            errors.push(CompileError::new(
+                attr.span,
+                format!("Cannot find attribute `{}`", attr.name),
+            ));
+            continue;
+        }
+        for name in &attr.args {
+            if !is_lint_name(&name.ident) {
+                errors.push(CompileError::new(
+                    name.span,
+                    format!("Unknown lint `{name}`"),
+                ));
+            }
+        }
+    }
+    errors
+}
+
+/// The level of each lint in the whole program, as set on the command line.
+#[derive(Debug, Default)]
+pub struct LintLevels {
+    levels: HashMap<&'static str, Level>,
+    warnings: Option<Level>,
+}
+
+impl LintLevels {
+    /// Set the level of the lint with the given name, overriding any level
+    /// it was given before.
+    pub fn set(&mut self, name: &str, level: Level) -> Result<(), CompileError> {
+        if name == WARNINGS {
+            self.warnings = Some(level);
+            return Ok(());
+        }
+        match LINTS.iter().find(|lint| lint.name == name) {
+            Some(lint) => {
+                self.levels.insert(lint.name, level);
+                Ok(())
+            }
+            None => Err(CompileError::without_span(format!("Unknown lint `{name}`"))),
+        }
+    }
+
+    /// The level of a lint in code with the given attributes, along with a
+    /// note explaining where it was set. An attribute naming the lint
+    /// overrides the command line, and `warnings` only affects the lints
+    /// which are not allowed there.
+    fn level(&self, lint: &Lint, attributes: &[Attribute]) -> (Level, String) {
+        let (level, note) = self.command_line_level(lint);
+        let from_attribute = attributes.iter().rev().find_map(|attr| {
+            let attr_level = Level::from_attribute(&attr.name.ident)?;
+            let name = attr.args.iter().find(|arg| {
+                arg.ident == lint.name || (arg.ident == WARNINGS && level != Level::Allow)
+            })?;
+            Some((attr_level, name))
+        });
+        match from_attribute {
+            Some((level, name)) => (
+                level,
+                format!("the lint level is set by `#[{level}({name})]`"),
+            ),
+            None => (level, note),
+        }
+    }
+
+    fn command_line_level(&self, lint: &Lint) -> (Level, String) {
+        if let Some(&level) = self.levels.get(lint.name) {
+            let note = format!(
+                "requested on the command line with `{} {}`",
+                level.flag(),
+                lint.name
+            );
+            return (level, note);
+        }
+        match self.warnings {
+            Some(level) if lint.default == Level::Warn => (
+                level,
+                format!(
+                    "`{flag} {}` implied by `{flag} {WARNINGS}`",
+                    lint.name,
+                    flag = level.flag(),
+                ),
+            ),
+            _ => (
+                lint.default,
+                format!("`#[{}({})]` on by default", lint.default, lint.name),
+            ),
+        }
+    }
+}
+
+/// Collects the lints found in a program as warnings or errors, depending on
+/// their level where they are found.
+pub struct LintReporter<'a> {
+    levels: &'a LintLevels,
+    pub warnings: Vec<Warning>,
+    pub errors: Vec<CompileError>,
+}
+
+impl<'a> LintReporter<'a> {
+    pub fn new(levels: &'a LintLevels) -> Self {
+        Self {
+            levels,
+            warnings: Vec::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    /// Report a lint in code with the given attributes, those of the
+    /// function it is found in. A lint found more than once at the same
+    /// place, such as in each instance of a generic function, is reported
+    /// once.
+    pub fn report(
+        &mut self,
+        lint: &Lint,
+        attributes: &[Attribute],
+        span: Span,
+        message: impl Into<String>,
+    ) {
+        match self.levels.level(lint, attributes) {
+            (Level::Allow, _) => {}
+            (Level::Warn, note) => {
+                let warning = Warning::new(span, message).with_note(note);
+                if !self.warnings.contains(&warning) {
+                    self.warnings.push(warning);
+                }
+            }
+            (Level::Deny, note) => {
+                let err = CompileError::new(span, message).with_note(note);
+                if !self.errors.contains(&err) {
+                    self.errors.push(err);
+                }
+            }
+        }
+    }
+}
@@ -1,57 +1,183 @@
 mod compiler;
+mod diagnostics;
+mod lints;
 mod parser;
+mod sema;
 
 use clap::Parser;
-use nom::{multi::many0, IResult};
+use diagnostics::{CompileError, SourceMap, Warning};
+use lints::{Level, LintLevels};
 use parser::ast::*;
-use parser::stmts::function;
+use parser::helpers::Input;
+use parser::items::source_file;
+use std::collections::{HashSet, VecDeque};
 use std::fs;
+use std::path::Path;
+use std::process::ExitCode;
 
 #[derive(Parser)]
 struct Cli {
     path: std::path::PathBuf,
+    /// Build without the runtime checks of a debug build, such as null checks.
+    #[arg(long)]
+    release: bool,
+    /// Warn about a lint, or about every lint with `warnings`.
+    #[arg(short = 'W', value_name = "LINT")]
+    warn: Vec<String>,
+    /// Allow a lint, or every lint with `warnings`.
+    #[arg(short = 'A', value_name = "LINT")]
+    allow: Vec<String>,
+    /// Report a lint as an error, or every lint with `warnings`.
+    #[arg(short = 'D', value_name = "LINT")]
+    deny: Vec<String>,
 }
 
-fn parse(input: &str) -> Result<Vec<FnDecl>, String> {
-    let parser = many0(function)(input);
-    match parser {
-        IResult::Ok((remaining, result)) => {
-            if remaining.len() > 0 {
-                let msg = format!("failed to parse, unparsed tokens in file: {remaining}");
-                return Err(msg);
+impl Cli {
+    /// The lint levels set by the `-A`, `-W` and `-D` flags. Where a lint is
+    /// named by more than one, the strictest level wins, and a lint named
+    /// by itself is not affected by the level given to `warnings`.
+    fn lint_levels(&self) -> Result<LintLevels, Vec<CompileError>> {
+        let mut levels = LintLevels::default();
+        let mut errors = Vec::new();
+        let flags = [
+            (&self.allow, Level::Allow),
+            (&self.warn, Level::Warn),
+            (&self.deny, Level::Deny),
+        ];
+        for (names, level) in flags {
+            for name in names {
+                if let Err(err) = levels.set(name, level) {
+                    errors.push(err);
+                }
             }
-            println!("Successfully parsed program");
-            return Ok(result);
         }
-        _ => Err("failed to parse, an unknown error occurred".to_owned()),
+        if errors.is_empty() {
+            Ok(levels)
+        } else {
+            Err(errors)
+        }
     }
 }
 
-fn main() -> Result<(), String> {
-    let args = Cli::parse();
-    let contents = fs::read_to_string(args.path.clone()).expect(
-        format!(
-            "unable to read file {}",
-            args.path.clone().to_string_lossy()
-        )
-        .as_str(),
-    );
-    let ast = match parse(contents.as_str()) {
-        Ok(ast) => {
-            println!("Successfully constructed AST");
-            ast
+/// Parse a source file, reporting every syntax error in it.
+fn parse_file(path: &Path, sources: &mut SourceMap) -> Result<Vec<Item>, Vec<CompileError>> {
+    let contents = fs::read_to_string(path).map_err(|err| {
+        vec![CompileError::without_span(format!(
+            "Unable to read file {}: {err}",
+            path.to_string_lossy()
+        ))]
+    })?;
+    let file = sources.add_file(path, contents);
+    let items = source_file(Input::new_extra(sources.contents(file), file))?;
+    println!("Successfully parsed program");
+    Ok(items)
+}
+
+/// Parse the root file and every module it imports, directly or through
+/// another module. `import math;` refers to `math.wellick` in the same
+/// directory as the root file. Each module is parsed once, however many
+/// files import it. Every module is parsed even if another has syntax
+/// errors, so that all of them are reported at once.
+fn parse_program(root: &Path, sources: &mut SourceMap) -> Result<Vec<Module>, Vec<CompileError>> {
+    let dir = root.parent().unwrap_or_else(|| Path::new("."));
+    let mut errors = Vec::new();
+    let parse = |path: &Path, sources: &mut SourceMap, errors: &mut Vec<CompileError>| {
+        parse_file(path, sources).unwrap_or_else(|errs| {
+            errors.extend(errs);
+            Vec::new()
+        })
+    };
+    let mut modules = vec![Module {
+        name: None,
+        items: parse(root, sources, &mut errors),
+    }];
+
+    let mut seen = HashSet::new();
+    let mut queue = VecDeque::new();
+    let module_path = |name: &str| dir.join(format!("{name}.wellick"));
+    loop {
+        let module = modules.last().expect("the root module is always parsed");
+        let own_path = match &module.name {
+            Some(name) => module_path(name),
+            None => root.to_path_buf(),
+        };
+        for item in &module.items {
+            if let Item::Import(name) = item {
+                if module_path(&name.ident) == own_path {
+                    errors.push(CompileError::new(
+                        name.span,
+                        format!("The module `{name}` cannot import itself"),
+                    ));
+                } else if seen.insert(name.ident.clone()) {
+                    queue.push_back(name.clone());
+                }
+            }
         }
-        Err(err) => {
-            println!("SyntaxError: {}", err);
-            return Err("Failed to compile".to_owned());
+
+        let name = match queue.pop_front() {
+            Some(name) => name,
+            None => break,
+        };
+        let path = module_path(&name.ident);
+        if !path.exists() {
+            errors.push(CompileError::new(
+                name.span,
+                format!(
+                    "Cannot find module `{name}`, expected it at {}",
+                    path.to_string_lossy()
+                ),
+            ));
+            continue;
         }
-    };
+        modules.push(Module {
+            name: Some(name.ident),
+            items: parse(&path, sources, &mut errors),
+        });
+    }
+
+    if errors.is_empty() {
+        Ok(modules)
+    } else {
+        Err(errors)
+    }
+}
+
+fn compile(
+    args: &Cli,
+    sources: &mut SourceMap,
+    warnings: &mut Vec<Warning>,
+) -> Result<(), Vec<CompileError>> {
+    let levels = args.lint_levels()?;
+    let ast = parse_program(&args.path, sources)?;
+    println!("Successfully constructed AST");
 
     dbg!(&ast);
 
-    let aot_compiler = compiler::Compiler::default();
+    let program = sema::analyze(ast, &levels, warnings)?;
 
-    aot_compiler.compile(ast)?;
+    let aot_compiler = compiler::Compiler::default().with_null_checks(!args.release);
 
-    Ok(())
+    aot_compiler.compile(program).map_err(|err| vec![err])
+}
+
+fn main() -> ExitCode {
+    let args = Cli::parse();
+    let mut sources = SourceMap::default();
+    let mut warnings = Vec::new();
+    let result = compile(&args, &mut sources, &mut warnings);
+    for warning in &warnings {
+        println!("{}\n", warning.render(&sources));
+    }
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(errors) => {
+            for err in &errors {
+                println!("{}\n", err.render(&sources));
+            }
+            if errors.len() > 1 {
+                println!("error: aborting due to {} previous errors", errors.len());
+            }
+            ExitCode::FAILURE
+        }
+    }
 }
@@ -1,16 +1,42 @@
 use std::{
     fmt::{self, Formatter},
+    hash::{Hash, Hasher},
+    ops::Deref,
     str::FromStr,
 };
 
+/// The range of bytes in a source file which a node was parsed from, used to
+/// point diagnostics at the code responsible.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    /// The index of the file in the program's list of source files.
+    pub file: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    /// The smallest span covering both `self` and `other`, which must be in
+    /// the same file.
+    pub fn to(self, other: Span) -> Span {
+        Span {
+            file: self.file,
+            start: self.start.min(other.start),
+            end: self.end.max(other.end),
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct IntegerLiteral {
     token: String,
+    pub span: Span,
 }
 
 #[derive(Clone)]
 pub struct FloatLiteral {
     token: String,
+    pub span: Span,
 }
 
 impl fmt::Debug for IntegerLiteral {
@@ -26,9 +52,10 @@ impl fmt::Debug for FloatLiteral {
 }
 
 impl IntegerLiteral {
-    pub fn new(token: &str) -> Self {
+    pub fn new(token: &str, span: Span) -> Self {
         Self {
             token: token.to_string(),
+            span,
         }
     }
 
@@ -38,9 +65,10 @@ impl IntegerLiteral {
 }
 
 impl FloatLiteral {
-    pub fn new(token: &str) -> Self {
+    pub fn new(token: &str, span: Span) -> Self {
         Self {
             token: token.to_string(),
+            span,
         }
     }
 
@@ -57,7 +85,16 @@ pub enum Literal {
     Integer(IntegerLiteral),
 }
 
-#[derive(Debug, Clone)]
+impl Literal {
+    pub fn span(&self) -> Span {
+        match self {
+            Literal::Float(literal) => literal.span,
+            Literal::Integer(literal) => literal.span,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Pointer {
     pub ty: EmptyType,
     pub mutable: bool,
@@ -69,66 +106,220 @@ impl Pointer {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum IntegerType {
     I32,
     I64,
     PointerSize,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum FloatType {
     F32,
     F64,
 }
 
+/// A use of a type alias, `type Handle = *mut i64;`, once resolved. It is
+/// the same type as `ty`, but keeps its name for diagnostics.
+#[derive(Debug, Clone)]
+pub struct Alias {
+    pub name: String,
+    pub ty: EmptyType,
+}
+
 #[derive(Debug, Clone)]
 pub enum EmptyType {
     Float(FloatType),
     Integer(IntegerType),
     Pointer(Box<Pointer>),
+    // A type referred to by name, such as a generic type parameter.
+    Named(String),
+    Alias(Box<Alias>),
+}
+
+impl EmptyType {
+    /// The type with any aliases looked through, which should be used when
+    /// matching on the kind of a type.
+    pub fn resolved(&self) -> &EmptyType {
+        match self {
+            EmptyType::Alias(alias) => alias.ty.resolved(),
+            ty => ty,
+        }
+    }
+
+    /// The type with every alias in it replaced by the type it names, e.g.
+    /// `*mut i64` for `*Handle`.
+    pub fn without_aliases(&self) -> EmptyType {
+        match self.resolved() {
+            EmptyType::Pointer(ptr) => EmptyType::Pointer(Box::new(Pointer::new(
+                ptr.ty.without_aliases(),
+                ptr.mutable,
+            ))),
+            ty => ty.clone(),
+        }
+    }
+}
+
+// An alias is equal to the type it names, so types are compared and hashed
+// with aliases looked through.
+impl PartialEq for EmptyType {
+    fn eq(&self, other: &Self) -> bool {
+        match (self.resolved(), other.resolved()) {
+            (EmptyType::Float(a), EmptyType::Float(b)) => a == b,
+            (EmptyType::Integer(a), EmptyType::Integer(b)) => a == b,
+            (EmptyType::Pointer(a), EmptyType::Pointer(b)) => a == b,
+            (EmptyType::Named(a), EmptyType::Named(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for EmptyType {}
+
+impl Hash for EmptyType {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let ty = self.resolved();
+        std::mem::discriminant(ty).hash(state);
+        match ty {
+            EmptyType::Float(float) => float.hash(state),
+            EmptyType::Integer(int) => int.hash(state),
+            EmptyType::Pointer(ptr) => ptr.hash(state),
+            EmptyType::Named(name) => name.hash(state),
+            EmptyType::Alias(_) => unreachable!("aliases are resolved"),
+        }
+    }
+}
+
+impl fmt::Display for EmptyType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            EmptyType::Float(FloatType::F32) => f.write_str("f32"),
+            EmptyType::Float(FloatType::F64) => f.write_str("f64"),
+            EmptyType::Integer(IntegerType::I32) => f.write_str("i32"),
+            EmptyType::Integer(IntegerType::I64) => f.write_str("i64"),
+            EmptyType::Integer(IntegerType::PointerSize) => f.write_str("isize"),
+            EmptyType::Pointer(ptr) if ptr.mutable => write!(f, "*mut {}", ptr.ty),
+            EmptyType::Pointer(ptr) => write!(f, "*{}", ptr.ty),
+            EmptyType::Named(name) => f.write_str(name),
+            EmptyType::Alias(alias) => f.write_str(&alias.name),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct Assignment {
     pub target: Name,
-    pub value: Expression,
-    pub var_type: EmptyType,
+    /// The initial value, or `None` if the variable is assigned later.
+    pub value: Option<Expression>,
+    /// The declared type, or `None` if it is to be inferred from the value.
+    pub var_type: Option<EmptyType>,
     pub mutable: bool,
+    pub span: Span,
 }
 
 impl Assignment {
-    pub fn new(target: Name, var_type: EmptyType, value: Expression, mutable: bool) -> Self {
+    pub fn new(
+        target: Name,
+        var_type: Option<EmptyType>,
+        value: Option<Expression>,
+        mutable: bool,
+        span: Span,
+    ) -> Self {
         Self {
             target,
             var_type,
             value,
             mutable,
+            span,
         }
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct Local {
-    pub target: Name,
+    /// The place being assigned to, either a variable, a field of one or the
+    /// target of a pointer.
+    pub target: Expression,
     pub value: Expression,
+    pub span: Span,
 }
 
 impl Local {
-    pub fn new(target: Name, value: Expression) -> Self {
-        Self { target, value }
+    pub fn new(target: Expression, value: Expression, span: Span) -> Self {
+        Self {
+            target,
+            value,
+            span,
+        }
     }
 }
 
+/// The operator of a compound assignment, e.g. the `+` of `x += 1`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompoundOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    BitAnd,
+    BitOr,
+    BitXor,
+    Shl,
+    Shr,
+}
+
+impl fmt::Display for CompoundOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let op = match self {
+            CompoundOp::Add => "+=",
+            CompoundOp::Sub => "-=",
+            CompoundOp::Mul => "*=",
+            CompoundOp::Div => "/=",
+            CompoundOp::Rem => "%=",
+            CompoundOp::BitAnd => "&=",
+            CompoundOp::BitOr => "|=",
+            CompoundOp::BitXor => "^=",
+            CompoundOp::Shl => "<<=",
+            CompoundOp::Shr => ">>=",
+        };
+        write!(f, "{op}")
+    }
+}
+
+/// A compound assignment, e.g. `x += 1`, which updates a place in terms of
+/// its current value.
+#[derive(Debug, Clone)]
+pub struct CompoundAssign {
+    pub target: Expression,
+    pub op: CompoundOp,
+    pub value: Expression,
+    pub span: Span,
+}
+
+/// A `return` statement, e.g. `return x;`.
+#[derive(Debug, Clone)]
+pub struct Return {
+    pub value: Expression,
+    pub span: Span,
+}
+
 #[derive(Debug, Clone)]
 pub struct FnArg {
     pub name: String,
     pub t: EmptyType,
+    pub mutable: bool,
+    pub span: Span,
 }
 
 impl FnArg {
-    pub fn new(name: String, t: EmptyType) -> Self {
-        Self { name, t }
+    pub fn new(name: String, t: EmptyType, mutable: bool, span: Span) -> Self {
+        Self {
+            name,
+            t,
+            mutable,
+            span,
+        }
     }
 }
 
@@ -136,11 +327,31 @@ impl FnArg {
 pub struct AddressOf {
     pub name: String,
     pub mutable: bool,
+    pub span: Span,
 }
 
 impl AddressOf {
-    pub fn new(name: String, mutable: bool) -> Self {
-        AddressOf { name, mutable }
+    pub fn new(name: String, mutable: bool, span: Span) -> Self {
+        AddressOf {
+            name,
+            mutable,
+            span,
+        }
+    }
+}
+
+/// A generic type parameter and the traits it must implement,
+/// e.g. `T: Shape` in `fn total<T: Shape>`.
+#[derive(Debug, Clone)]
+pub struct TypeParam {
+    pub name: String,
+    pub bounds: Vec<String>,
+    pub span: Span,
+}
+
+impl TypeParam {
+    pub fn new(name: String, bounds: Vec<String>, span: Span) -> Self {
+        Self { name, bounds, span }
     }
 }
 
@@ -148,37 +359,254 @@ impl AddressOf {
 #[derive(Debug, Clone)]
 pub struct FnDecl {
     pub name: String,
+    /// Whether the function is visible outside of its module.
+    pub public: bool,
+    /// Whether the function is a `const fn`, which may also be evaluated at
+    /// compile time.
+    pub is_const: bool,
+    /// The text of the `///` comment preceding the declaration. It is not
+    /// used by the compiler, but is kept for generating API documentation.
+    #[allow(dead_code)]
+    pub doc: Option<String>,
+    /// The attributes preceding the declaration, e.g. `#[allow(dead_code)]`.
+    pub attributes: Vec<Attribute>,
+    /// The generic type parameters, e.g. `T` in `fn max<T>`.
+    pub type_params: Vec<TypeParam>,
     pub args: Vec<FnArg>,
     pub ret_type: EmptyType,
     pub body: Vec<Stmt>,
+    /// The span of the signature, from `fn` up to the body.
+    pub span: Span,
 }
 
 impl FnDecl {
-    pub fn new(name: String, args: Vec<FnArg>, ret_type: EmptyType, body: Vec<Stmt>) -> Self {
+    pub fn new(
+        name: String,
+        type_params: Vec<TypeParam>,
+        args: Vec<FnArg>,
+        ret_type: EmptyType,
+        body: Vec<Stmt>,
+        span: Span,
+    ) -> Self {
         Self {
             name,
+            public: false,
+            is_const: false,
+            doc: None,
+            attributes: Vec::new(),
+            type_params,
             args,
             ret_type,
             body,
+            span,
         }
     }
 }
 
+/// An attribute of a function, such as the lints it allows.
+/// e.g. #[allow(unused_variables, dead_code)]
+#[derive(Debug, Clone)]
+pub struct Attribute {
+    pub name: Name,
+    pub args: Vec<Name>,
+    pub span: Span,
+}
+
+/// A field of a struct declaration, e.g. `x: f64`.
+#[derive(Debug, Clone)]
+pub struct Field {
+    pub name: String,
+    pub ty: EmptyType,
+    #[allow(dead_code)]
+    pub doc: Option<String>,
+    pub span: Span,
+}
+
+/// Struct declaration AST node, e.g. `struct Point { x: f64, y: f64 }`.
+#[derive(Debug, Clone)]
+pub struct StructDecl {
+    pub name: String,
+    pub public: bool,
+    #[allow(dead_code)]
+    pub doc: Option<String>,
+    pub fields: Vec<Field>,
+    /// The span of the name, which identifies the struct in diagnostics.
+    pub span: Span,
+}
+
+/// Trait declaration AST node, e.g. `trait Shape { fn area(&self) -> f64; }`.
+/// The methods are declared without a body.
+#[derive(Debug, Clone)]
+pub struct TraitDecl {
+    pub name: String,
+    pub public: bool,
+    #[allow(dead_code)]
+    pub doc: Option<String>,
+    pub methods: Vec<FnDecl>,
+    /// The span of the name, which identifies the trait in diagnostics.
+    pub span: Span,
+}
+
+/// An `impl` block, holding the methods and associated functions of a type,
+/// or the implementation of a trait for a type in `impl Shape for Circle`.
+#[derive(Debug, Clone)]
+pub struct ImplBlock {
+    pub trait_name: Option<String>,
+    pub ty: String,
+    pub methods: Vec<FnDecl>,
+    #[allow(dead_code)]
+    pub doc: Option<String>,
+    /// The span of the header, e.g. `impl Shape for Circle`.
+    pub span: Span,
+}
+
+/// A constant, e.g. `const LIMIT: i32 = 100;`. The value is computed at
+/// compile time.
+#[derive(Debug, Clone)]
+pub struct ConstDecl {
+    pub name: String,
+    pub public: bool,
+    #[allow(dead_code)]
+    pub doc: Option<String>,
+    pub ty: EmptyType,
+    pub value: Expression,
+    pub span: Span,
+}
+
+/// A type alias, e.g. `type Handle = *mut i64;`.
+#[derive(Debug, Clone)]
+pub struct TypeAlias {
+    pub name: String,
+    pub public: bool,
+    #[allow(dead_code)]
+    pub doc: Option<String>,
+    pub ty: EmptyType,
+    pub span: Span,
+}
+
+/// A top-level item of a source file.
+#[derive(Debug, Clone)]
+pub enum Item {
+    Function(FnDecl),
+    Struct(StructDecl),
+    Trait(TraitDecl),
+    Impl(ImplBlock),
+    Const(ConstDecl),
+    TypeAlias(TypeAlias),
+    /// A module in a sibling file, from `import math;` or `mod math;`.
+    Import(Name),
+}
+
+/// The items of one source file. The root file has no name, every other
+/// file is a module named after the file.
+#[derive(Debug, Clone)]
+pub struct Module {
+    pub name: Option<String>,
+    pub items: Vec<Item>,
+}
+
 #[derive(Debug, Clone)]
 pub struct Call {
+    /// The function being called, either a plain name such as `max` or a path
+    /// to an associated function such as `Point::new`.
     pub func: String,
+    /// Explicit generic type arguments, e.g. `i32` in `max::<i32>(a, b)`.
+    pub type_args: Vec<EmptyType>,
     pub args: Vec<Expression>,
+    pub span: Span,
+}
+
+/// A method call, e.g. `p.len()`.
+#[derive(Debug, Clone)]
+pub struct MethodCall {
+    pub receiver: Box<Expression>,
+    pub method: String,
+    pub args: Vec<Expression>,
+    /// The function implementing the method, filled in once the type of the
+    /// receiver is known.
+    pub func: Option<String>,
+    pub span: Span,
+}
+
+/// A struct literal, e.g. `Point { x: 1.0, y: 2.0 }`.
+#[derive(Debug, Clone)]
+pub struct StructLiteral {
+    pub name: String,
+    pub fields: Vec<(String, Expression)>,
+    pub span: Span,
+}
+
+/// A block of statements, e.g. `{ let x = 1; iadd(x, 2) }`. The value of
+/// the block is its final expression, if it has one.
+#[derive(Debug, Clone)]
+pub struct Block {
+    pub stmts: Vec<Stmt>,
+    pub value: Option<Box<Expression>>,
+    pub span: Span,
+}
+
+impl Block {
+    pub fn new(stmts: Vec<Stmt>, value: Option<Expression>, span: Span) -> Self {
+        Self {
+            stmts,
+            value: value.map(Box::new),
+            span,
+        }
+    }
+
+    /// Whether every path through the block returns from the function, so
+    /// that control never reaches the end of it.
+    pub fn diverges(&self) -> bool {
+        self.value.as_deref().is_some_and(Expression::diverges)
+            || self.stmts.iter().any(Stmt::diverges)
+    }
+}
+
+/// An `if` with an optional `else`. An `else if` is represented by an `else`
+/// block whose value is another `if`.
+#[derive(Debug, Clone)]
+pub struct If {
+    pub condition: Box<Expression>,
+    pub then_body: Block,
+    pub else_body: Option<Block>,
+    pub span: Span,
+}
+
+impl If {
+    pub fn diverges(&self) -> bool {
+        match &self.else_body {
+            _ if self.condition.diverges() => true,
+            Some(else_body) => self.then_body.diverges() && else_body.diverges(),
+            None => false,
+        }
+    }
 }
 
+/// A name as written in the source, such as a variable or a field.
 #[derive(Debug, Clone)]
 pub struct Name {
     pub ident: String,
+    pub span: Span,
 }
 
-#[derive(Debug, Clone)]
-pub struct Constant {
-    pub value: String,
-    pub _type: String,
+impl Name {
+    pub fn new(ident: String, span: Span) -> Self {
+        Self { ident, span }
+    }
+}
+
+impl Deref for Name {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.ident
+    }
+}
+
+impl fmt::Display for Name {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.ident)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -190,20 +618,149 @@ pub enum Expression {
     Literal(Literal),
 
     // A variable identifier e.g. x;
-    Identifier(String),
+    Identifier(Name),
 
     // Address-of a variable e.g. &x; gets the address of x.
     AddressOf(AddressOf),
 
-    // de-referencing a variable e.g. *x;
-    DeRef(String),
+    // de-referencing a variable e.g. *x; The span of the name covers the `*`.
+    DeRef(Name),
+
+    // A struct literal e.g. Point { x: 1.0, y: 2.0 };
+    StructLiteral(StructLiteral),
+
+    // Accessing a field of a struct, or of a struct behind a pointer e.g. p.x;
+    Field(Box<Expression>, Name),
+
+    // A method call e.g. p.len();
+    MethodCall(MethodCall),
+
+    // An if expression e.g. if c { 1 } else { 2 };
+    If(If),
+
+    // A block expression e.g. { let a = 1; iadd(a, 2) };
+    Block(Block),
+
+    // The null pointer, which may have any pointer type e.g. null;
+    Null(Span),
+
+    // A comparison of two values e.g. p == null;
+    Comparison(Comparison),
+}
+
+impl Expression {
+    pub fn span(&self) -> Span {
+        match self {
+            Expression::Call(call) => call.span,
+            Expression::Literal(literal) => literal.span(),
+            Expression::Identifier(name) => name.span,
+            Expression::AddressOf(addr_of) => addr_of.span,
+            Expression::DeRef(name) => name.span,
+            Expression::StructLiteral(literal) => literal.span,
+            Expression::Field(base, name) => base.span().to(name.span),
+            Expression::MethodCall(call) => call.span,
+            Expression::If(if_expr) => if_expr.span,
+            Expression::Block(block) => block.span,
+            Expression::Null(span) => *span,
+            Expression::Comparison(comparison) => comparison.span,
+        }
+    }
+
+    /// Whether control never continues past the expression, because it is,
+    /// or is computed from, a block or `if` which returns on every path.
+    pub fn diverges(&self) -> bool {
+        match self {
+            Expression::If(if_expr) => if_expr.diverges(),
+            Expression::Block(block) => block.diverges(),
+            Expression::Call(call) => call.args.iter().any(Expression::diverges),
+            Expression::MethodCall(call) => {
+                call.receiver.diverges() || call.args.iter().any(Expression::diverges)
+            }
+            Expression::StructLiteral(literal) => {
+                literal.fields.iter().any(|(_, value)| value.diverges())
+            }
+            Expression::Field(base, _) => base.diverges(),
+            Expression::Comparison(comparison) => {
+                comparison.lhs.diverges() || comparison.rhs.diverges()
+            }
+            Expression::Literal(_)
+            | Expression::Identifier(_)
+            | Expression::AddressOf(_)
+            | Expression::DeRef(_)
+            | Expression::Null(_) => false,
+        }
+    }
+}
+
+/// The operator of a comparison.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ComparisonOp {
+    Eq,
+    Ne,
+}
+
+impl fmt::Display for ComparisonOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ComparisonOp::Eq => f.write_str("=="),
+            ComparisonOp::Ne => f.write_str("!="),
+        }
+    }
+}
+
+/// A comparison of two values of the same type, e.g. `p != null`. The result
+/// is 1 if the comparison holds and 0 otherwise.
+#[derive(Debug, Clone)]
+pub struct Comparison {
+    pub op: ComparisonOp,
+    pub lhs: Box<Expression>,
+    pub rhs: Box<Expression>,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone)]
 pub enum Stmt {
-    Return(Expression),
-    If(Expression, Vec<Stmt>),
+    Return(Return),
+    If(If),
     Assign(Assignment),
     ReAssign(Local),
+    CompoundAssign(CompoundAssign),
     Call(Call),
+    MethodCall(MethodCall),
+    Block(Block),
+}
+
+impl Stmt {
+    /// Whether control never continues past the statement, because every
+    /// path through it returns from the function.
+    pub fn diverges(&self) -> bool {
+        match self {
+            Stmt::Return(_) => true,
+            Stmt::Assign(Assignment {
+                value: Some(value), ..
+            })
+            | Stmt::ReAssign(Local { value, .. })
+            | Stmt::CompoundAssign(CompoundAssign { value, .. }) => value.diverges(),
+            Stmt::Call(call) => call.args.iter().any(Expression::diverges),
+            Stmt::MethodCall(call) => {
+                call.receiver.diverges() || call.args.iter().any(Expression::diverges)
+            }
+            Stmt::If(if_stmt) => if_stmt.diverges(),
+            Stmt::Block(block) => block.diverges(),
+            Stmt::Assign(_) => false,
+        }
+    }
+
+    pub fn span(&self) -> Span {
+        match self {
+            Stmt::Return(ret) => ret.span,
+            Stmt::If(if_stmt) => if_stmt.span,
+            Stmt::Assign(assign) => assign.span,
+            Stmt::ReAssign(local) => local.span,
+            Stmt::CompoundAssign(assign) => assign.span,
+            Stmt::Call(call) => call.span,
+            Stmt::MethodCall(call) => call.span,
+            Stmt::Block(block) => block.span,
+        }
+    }
 }
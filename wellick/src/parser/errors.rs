@@ -0,0 +1,228 @@
+use super::ast::Span;
+use super::helpers::{keyword, sp, Input};
+use crate::diagnostics::CompileError;
+use nom::bytes::complete::tag;
+use nom::error::{ContextError, ErrorKind, ParseError};
+use nom::Slice;
+
+/// The error type of every parser.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SyntaxError {
+    /// The next token is not one the parser accepts at this point.
+    Expected {
+        /// The unexpected token.
+        span: Span,
+        /// A description of the unexpected token, e.g. "`}`" or "end of file".
+        found: String,
+        /// Descriptions of the tokens which would have been accepted, e.g.
+        /// "`;`" or "expression".
+        expected: Vec<String>,
+    },
+    /// Errors which have already been reported in full, such as those found
+    /// in the statements of a block before recovering from them.
+    Errors(Vec<CompileError>),
+}
+
+impl SyntaxError {
+    /// An error at the start of `input`, where one of `expected` was wanted.
+    pub fn expected(input: Input, expected: impl Into<String>) -> Self {
+        let (span, found) = token_at(input);
+        SyntaxError::Expected {
+            span,
+            found,
+            expected: vec![expected.into()],
+        }
+    }
+
+    /// The errors to report for this syntax error.
+    pub fn into_errors(self) -> Vec<CompileError> {
+        match self {
+            SyntaxError::Expected {
+                span,
+                found,
+                expected,
+            } => {
+                let message = match expected.as_slice() {
+                    [] => format!("Unexpected {found}"),
+                    [expected] => format!("Expected {expected}, found {found}"),
+                    [rest @ .., last] => {
+                        format!(
+                            "Expected one of {} or {last}, found {found}",
+                            rest.join(", ")
+                        )
+                    }
+                };
+                vec![CompileError::new(span, message)]
+            }
+            SyntaxError::Errors(errors) => errors,
+        }
+    }
+}
+
+impl From<CompileError> for SyntaxError {
+    fn from(err: CompileError) -> Self {
+        SyntaxError::Errors(vec![err])
+    }
+}
+
+impl<'a> ParseError<Input<'a>> for SyntaxError {
+    fn from_error_kind(input: Input<'a>, _kind: ErrorKind) -> Self {
+        let (span, found) = token_at(input);
+        SyntaxError::Expected {
+            span,
+            found,
+            expected: Vec::new(),
+        }
+    }
+
+    fn append(_input: Input<'a>, _kind: ErrorKind, other: Self) -> Self {
+        other
+    }
+
+    /// Of two alternatives which both failed, report the one which got
+    /// furthest. Where both failed at the same token, either of the things
+    /// they expected would do.
+    fn or(self, other: Self) -> Self {
+        match (self, other) {
+            (
+                SyntaxError::Expected {
+                    span,
+                    found,
+                    mut expected,
+                },
+                SyntaxError::Expected {
+                    span: other_span,
+                    expected: other_expected,
+                    ..
+                },
+            ) if span.start == other_span.start => {
+                for item in other_expected {
+                    if !expected.contains(&item) {
+                        expected.push(item);
+                    }
+                }
+                SyntaxError::Expected {
+                    span,
+                    found,
+                    expected,
+                }
+            }
+            (
+                this @ SyntaxError::Expected { span, .. },
+                other @ SyntaxError::Expected {
+                    span: other_span, ..
+                },
+            ) => {
+                if span.start > other_span.start {
+                    this
+                } else {
+                    other
+                }
+            }
+            (errors @ SyntaxError::Errors(_), _) | (_, errors @ SyntaxError::Errors(_)) => errors,
+        }
+    }
+}
+
+impl<'a> ContextError<Input<'a>> for SyntaxError {
+    /// Describe what was expected by the name of the construct, such as
+    /// "expression", when it could not even be started. Errors from within
+    /// the construct are kept as they are.
+    fn add_context(input: Input<'a>, ctx: &'static str, other: Self) -> Self {
+        match other {
+            SyntaxError::Expected {
+                span,
+                found,
+                expected: _,
+            } if span.start == token_at(input).0.start => SyntaxError::Expected {
+                span,
+                found,
+                expected: vec![ctx.to_string()],
+            },
+            other => other,
+        }
+    }
+}
+
+/// The span and description of the token at the start of `input`, after any
+/// whitespace and comments.
+fn token_at(input: Input) -> (Span, String) {
+    let input = sp::<(Input, ErrorKind)>(input).map_or(input, |(rest, _)| rest);
+    let start = input.location_offset();
+    let len = word_len(input).max(input.fragment().chars().next().map_or(0, char::len_utf8));
+    let span = Span {
+        file: input.extra,
+        start,
+        end: start + len,
+    };
+    let found = match len {
+        0 => "end of file".to_string(),
+        _ => format!("`{}`", &input.fragment()[..len]),
+    };
+    (span, found)
+}
+
+/// The length of the identifier, keyword or number at the start of `input`.
+fn word_len(input: Input) -> usize {
+    input
+        .fragment()
+        .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .unwrap_or(input.len())
+}
+
+/// Skip the statement or item at the start of `input`, which failed to
+/// parse, so that parsing can continue after it.
+///
+/// The construct is assumed to end at the first `;` outside of any braces,
+/// or at the closing brace of its body, along with any `else` branches and
+/// a `;` which follow. Parsing also resumes at a line starting with one of
+/// `keywords`, so that a missing `;` does not swallow the next statement,
+/// and before an unmatched `}` which closes the surrounding block.
+pub(crate) fn recover<'a>(mut input: Input<'a>, keywords: &[&str]) -> Input<'a> {
+    let start = input.location_offset();
+    let mut depth = 0usize;
+    loop {
+        let (rest, _) = match sp::<(Input, ErrorKind)>(input) {
+            Ok(skipped) => skipped,
+            // Only an unterminated comment is left.
+            Err(_) => return input.slice(input.len()..),
+        };
+        let new_line =
+            input.fragment()[..rest.location_offset() - input.location_offset()].contains('\n');
+        input = rest;
+        let consumed = input.location_offset() > start;
+
+        let word = &input.fragment()[..word_len(input)];
+        if !word.is_empty() {
+            if depth == 0 && consumed && new_line && keywords.contains(&word) {
+                return input;
+            }
+            input = input.slice(word.len()..);
+            continue;
+        }
+
+        let Some(c) = input.fragment().chars().next() else {
+            return input;
+        };
+        let next = input.slice(c.len_utf8()..);
+        match c {
+            '{' => depth += 1,
+            '}' if depth == 0 && consumed => return input,
+            '}' => {
+                depth = depth.saturating_sub(1);
+                if depth == 0 {
+                    let Ok((after, _)) = sp::<(Input, ErrorKind)>(next) else {
+                        return next.slice(next.len()..);
+                    };
+                    if keyword("else")(after).is_err() {
+                        return tag::<_, _, (Input, ErrorKind)>(";")(after)
+                            .map_or(next, |(after, _)| after);
+                    }
+                }
+            }
+            ';' if depth == 0 => return next,
+            _ => {}
+        }
+        input = next;
+    }
+}
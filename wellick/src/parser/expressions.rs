@@ -1,73 +1,195 @@
-use super::ast::{AddressOf, Call, Expression, Name};
-use super::helpers::{identifier, identifier_to_obj, mutable_qualifier, ws};
+use super::ast::{
+    AddressOf, Call, Comparison, ComparisonOp, Expression, MethodCall, Name, StructLiteral,
+};
+use super::helpers::{
+    identifier, identifier_to_obj, keyword, mutable_qualifier, path, sp, spanned, token, ws, Input,
+    PResult,
+};
 use super::literals::literal;
+use super::stmts::{block, if_expr, type_expr};
 
 use nom::branch::alt;
 use nom::bytes::complete::tag;
-use nom::character::complete::{char, multispace0};
-use nom::combinator::{map, opt};
-use nom::multi::separated_list0;
-use nom::sequence::{delimited, preceded, terminated, tuple};
-use nom::IResult;
+use nom::combinator::{cut, map, opt, value};
+use nom::error::context;
+use nom::multi::{many0, separated_list0, separated_list1};
+use nom::sequence::{delimited, pair, preceded, separated_pair, terminated, tuple};
 
-pub fn func_call(input: &str) -> IResult<&str, Call> {
+/// Parse the arguments of a call, a list of expressions separated by commas
+/// with any optional whitespace (including newlines).
+fn call_args(input: Input) -> PResult<Vec<Expression>> {
+    delimited(
+        ws(token("(")),
+        cut(terminated(
+            separated_list0(ws(token(",")), expression),
+            // The function arguments may be terminated by an optional comma.
+            opt(token(",")),
+        )),
+        cut(ws(token(")"))),
+    )(input)
+}
+
+pub fn func_call(input: Input) -> PResult<Call> {
     map(
-        tuple((
+        spanned(tuple((
             // The function name
+            path,
+            // Optional explicit generic type arguments, e.g. max::<i32>(a, b)
+            opt(preceded(
+                token("::"),
+                delimited(
+                    ws(token("<")),
+                    separated_list1(ws(token(",")), type_expr),
+                    ws(token(">")),
+                ),
+            )),
+            call_args,
+        ))),
+        // The function name may be a path, e.g. `Point::new` or `math::gcd`.
+        |((func, type_args, args), span)| Call {
+            func: func.to_string(),
+            type_args: type_args.unwrap_or_default(),
+            args,
+            span,
+        },
+    )(input)
+}
+
+pub fn reference(input: Input) -> PResult<AddressOf> {
+    map(
+        spanned(tuple((
+            preceded(token("&"), ws(mutable_qualifier)),
             identifier,
-            // The function arguments, a list of identifiers or literals separated
-            // by commas with any optional whitespace (including newlines).
+        ))),
+        |((mutable, identifier), span)| AddressOf::new(identifier.to_string(), mutable, span),
+    )(input)
+}
+
+pub fn dereference(input: Input) -> PResult<Name> {
+    map(
+        spanned(preceded(token("*"), identifier_to_obj)),
+        |(name, span)| Name { span, ..name },
+    )(input)
+}
+
+/// Parse a struct literal, e.g. `Point { x: 1.0, y: 2.0 }`. At least one
+/// field is required, so that `if x {}` is not mistaken for a struct literal.
+pub fn struct_literal(input: Input) -> PResult<StructLiteral> {
+    map(
+        spanned(pair(
+            path,
             delimited(
-                ws(tag("(")),
+                ws(token("{")),
                 terminated(
-                    separated_list0(ws(tag(",")), expression),
-                    // The function arguments may be terminated by an optional comma.
-                    opt(tag(",")),
+                    separated_list1(
+                        ws(token(",")),
+                        separated_pair(identifier, ws(token(":")), expression),
+                    ),
+                    opt(ws(token(","))),
                 ),
-                ws(tag(")")),
+                ws(token("}")),
             ),
         )),
-        |(func, args)| Call {
-            func: func.to_string(),
-            args,
+        |((name, fields), span)| StructLiteral {
+            name: name.to_string(),
+            fields: fields
+                .into_iter()
+                .map(|(field, value)| (field.to_string(), value))
+                .collect(),
+            span,
         },
     )(input)
 }
 
-pub fn reference(input: &str) -> IResult<&str, AddressOf> {
-    map(
-        tuple((preceded(char('&'), ws(mutable_qualifier)), identifier)),
-        |(mutable, identifier)| AddressOf::new(identifier.to_string(), mutable),
-    )(input)
+fn primary(input: Input) -> PResult<Expression> {
+    alt((
+        map(literal, Expression::Literal),
+        map(if_expr, Expression::If),
+        map(block, Expression::Block),
+        map(spanned(keyword("null")), |(_, span)| Expression::Null(span)),
+        map(func_call, Expression::Call),
+        map(struct_literal, Expression::StructLiteral),
+        map(reference, Expression::AddressOf),
+        map(dereference, Expression::DeRef),
+        // A variable, or a constant which may be in another module e.g. math::PI
+        map(spanned(path), |(x, span)| {
+            Expression::Identifier(Name::new(x.to_string(), span))
+        }),
+    ))(input)
+}
+
+fn comparison_op(input: Input) -> PResult<ComparisonOp> {
+    alt((
+        value(ComparisonOp::Eq, tag("==")),
+        value(ComparisonOp::Ne, tag("!=")),
+    ))(input)
 }
 
-pub fn dereference(input: &str) -> IResult<&str, Name> {
-    preceded(char('*'), identifier_to_obj)(input)
+/// Parse an expression, either a single operand or a comparison of two.
+/// Comparisons do not chain, so `a == b == c` is not an expression.
+pub fn expression(input: Input) -> PResult<Expression> {
+    context("expression", comparison)(input)
+}
+
+fn comparison(input: Input) -> PResult<Expression> {
+    map(
+        pair(operand, opt(pair(ws(comparison_op), operand))),
+        |(lhs, rhs)| match rhs {
+            Some((op, rhs)) => Expression::Comparison(Comparison {
+                op,
+                span: lhs.span().to(rhs.span()),
+                lhs: Box::new(lhs),
+                rhs: Box::new(rhs),
+            }),
+            None => lhs,
+        },
+    )(input)
 }
 
-pub fn expression(input: &str) -> IResult<&str, Expression> {
+/// Parse an operand, a primary expression followed by any field accesses
+/// and method calls.
+fn operand(input: Input) -> PResult<Expression> {
     delimited(
-        multispace0,
-        alt((
-            map(literal, |x| Expression::Literal(x)),
-            map(func_call, |x| Expression::Call(x)),
-            map(reference, |x| Expression::AddressOf(x)),
-            map(dereference, |x| Expression::DeRef(x.ident)),
-            map(identifier_to_obj, |x| Expression::Identifier(x.ident)),
-        )),
-        multispace0,
+        sp,
+        map(
+            pair(
+                primary,
+                // Any number of field accesses and method calls, e.g. a.b.len()
+                many0(preceded(
+                    ws(token(".")),
+                    spanned(pair(identifier_to_obj, opt(call_args))),
+                )),
+            ),
+            |(expr, postfix)| {
+                postfix
+                    .into_iter()
+                    .fold(expr, |expr, ((name, args), span)| match args {
+                        Some(args) => Expression::MethodCall(MethodCall {
+                            span: expr.span().to(span),
+                            receiver: Box::new(expr),
+                            method: name.ident,
+                            args,
+                            func: None,
+                        }),
+                        None => Expression::Field(Box::new(expr), name),
+                    })
+            },
+        ),
+        sp,
     )(input)
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::parser::expressions::{dereference, reference};
+    use crate::parser::ast::{ComparisonOp, Expression};
+    use crate::parser::expressions::{dereference, expression, func_call, reference};
+    use crate::parser::helpers::input;
 
     #[test]
     fn test_deref() -> Result<(), String> {
         let derefs = ["*x"];
         for deref in derefs {
-            match dereference(deref) {
+            match dereference(input(deref)) {
                 Ok((remaining, _)) => {
                     assert_eq!(remaining.len(), 0);
                 }
@@ -79,12 +201,91 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_generic_call() {
+        let (remaining, call) = func_call(input("max::<i32>(a, 10)")).unwrap();
+        assert_eq!(remaining.len(), 0);
+        assert_eq!(call.type_args.len(), 1);
+        assert_eq!(call.args.len(), 2);
+
+        let (_, call) = func_call(input("max(a, b)")).unwrap();
+        assert!(call.type_args.is_empty());
+    }
+
+    #[test]
+    fn test_associated_call() {
+        let (remaining, call) = func_call(input("Point::new(1.0, 2.0)")).unwrap();
+        assert_eq!(remaining.len(), 0);
+        assert_eq!(call.func, "Point::new");
+    }
+
+    #[test]
+    fn test_struct_literal() {
+        let (remaining, expr) = expression(input("Point { x: 1.0, y: f(2.0), }")).unwrap();
+        assert_eq!(remaining.len(), 0);
+        match expr {
+            Expression::StructLiteral(lit) => {
+                assert_eq!(lit.name, "Point");
+                assert_eq!(lit.fields.len(), 2);
+            }
+            _ => unreachable!("struct literal parsed as incorrect expression"),
+        }
+    }
+
+    #[test]
+    fn test_postfix() {
+        let (remaining, expr) = expression(input("a.b.len(1).c")).unwrap();
+        assert_eq!(remaining.len(), 0);
+        match expr {
+            Expression::Field(base, field) => {
+                assert_eq!(field.ident, "c");
+                assert!(matches!(*base, Expression::MethodCall(ref call) if call.method == "len"));
+            }
+            _ => unreachable!("field access parsed as incorrect expression"),
+        }
+    }
+
+    #[test]
+    fn test_comparison() {
+        let (remaining, expr) = expression(input("p.next != null")).unwrap();
+        assert_eq!(remaining.len(), 0);
+        match expr {
+            Expression::Comparison(comparison) => {
+                assert_eq!(comparison.op, ComparisonOp::Ne);
+                assert!(matches!(*comparison.lhs, Expression::Field(_, _)));
+                assert!(matches!(*comparison.rhs, Expression::Null(_)));
+            }
+            _ => unreachable!("comparison parsed as incorrect expression"),
+        }
+
+        // `null` is only a keyword on its own, not as part of a name.
+        let (_, expr) = expression(input("nullable")).unwrap();
+        assert!(matches!(expr, Expression::Identifier(ref name) if name.ident == "nullable"));
+    }
+
+    #[test]
+    fn test_spans() {
+        let code = "  f(a).len() != *p";
+        let (_, expr) = expression(input(code)).unwrap();
+        let span = expr.span();
+        assert_eq!(&code[span.start..span.end], "f(a).len() != *p");
+        match expr {
+            Expression::Comparison(comparison) => {
+                let span = comparison.lhs.span();
+                assert_eq!(&code[span.start..span.end], "f(a).len()");
+                let span = comparison.rhs.span();
+                assert_eq!(&code[span.start..span.end], "*p");
+            }
+            _ => unreachable!("comparison parsed as incorrect expression"),
+        }
+    }
+
     #[test]
     fn test_ref() -> Result<(), String> {
         let refs = ["&x", "&mut x"];
 
         for ref_ in refs {
-            match reference(ref_) {
+            match reference(input(ref_)) {
                 Ok((remaining, _)) => {
                     assert_eq!(remaining.len(), 0);
                 }
@@ -1,55 +1,204 @@
-use super::ast::Name;
+use super::ast::{Name, Span};
+use super::errors::SyntaxError;
 use nom::branch::alt;
 use nom::bytes::complete::tag;
-use nom::character::complete::{alpha1, alphanumeric1, multispace0};
-use nom::combinator::{opt, recognize};
-use nom::error::ParseError;
-use nom::multi::many0_count;
-use nom::sequence::delimited;
-use nom::sequence::pair;
+use nom::character::complete::{
+    alpha1, alphanumeric1, anychar, char, multispace1, not_line_ending,
+};
+use nom::combinator::{map, not, opt, recognize, value};
+use nom::error::{context, ErrorKind, ParseError};
+use nom::multi::{many0, many0_count};
+use nom::sequence::{delimited, pair, preceded, terminated};
 use nom::IResult;
+use nom_locate::LocatedSpan;
+
+/// The input to every parser, the text of a source file which keeps track of
+/// the position in it. The extra value is the index of the file, so that the
+/// spans of nodes know which file they came from.
+pub type Input<'a> = LocatedSpan<&'a str, usize>;
+
+/// The result of a parser. Syntax errors are reported as a [`SyntaxError`]
+/// describing what was expected at the text which could not be parsed.
+pub type PResult<'a, O> = IResult<Input<'a>, O, SyntaxError>;
+
+/// A combinator which runs `parser` and also returns the span of the text it
+/// consumed, excluding whitespace at either end.
+pub fn spanned<'a, O>(
+    mut parser: impl FnMut(Input<'a>) -> PResult<'a, O>,
+) -> impl FnMut(Input<'a>) -> PResult<'a, (O, Span)> {
+    move |input: Input<'a>| {
+        let (rest, output) = parser(input)?;
+        let consumed = &input.fragment()[..rest.location_offset() - input.location_offset()];
+        let start = input.location_offset() + consumed.len() - consumed.trim_start().len();
+        let end = input.location_offset() + consumed.trim_end().len();
+        let span = Span {
+            file: input.extra,
+            start,
+            end: end.max(start),
+        };
+        Ok((rest, (output, span)))
+    }
+}
+
+/// Parse a `//` comment up to the end of the line. `///` starts a doc
+/// comment instead, which is not skipped, but `////` is an ordinary comment.
+fn line_comment<'a, E: ParseError<Input<'a>>>(input: Input<'a>) -> IResult<Input<'a>, (), E> {
+    value(
+        (),
+        pair(
+            terminated(tag("//"), not(pair(char('/'), not(char('/'))))),
+            not_line_ending,
+        ),
+    )(input)
+}
+
+/// Parse a `/* */` comment, which may contain other block comments.
+fn block_comment<'a, E: ParseError<Input<'a>>>(input: Input<'a>) -> IResult<Input<'a>, (), E> {
+    let (mut input, _) = tag("/*")(input)?;
+    loop {
+        if let Ok((rest, _)) = tag::<_, _, E>("*/")(input) {
+            return Ok((rest, ()));
+        }
+        if input.starts_with("/*") {
+            (input, _) = block_comment(input)?;
+            continue;
+        }
+        match anychar::<_, E>(input) {
+            Ok((rest, _)) => input = rest,
+            // An unterminated comment is an error, rather than running to the end of the file.
+            Err(_) => return Err(nom::Err::Failure(E::from_error_kind(input, ErrorKind::Eof))),
+        }
+    }
+}
+
+/// Skip any amount of whitespace and comments. This is used everywhere
+/// whitespace is allowed, so comments may appear anywhere between tokens.
+pub(crate) fn sp<'a, E: ParseError<Input<'a>>>(input: Input<'a>) -> IResult<Input<'a>, (), E> {
+    value(
+        (),
+        many0_count(alt((value((), multispace1), line_comment, block_comment))),
+    )(input)
+}
 
 /// From the nom [docs](https://github.com/rust-bakery/nom/blob/main/doc/nom_recipes.md#wrapper-combinators-that-eat-whitespace-before-and-after-a-parser)
 /// A combinator that takes a parser `inner` and produces a parser that also consumes both leading and
-/// trailing whitespace, returning the output of `inner`.
-pub(crate) fn ws<'a, F: 'a, O, E: ParseError<&'a str>>(
+/// trailing whitespace and comments, returning the output of `inner`.
+pub(crate) fn ws<'a, F, O, E: ParseError<Input<'a>>>(
     inner: F,
-) -> impl FnMut(&'a str) -> IResult<&'a str, O, E>
+) -> impl FnMut(Input<'a>) -> IResult<Input<'a>, O, E>
 where
-    F: Fn(&'a str) -> IResult<&'a str, O, E>,
+    F: Fn(Input<'a>) -> IResult<Input<'a>, O, E> + 'a,
 {
-    delimited(multispace0, inner, multispace0)
+    delimited(sp, inner, sp)
 }
 
-pub fn mutable_qualifier(input: &str) -> IResult<&str, bool> {
-    let (i, result) = opt(tag("mut"))(input)?;
-    if result.is_some() {
-        return Ok((i, true));
+/// Parse any number of consecutive `///` doc comment lines, returning their
+/// text with the `///` and a single following space removed.
+/// e.g. /// Add two numbers.
+pub fn doc_comment(input: Input) -> PResult<Option<String>> {
+    map(
+        many0(terminated(
+            preceded(
+                terminated(tag("///"), not(char('/'))),
+                map(not_line_ending, |line: Input| {
+                    let line = *line.fragment();
+                    line.strip_prefix(' ').unwrap_or(line)
+                }),
+            ),
+            sp,
+        )),
+        |lines| (!lines.is_empty()).then(|| lines.join("\n")),
+    )(input)
+}
+
+/// A combinator that parses the punctuation `t`, which is reported as
+/// expected when it is missing.
+pub fn token<'a>(t: &'static str) -> impl Fn(Input<'a>) -> PResult<'a, Input<'a>> {
+    move |input| {
+        tag(t)(input)
+            .map_err(|err| err.map(|_: SyntaxError| SyntaxError::expected(input, format!("`{t}`"))))
     }
-    return Ok((i, false));
+}
+
+/// A combinator that parses the keyword `kw`. The keyword must not be followed by
+/// another identifier character, so that names such as `mutex` are not split in two.
+pub fn keyword<'a>(kw: &'static str) -> impl FnMut(Input<'a>) -> PResult<'a, Input<'a>> {
+    move |input| {
+        terminated(tag(kw), not(alt((alphanumeric1, tag("_")))))(input).map_err(|err| {
+            err.map(|_: SyntaxError| SyntaxError::expected(input, format!("`{kw}`")))
+        })
+    }
+}
+
+/// Parse an optional `mut` keyword.
+pub fn mutable_qualifier(input: Input) -> PResult<bool> {
+    let (i, result) = opt(keyword("mut"))(input)?;
+    Ok((i, result.is_some()))
 }
 
 /// From the nom [docs](https://github.com/rust-bakery/nom/blob/main/doc/nom_recipes.md#rust-style-identifiers)
 /// A parser that mimics Rust style identifiers.
-pub fn identifier(input: &str) -> IResult<&str, &str> {
-    recognize(pair(
-        alt((alpha1, tag("_"))),
-        many0_count(alt((alphanumeric1, tag("_")))),
-    ))(input)
+pub fn identifier(input: Input) -> PResult<Input> {
+    context(
+        "identifier",
+        recognize(pair(
+            alt((alpha1, tag("_"))),
+            many0_count(alt((alphanumeric1, tag("_")))),
+        )),
+    )(input)
+}
+
+/// Parse a path made up of identifiers separated by `::`, such as `max`,
+/// `Point::new` or `math::gcd`.
+pub fn path(input: Input) -> PResult<Input> {
+    recognize(pair(identifier, many0(pair(tag("::"), identifier))))(input)
 }
 
 /// From the nom [docs](https://github.com/rust-bakery/nom/blob/main/doc/nom_recipes.md#rust-style-identifiers)
 /// A parser that mimics Rust style identifiers and returns a Name object.
-pub fn identifier_to_obj(input: &str) -> IResult<&str, Name> {
-    let (i, ident) = recognize(pair(
-        alt((alpha1, tag("_"))),
-        many0_count(alt((alphanumeric1, tag("_")))),
-    ))(input)?;
-
-    Ok((
-        i,
-        Name {
-            ident: ident.to_string(),
-        },
-    ))
+pub fn identifier_to_obj(input: Input) -> PResult<Name> {
+    map(spanned(identifier), |(ident, span)| {
+        Name::new(ident.to_string(), span)
+    })(input)
+}
+
+/// Wrap source code for a parser in a test, as the only file of a program.
+#[cfg(test)]
+pub fn input(code: &str) -> Input<'_> {
+    Input::new_extra(code, 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::helpers::{doc_comment, input, sp, Input};
+
+    #[test]
+    fn test_skip_comments() {
+        let cases = [
+            "  // a comment\n  x",
+            "/* block */x",
+            "/* outer /* inner */ still a comment */ x",
+            "//// not a doc comment\nx",
+            "// trailing comment without a newline\n/**/x",
+        ];
+        for code in cases {
+            let (remaining, _) = sp::<nom::error::Error<Input>>(input(code)).unwrap();
+            assert_eq!(*remaining.fragment(), "x");
+        }
+
+        assert!(sp::<nom::error::Error<Input>>(input("/* unterminated /* */")).is_err());
+        // Doc comments are left for the parser of the item they document.
+        let (remaining, _) = sp::<nom::error::Error<Input>>(input("  /// docs\n")).unwrap();
+        assert_eq!(*remaining.fragment(), "/// docs\n");
+    }
+
+    #[test]
+    fn test_doc_comment() {
+        let (remaining, doc) = doc_comment(input("/// First line.\n///Second line.\n fn")).unwrap();
+        assert_eq!(*remaining.fragment(), "fn");
+        assert_eq!(doc.as_deref(), Some("First line.\nSecond line."));
+
+        let (_, doc) = doc_comment(input("fn")).unwrap();
+        assert!(doc.is_none());
+    }
 }
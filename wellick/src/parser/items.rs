@@ -0,0 +1,502 @@
+use super::ast::{
+    Attribute, ConstDecl, Field, FnDecl, ImplBlock, Item, Name, StructDecl, TraitDecl, TypeAlias,
+};
+use super::errors::{recover, SyntaxError};
+use super::expressions::expression;
+use super::helpers::{
+    doc_comment, identifier_to_obj, keyword, path, sp, spanned, token, ws, Input, PResult,
+};
+use super::stmts::{fn_prototype, function, type_expr};
+
+use crate::diagnostics::CompileError;
+
+use nom::branch::alt;
+use nom::combinator::{cut, map, opt};
+use nom::error::context;
+use nom::multi::{many0, separated_list0};
+use nom::sequence::{delimited, pair, preceded, separated_pair, terminated, tuple};
+
+/// Parse a struct declaration.
+/// e.g. struct Point { x: f64, y: f64 }
+pub fn struct_decl(input: Input) -> PResult<StructDecl> {
+    map(
+        pair(
+            preceded(keyword("struct"), cut(ws(identifier_to_obj))),
+            cut(delimited(
+                ws(token("{")),
+                terminated(
+                    separated_list0(
+                        ws(token(",")),
+                        pair(
+                            doc_comment,
+                            separated_pair(identifier_to_obj, cut(ws(token(":"))), cut(type_expr)),
+                        ),
+                    ),
+                    opt(ws(token(","))),
+                ),
+                ws(token("}")),
+            )),
+        ),
+        |(name, fields)| StructDecl {
+            name: name.ident,
+            public: false,
+            doc: None,
+            fields: fields
+                .into_iter()
+                .map(|(doc, (name, ty))| Field {
+                    name: name.ident,
+                    ty,
+                    doc,
+                    span: name.span,
+                })
+                .collect(),
+            span: name.span,
+        },
+    )(input)
+}
+
+/// Parse a trait declaration.
+/// e.g. trait Shape { fn area(&self) -> f64; }
+pub fn trait_decl(input: Input) -> PResult<TraitDecl> {
+    map(
+        pair(
+            preceded(keyword("trait"), cut(ws(identifier_to_obj))),
+            cut(delimited(
+                ws(token("{")),
+                many0(documented(fn_prototype)),
+                ws(token("}")),
+            )),
+        ),
+        |(name, methods)| TraitDecl {
+            name: name.ident,
+            public: false,
+            doc: None,
+            methods,
+            span: name.span,
+        },
+    )(input)
+}
+
+/// Parse an impl block holding the methods of a type, or the
+/// implementation of a trait for a type.
+/// e.g. impl Point { fn len(&self) -> f64 { ... } }
+/// e.g. impl Shape for Circle { fn area(&self) -> f64 { ... } }
+pub fn impl_block(input: Input) -> PResult<ImplBlock> {
+    map(
+        pair(
+            spanned(pair(
+                preceded(keyword("impl"), cut(ws(path))),
+                opt(preceded(keyword("for"), cut(ws(path)))),
+            )),
+            cut(delimited(
+                ws(token("{")),
+                many0(documented(public_function)),
+                ws(token("}")),
+            )),
+        ),
+        |(((first, second), span), methods)| match second {
+            Some(ty) => ImplBlock {
+                trait_name: Some(first.to_string()),
+                ty: ty.to_string(),
+                methods,
+                doc: None,
+                span,
+            },
+            None => ImplBlock {
+                trait_name: None,
+                ty: first.to_string(),
+                methods,
+                doc: None,
+                span,
+            },
+        },
+    )(input)
+}
+
+/// Parse a constant.
+/// e.g. const LIMIT: i32 = 100;
+pub fn const_decl(input: Input) -> PResult<ConstDecl> {
+    map(
+        tuple((
+            preceded(keyword("const"), ws(identifier_to_obj)),
+            // `const fn` has already been tried, so this must be a constant.
+            cut(preceded(ws(token(":")), type_expr)),
+            cut(delimited(ws(token("=")), expression, token(";"))),
+        )),
+        |(name, ty, value)| ConstDecl {
+            name: name.ident,
+            public: false,
+            doc: None,
+            ty,
+            value,
+            span: name.span,
+        },
+    )(input)
+}
+
+/// Parse a type alias.
+/// e.g. type Handle = *mut i64;
+pub fn type_alias(input: Input) -> PResult<TypeAlias> {
+    map(
+        pair(
+            preceded(keyword("type"), cut(ws(identifier_to_obj))),
+            cut(delimited(ws(token("=")), type_expr, token(";"))),
+        ),
+        |(name, ty)| TypeAlias {
+            name: name.ident,
+            public: false,
+            doc: None,
+            ty,
+            span: name.span,
+        },
+    )(input)
+}
+
+/// Parse an optional `pub` keyword, making an item visible outside of its module.
+fn visibility(input: Input) -> PResult<bool> {
+    map(opt(terminated(keyword("pub"), sp)), |public| {
+        public.is_some()
+    })(input)
+}
+
+/// Attach the doc comment preceding a function declaration to it.
+fn documented<'a>(
+    parser: impl FnMut(Input<'a>) -> PResult<'a, FnDecl>,
+) -> impl FnMut(Input<'a>) -> PResult<'a, FnDecl> {
+    map(pair(doc_comment, parser), |(doc, func)| FnDecl {
+        doc,
+        ..func
+    })
+}
+
+/// Parse an attribute.
+/// e.g. #[allow(unused_variables, dead_code)]
+pub fn attribute(input: Input) -> PResult<Attribute> {
+    map(
+        spanned(preceded(
+            token("#"),
+            cut(delimited(
+                ws(token("[")),
+                pair(
+                    identifier_to_obj,
+                    opt(delimited(
+                        ws(token("(")),
+                        separated_list0(ws(token(",")), ws(identifier_to_obj)),
+                        token(")"),
+                    )),
+                ),
+                ws(token("]")),
+            )),
+        )),
+        |((name, args), span)| Attribute {
+            name,
+            args: args.unwrap_or_default(),
+            span,
+        },
+    )(input)
+}
+
+/// Parse a function declaration which may have attributes and be marked
+/// `pub`.
+fn public_function(input: Input) -> PResult<FnDecl> {
+    map(
+        tuple((many0(terminated(attribute, sp)), visibility, function)),
+        |(attributes, public, func)| FnDecl {
+            public,
+            attributes,
+            ..func
+        },
+    )(input)
+}
+
+/// Parse a reference to a module in a sibling file.
+/// e.g. import math;
+/// e.g. mod util;
+pub fn import(input: Input) -> PResult<Name> {
+    delimited(
+        alt((keyword("import"), keyword("mod"))),
+        cut(ws(identifier_to_obj)),
+        cut(token(";")),
+    )(input)
+}
+
+/// Parse a top-level item of a source file, along with its doc comment.
+pub fn item(input: Input) -> PResult<Item> {
+    context("item", documented_item)(input)
+}
+
+fn documented_item(input: Input) -> PResult<Item> {
+    delimited(
+        sp,
+        map(
+            pair(
+                doc_comment,
+                alt((
+                    map(public_function, Item::Function),
+                    map(pair(visibility, struct_decl), |(public, decl)| {
+                        Item::Struct(StructDecl { public, ..decl })
+                    }),
+                    map(pair(visibility, trait_decl), |(public, decl)| {
+                        Item::Trait(TraitDecl { public, ..decl })
+                    }),
+                    map(impl_block, Item::Impl),
+                    map(pair(visibility, const_decl), |(public, decl)| {
+                        Item::Const(ConstDecl { public, ..decl })
+                    }),
+                    map(pair(visibility, type_alias), |(public, alias)| {
+                        Item::TypeAlias(TypeAlias { public, ..alias })
+                    }),
+                    map(import, Item::Import),
+                )),
+            ),
+            |(doc, item)| match item {
+                Item::Function(func) => Item::Function(FnDecl { doc, ..func }),
+                Item::Struct(decl) => Item::Struct(StructDecl { doc, ..decl }),
+                Item::Trait(decl) => Item::Trait(TraitDecl { doc, ..decl }),
+                Item::Impl(block) => Item::Impl(ImplBlock { doc, ..block }),
+                Item::Const(decl) => Item::Const(ConstDecl { doc, ..decl }),
+                Item::TypeAlias(alias) => Item::TypeAlias(TypeAlias { doc, ..alias }),
+                Item::Import(name) => Item::Import(name),
+            },
+        ),
+        sp,
+    )(input)
+}
+
+/// The keywords which start an item. Parsing resumes at a line starting with
+/// one of them after an item with a syntax error.
+const ITEM_KEYWORDS: &[&str] = &[
+    "pub", "fn", "const", "struct", "trait", "impl", "type", "import", "mod",
+];
+
+/// Parse every item of a source file. An item with a syntax error is skipped
+/// so that parsing can continue, and the errors in every item are reported
+/// together.
+pub fn source_file(mut input: Input) -> Result<Vec<Item>, Vec<CompileError>> {
+    let mut items = Vec::new();
+    let mut errors = Vec::new();
+    loop {
+        // Trailing comments are skipped, even in a file without any items.
+        input = match sp::<SyntaxError>(input) {
+            Ok((rest, _)) => rest,
+            Err(nom::Err::Error(err) | nom::Err::Failure(err)) => {
+                errors.extend(err.into_errors());
+                break;
+            }
+            Err(nom::Err::Incomplete(_)) => unreachable!("only complete parsers are used"),
+        };
+        if input.is_empty() {
+            break;
+        }
+        match item(input) {
+            Ok((rest, item)) => {
+                input = rest;
+                items.push(item);
+            }
+            Err(nom::Err::Error(err) | nom::Err::Failure(err)) => {
+                errors.extend(err.into_errors());
+                input = recover(input, ITEM_KEYWORDS);
+            }
+            Err(nom::Err::Incomplete(_)) => unreachable!("only complete parsers are used"),
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(items)
+    } else {
+        Err(errors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::ast::Item;
+    use crate::parser::helpers::input;
+    use crate::parser::items::{
+        attribute, const_decl, impl_block, item, source_file, struct_decl, trait_decl, type_alias,
+    };
+
+    #[test]
+    fn test_parse_struct() {
+        let (remaining, decl) =
+            struct_decl(input("struct Point { x: f64, y: *mut i32, }")).unwrap();
+        assert_eq!(remaining.len(), 0);
+        assert_eq!(decl.name, "Point");
+        assert_eq!(decl.fields.len(), 2);
+        assert_eq!(decl.fields[1].ty.to_string(), "*mut i32");
+    }
+
+    #[test]
+    fn test_parse_impl() {
+        let code = "impl Point {
+            fn new(x: f64, y: f64) -> Self { return Self { x: x, y: y }; }
+            fn len(&self) -> f64 { return self.x; }
+        }";
+        let (remaining, block) = impl_block(input(code)).unwrap();
+        assert_eq!(remaining.len(), 0);
+        assert_eq!(block.ty, "Point");
+        assert_eq!(block.methods.len(), 2);
+        assert_eq!(block.methods[1].args[0].name, "self");
+        assert!(block.trait_name.is_none());
+    }
+
+    #[test]
+    fn test_parse_trait() {
+        let code = "trait Shape {
+            fn area(&self) -> i32;
+            fn scale(&mut self, by: i32) -> i32;
+        }";
+        let (remaining, decl) = trait_decl(input(code)).unwrap();
+        assert_eq!(remaining.len(), 0);
+        assert_eq!(decl.name, "Shape");
+        assert_eq!(decl.methods.len(), 2);
+
+        let code = "impl Shape for Circle { fn area(&self) -> i32 { return 1; } }";
+        let (remaining, block) = impl_block(input(code)).unwrap();
+        assert_eq!(remaining.len(), 0);
+        assert_eq!(block.trait_name.as_deref(), Some("Shape"));
+        assert_eq!(block.ty, "Circle");
+    }
+
+    #[test]
+    fn test_parse_const() {
+        let (remaining, decl) = const_decl(input("const LIMIT: i64 = iadd(1, 2);")).unwrap();
+        assert_eq!(remaining.len(), 0);
+        assert_eq!(decl.name, "LIMIT");
+        assert_eq!(decl.ty.to_string(), "i64");
+
+        let code = "pub const fn square(x: i32) -> i32 { imul(x, x) }";
+        assert!(matches!(item(input(code)), Ok((_, Item::Function(f))) if f.public && f.is_const));
+        let code = "pub const SIZE: i32 = square(4);";
+        assert!(matches!(item(input(code)), Ok((_, Item::Const(c))) if c.public));
+    }
+
+    #[test]
+    fn test_parse_type_alias() {
+        let (remaining, alias) = type_alias(input("type Handle = *mut i64;")).unwrap();
+        assert_eq!(remaining.len(), 0);
+        assert_eq!(alias.name, "Handle");
+        assert_eq!(alias.ty.to_string(), "*mut i64");
+
+        let code = "pub type Origin = geometry::Point;";
+        assert!(matches!(item(input(code)), Ok((_, Item::TypeAlias(a))) if a.public));
+    }
+
+    #[test]
+    fn test_parse_attribute() {
+        let (remaining, attr) = attribute(input("#[allow(unused_mut, dead_code)]")).unwrap();
+        assert_eq!(remaining.len(), 0);
+        assert_eq!(attr.name.ident, "allow");
+        assert_eq!(attr.args.len(), 2);
+        assert_eq!(attr.args[1].ident, "dead_code");
+
+        let code = "/// Unused.\n#[allow(dead_code)]\n#[inline]\npub fn f() -> i32 { 1 }";
+        match item(input(code)) {
+            Ok((_, Item::Function(f))) => {
+                assert!(f.public);
+                assert_eq!(f.doc.as_deref(), Some("Unused."));
+                assert_eq!(f.attributes.len(), 2);
+                assert!(f.attributes[1].args.is_empty());
+            }
+            result => unreachable!("expected a function, got {:?}", result),
+        }
+        let code = "impl Point { #[allow(unused_variables)] fn f(&self) -> i32 { 1 } }";
+        let (_, block) = impl_block(input(code)).unwrap();
+        assert_eq!(block.methods[0].attributes.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_visibility() {
+        let code = "pub fn gcd(a: i32, b: i32) -> i32 {}";
+        assert!(matches!(item(input(code)), Ok((_, Item::Function(f))) if f.public));
+        let code = "fn gcd(a: i32, b: i32) -> i32 {}";
+        assert!(matches!(item(input(code)), Ok((_, Item::Function(f))) if !f.public));
+        let code = "pub struct Point { x: i32 }";
+        assert!(matches!(item(input(code)), Ok((_, Item::Struct(s))) if s.public));
+
+        let (_, block) = impl_block(input("impl math::Point { pub fn new() -> Self {} }")).unwrap();
+        assert_eq!(block.ty, "math::Point");
+        assert!(block.methods[0].public);
+        // `pub` must be followed by whitespace, so `public` is not the keyword.
+        assert!(item(input("public fn f() -> i32 {}")).is_err());
+    }
+
+    #[test]
+    fn test_parse_doc_comments() {
+        let code = "/// A point in space.
+        /// Measured in metres.
+        pub struct Point {
+            /// Distance along the x axis.
+            x: i32, // Not a doc comment.
+            y: i32,
+        }";
+        let decl = match item(input(code)) {
+            Ok((remaining, Item::Struct(decl))) if remaining.is_empty() => decl,
+            result => panic!("expected a struct, got {:?}", result),
+        };
+        assert_eq!(
+            decl.doc.as_deref(),
+            Some("A point in space.\nMeasured in metres.")
+        );
+        assert_eq!(
+            decl.fields[0].doc.as_deref(),
+            Some("Distance along the x axis.")
+        );
+        assert!(decl.fields[1].doc.is_none());
+
+        let code = "impl Point {
+            /// Make a new point.
+            fn new() -> Self {
+                /// Doc comments on statements are ignored.
+                return Point { x: 0, y: 0 };
+            }
+        }";
+        let (remaining, block) = impl_block(input(code)).unwrap();
+        assert_eq!(remaining.len(), 0);
+        assert_eq!(block.methods[0].doc.as_deref(), Some("Make a new point."));
+    }
+
+    #[test]
+    fn test_parse_items() {
+        let items = [
+            "fn main() -> i32 {}",
+            "struct Unit {}",
+            "trait Empty {}",
+            "impl Unit {}",
+            "impl Empty for Unit {}",
+            "import math;",
+            "mod util;",
+        ];
+        for code in items {
+            let (remaining, _) = item(input(code)).unwrap();
+            assert_eq!(remaining.len(), 0);
+        }
+        assert!(matches!(
+            item(input("struct A { a: i32 }")),
+            Ok((_, Item::Struct(_)))
+        ));
+    }
+
+    #[test]
+    fn test_item_recovery() {
+        let code = "struct Point { x i32 }
+        $$$
+        fn main() -> i32 { 0 }
+        fn broken(a i32) -> i32 { 1 }
+        import math";
+        let errors = source_file(input(code)).unwrap_err();
+        let messages: Vec<_> = errors.iter().map(|err| err.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "Expected `:`, found `i32`",
+                "Expected item, found `$`",
+                "Expected `:`, found `i32`",
+                "Expected `;`, found end of file",
+            ]
+        );
+
+        let items = source_file(input("// Only a comment\nfn main() -> i32 { 0 }\n")).unwrap();
+        assert_eq!(items.len(), 1);
+    }
+}
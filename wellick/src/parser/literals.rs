@@ -4,43 +4,46 @@ use nom::character::complete::{char, one_of};
 use nom::combinator::{map, opt, recognize};
 use nom::multi::{many0, many1};
 use nom::sequence::{preceded, terminated, tuple};
-use nom::IResult;
 
 use super::ast::{FloatLiteral, IntegerLiteral, Literal};
+use super::helpers::{spanned, Input, PResult};
 
-fn hexadecimal(input: &str) -> IResult<&str, Literal> {
+fn hexadecimal(input: Input) -> PResult<Literal> {
     map(
-        preceded(
+        spanned(preceded(
             tag_no_case("0x"),
             recognize(many1(terminated(
                 one_of("0123456789abcdefABCDEF"),
                 many0(char('_')),
             ))),
-        ),
-        |value: &str| Literal::Integer(IntegerLiteral::new(value)),
+        )),
+        |(value, span)| Literal::Integer(IntegerLiteral::new(&value, span)),
     )(input)
 }
 
-fn octal(input: &str) -> IResult<&str, Literal> {
+fn octal(input: Input) -> PResult<Literal> {
     map(
-        preceded(
+        spanned(preceded(
             tag_no_case("0o"),
             recognize(many1(terminated(one_of("01234567"), many0(char('_'))))),
-        ),
-        |value: &str| Literal::Integer(IntegerLiteral::new(value)),
+        )),
+        |(value, span)| Literal::Integer(IntegerLiteral::new(&value, span)),
     )(input)
 }
 
-fn decimal(input: &str) -> IResult<&str, Literal> {
+fn decimal(input: Input) -> PResult<Literal> {
     map(
-        recognize(many1(terminated(one_of("0123456789"), many0(char('_'))))),
-        |value: &str| Literal::Integer(IntegerLiteral::new(value)),
+        spanned(recognize(many1(terminated(
+            one_of("0123456789"),
+            many0(char('_')),
+        )))),
+        |(value, span)| Literal::Integer(IntegerLiteral::new(&value, span)),
     )(input)
 }
 
-fn float(input: &str) -> IResult<&str, Literal> {
+fn float(input: Input) -> PResult<Literal> {
     map(
-        alt((
+        spanned(alt((
             // Case one: .42
             recognize(tuple((
                 char('.'),
@@ -55,17 +58,18 @@ fn float(input: &str) -> IResult<&str, Literal> {
                 decimal,
             ))), // Case three: 42. and 42.42
             recognize(tuple((decimal, char('.'), opt(decimal)))),
-        )),
-        |value: &str| Literal::Float(FloatLiteral::new(value)),
+        ))),
+        |(value, span)| Literal::Float(FloatLiteral::new(&value, span)),
     )(input)
 }
 
-pub fn literal(input: &str) -> IResult<&str, Literal> {
+pub fn literal(input: Input) -> PResult<Literal> {
     alt((float, hexadecimal, octal, decimal))(input)
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::parser::helpers::input;
     use crate::parser::literals::literal;
 
     #[test]
@@ -73,7 +77,7 @@ mod tests {
         let expressions = ["0", "10", "0x10", "0x1F", "0x1f", "0o10", "10.0"];
 
         for expr in expressions {
-            literal(expr).unwrap();
+            literal(input(expr)).unwrap();
         }
     }
 }
@@ -1,5 +1,7 @@
 pub mod ast;
+pub mod errors;
 pub mod expressions;
 pub mod helpers;
+pub mod items;
 pub mod literals;
 pub mod stmts;
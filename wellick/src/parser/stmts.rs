@@ -1,23 +1,40 @@
-use std::process;
-
 use super::ast::{Assignment, EmptyType, Expression, FloatType, IntegerType, Local, Pointer, Stmt};
-use super::ast::{FnArg, FnDecl};
+use super::ast::{Block, CompoundAssign, CompoundOp, FnArg, FnDecl, If, Name, Return, TypeParam};
+use super::errors::{recover, SyntaxError};
 use super::expressions::{expression, func_call};
-use super::helpers::{identifier, identifier_to_obj, mutable_qualifier, ws};
+use super::helpers::{
+    doc_comment, identifier, identifier_to_obj, keyword, mutable_qualifier, path, sp, spanned,
+    token, ws, Input, PResult,
+};
+use crate::diagnostics::CompileError;
 
 use nom::branch::alt;
 use nom::bytes::complete::tag;
-use nom::character::complete::{char, space1};
-use nom::combinator::{map, opt};
-use nom::multi::{many0, separated_list0};
-use nom::sequence::{delimited, preceded, separated_pair, terminated, tuple};
-use nom::IResult;
+use nom::combinator::{cut, map, map_opt, opt, value};
+use nom::error::{context, ParseError};
+use nom::multi::{many0, separated_list0, separated_list1};
+use nom::sequence::{delimited, pair, preceded, separated_pair, terminated, tuple};
+
+/// Parse a type, wherever one is written: in a `let`, a parameter, a return
+/// type, a field or a type argument.
+/// e.g. i64
+/// e.g. *mut Point
+/// e.g. math::Handle
+pub fn type_expr(input: Input) -> PResult<EmptyType> {
+    context("type", type_kind)(input)
+}
 
-pub fn assign_type(input: &str) -> IResult<&str, EmptyType> {
+fn type_kind(input: Input) -> PResult<EmptyType> {
     alt((
         map(
-            alt((tag("f32"), tag("f64"), tag("i32"), tag("i64"), tag("isize"))),
-            |val| match val {
+            alt((
+                keyword("f32"),
+                keyword("f64"),
+                keyword("i32"),
+                keyword("i64"),
+                keyword("isize"),
+            )),
+            |val| match *val.fragment() {
                 "f32" => EmptyType::Float(FloatType::F32),
                 "f64" => EmptyType::Float(FloatType::F64),
                 "i32" => EmptyType::Integer(IntegerType::I32),
@@ -27,152 +44,491 @@ pub fn assign_type(input: &str) -> IResult<&str, EmptyType> {
             },
         ),
         map(
-            tuple((preceded(ws(tag("*")), ws(mutable_qualifier)), arg_type)),
+            tuple((preceded(ws(token("*")), ws(mutable_qualifier)), type_expr)),
             |(mutable, val)| EmptyType::Pointer(Box::new(Pointer::new(val, mutable))),
         ),
+        map(path, |name| EmptyType::Named(name.to_string())),
     ))(input)
 }
 
-pub fn ret_type(input: &str) -> IResult<&str, EmptyType> {
-    alt((
-        map(
-            alt((tag("f32"), tag("f64"), tag("i32"), tag("i64"), tag("isize"))),
-            |val| match val {
-                "f32" => EmptyType::Float(FloatType::F32),
-                "f64" => EmptyType::Float(FloatType::F64),
-                "i32" => EmptyType::Integer(IntegerType::I32),
-                "i64" => EmptyType::Integer(IntegerType::I64),
-                "isize" => EmptyType::Integer(IntegerType::PointerSize),
+/// Parse a block of statements surrounded by braces, optionally ending in an
+/// expression without a semicolon which is the value of the block.
+/// e.g. { let x: i32 = 10; }
+/// e.g. { let x: i32 = 10; iadd(x, 1) }
+/// Doc comments on statements are allowed, but are not kept.
+pub fn block(input: Input) -> PResult<Block> {
+    map(spanned(block_body), |((mut stmts, value), span)| {
+        // An `if` at the end of a block, whose branches have a value, is
+        // the value of the block rather than a statement.
+        let value = value.or_else(|| match stmts.last() {
+            Some(Stmt::If(if_stmt)) if if_stmt.then_body.value.is_some() => match stmts.pop() {
+                Some(Stmt::If(if_stmt)) => Some(Expression::If(if_stmt)),
                 _ => unreachable!(),
             },
-        ),
-        map(
-            tuple((preceded(ws(tag("*")), ws(mutable_qualifier)), arg_type)),
-            |_| {
-                println!("Returning a pointer from a function in Wellick is undefined behaviour.");
-                process::exit(1);
+            _ => None,
+        });
+        Block::new(stmts, value, span)
+    })(input)
+}
+
+/// Parse the statements and value of a block, from its opening brace to its
+/// closing one. A statement with a syntax error is skipped so that parsing
+/// can continue, and the errors in every statement are reported together.
+fn block_body(input: Input) -> PResult<(Vec<Stmt>, Option<Expression>)> {
+    let (mut input, _) = ws(token("{"))(input)?;
+    let mut stmts = Vec::new();
+    let mut errors = Vec::new();
+    let value = loop {
+        (input, _) = preceded(sp, doc_comment)(input)?;
+        let close = match ws(token("}"))(input) {
+            Ok((rest, _)) => {
+                input = rest;
+                break None;
+            }
+            Err(nom::Err::Error(err)) => err,
+            Err(err) => return Err(err),
+        };
+        if input.is_empty() {
+            errors.extend(close.into_errors());
+            return Err(nom::Err::Failure(SyntaxError::Errors(errors)));
+        }
+
+        let err = match stmt(input) {
+            Ok((rest, stmt)) => {
+                input = rest;
+                stmts.push(stmt);
+                continue;
+            }
+            // Otherwise this may be the value at the end of the block.
+            Err(nom::Err::Error(err)) => match terminated(expression, ws(token("}")))(input) {
+                Ok((rest, value)) => {
+                    input = rest;
+                    break Some(value);
+                }
+                Err(nom::Err::Error(other)) => err.or(other).or(close),
+                Err(nom::Err::Failure(other)) => other,
+                Err(incomplete) => return Err(incomplete),
             },
-        ),
-    ))(input)
+            Err(nom::Err::Failure(err)) => err,
+            Err(incomplete) => return Err(incomplete),
+        };
+        errors.extend(err.into_errors());
+        input = recover(input, &["let", "return", "if"]);
+    };
+
+    if !errors.is_empty() {
+        return Err(nom::Err::Failure(SyntaxError::Errors(errors)));
+    }
+    Ok((input, (stmts, value)))
+}
+
+/// Parse an `if` with an optional `else` or `else if`.
+/// e.g. if ilt(a, b) { b } else { a }
+pub fn if_expr(input: Input) -> PResult<If> {
+    map(
+        spanned(preceded(
+            keyword("if"),
+            cut(tuple((
+                ws(expression),
+                block,
+                opt(preceded(
+                    pair(sp, keyword("else")),
+                    cut(alt((
+                        map(preceded(sp, if_expr), |else_if| {
+                            let span = else_if.span;
+                            Block::new(Vec::new(), Some(Expression::If(else_if)), span)
+                        }),
+                        block,
+                    ))),
+                )),
+            ))),
+        )),
+        |((condition, then_body, else_body), span)| If {
+            condition: Box::new(condition),
+            then_body,
+            else_body,
+            span,
+        },
+    )(input)
 }
 
-pub fn arg_type(input: &str) -> IResult<&str, EmptyType> {
+/// Parse the receiver of a method, one of `&self`, `&mut self`, `self` or `mut self`.
+/// The type of the receiver refers to the `Self` type of the surrounding `impl` block.
+fn self_arg(input: Input) -> PResult<FnArg> {
+    let self_type = || EmptyType::Named("Self".to_string());
     alt((
         map(
-            alt((tag("f32"), tag("f64"), tag("i32"), tag("i64"), tag("isize"))),
-            |val| match val {
-                "f32" => EmptyType::Float(FloatType::F32),
-                "f64" => EmptyType::Float(FloatType::F64),
-                "i32" => EmptyType::Integer(IntegerType::I32),
-                "i64" => EmptyType::Integer(IntegerType::I64),
-                "isize" => EmptyType::Integer(IntegerType::PointerSize),
-                _ => unreachable!(),
+            spanned(preceded(
+                token("&"),
+                terminated(ws(mutable_qualifier), keyword("self")),
+            )),
+            move |(mutable, span)| {
+                let ptr = Pointer::new(self_type(), mutable);
+                FnArg::new(
+                    "self".to_string(),
+                    EmptyType::Pointer(Box::new(ptr)),
+                    false,
+                    span,
+                )
             },
         ),
         map(
-            tuple((preceded(ws(tag("*")), ws(mutable_qualifier)), arg_type)),
-            |(mutable, val)| EmptyType::Pointer(Box::new(Pointer::new(val, mutable))),
+            spanned(terminated(ws(mutable_qualifier), keyword("self"))),
+            move |(mutable, span)| FnArg::new("self".to_string(), self_type(), mutable, span),
         ),
     ))(input)
 }
 
-pub fn if_stmt(input: &str) -> IResult<&str, (Expression, Vec<Stmt>)> {
+fn function_args(input: Input) -> PResult<Vec<FnArg>> {
     map(
-        tuple((
-            preceded(tag("if"), ws(expression)),
-            delimited(ws(tag("{")), many0(stmt), ws(tag("}"))),
-        )),
-        |(comparison, if_then)| (comparison, if_then),
+        pair(
+            opt(terminated(self_arg, opt(ws(token(","))))),
+            terminated(
+                separated_list0(
+                    ws(token(",")),
+                    spanned(separated_pair(
+                        pair(ws(mutable_qualifier), identifier),
+                        cut(ws(token(":"))),
+                        cut(type_expr),
+                    )),
+                ),
+                opt(token(",")),
+            ),
+        ),
+        |(self_arg, args)| {
+            self_arg
+                .into_iter()
+                .chain(args.into_iter().map(|(((mutable, arg), t), span)| {
+                    FnArg::new(arg.to_string(), t, mutable, span)
+                }))
+                .collect()
+        },
     )(input)
 }
 
-fn function_args(input: &str) -> IResult<&str, Vec<FnArg>> {
-    map(
-        terminated(
-            separated_list0(
-                ws(tag(",")),
-                separated_pair(identifier, ws(tag(":")), arg_type),
+/// Parse the generic type parameters of a function declaration, each
+/// optionally bounded by one or more traits.
+/// e.g. <T, U: Shape + Named>
+fn type_params(input: Input) -> PResult<Vec<TypeParam>> {
+    delimited(
+        ws(token("<")),
+        separated_list1(
+            ws(token(",")),
+            map(
+                spanned(pair(
+                    identifier,
+                    opt(preceded(
+                        ws(token(":")),
+                        separated_list1(ws(token("+")), path),
+                    )),
+                )),
+                |((name, bounds), span)| {
+                    let bounds = bounds.unwrap_or_default();
+                    TypeParam::new(
+                        name.to_string(),
+                        bounds.into_iter().map(|bound| bound.to_string()).collect(),
+                        span,
+                    )
+                },
             ),
-            opt(tag(",")),
         ),
-        |args| {
-            args.into_iter()
-                .map(|(arg, t)| FnArg::new(arg.to_string(), t))
-                .collect()
-        },
+        ws(token(">")),
     )(input)
 }
 
-pub fn function(input: &str) -> IResult<&str, FnDecl> {
+/// Parse everything in a function declaration up to its body, which is
+/// left empty.
+/// e.g. fn max<T>(a: T, b: T) -> T
+fn fn_signature(input: Input) -> PResult<FnDecl> {
+    let (rest, ((fn_name, type_params, fn_args, ret_type), span)) = spanned(preceded(
+        keyword("fn"),
+        cut(tuple((
+            ws(identifier),
+            opt(type_params),
+            delimited(ws(token("(")), function_args, ws(token(")"))),
+            opt(preceded(ws(token("->")), cut(type_expr))),
+        ))),
+    ))(input)?;
+
+    let ret_type = match ret_type {
+        Some(ret_type) => ret_type,
+        None => {
+            return Err(nom::Err::Failure(
+                CompileError::new(
+                    span,
+                    format!("Function `{fn_name}` is missing a return type"),
+                )
+                .into(),
+            ))
+        }
+    };
+
+    let decl = FnDecl::new(
+        fn_name.to_string(),
+        type_params.unwrap_or_default(),
+        fn_args,
+        ret_type,
+        Vec::new(),
+        span,
+    );
+    Ok((rest, decl))
+}
+
+/// Parse a function declaration. The value of the body, if it has one, is
+/// returned from the function.
+/// e.g. fn one() -> i32 { 1 }
+/// e.g. const fn square(x: i32) -> i32 { imul(x, x) }
+pub fn function(input: Input) -> PResult<FnDecl> {
     map(
         tuple((
-            preceded(terminated(tag("fn"), space1), identifier),
-            delimited(ws(tag("(")), function_args, ws(tag(")"))),
-            opt(preceded(ws(tag("->")), ret_type)),
-            delimited(ws(tag("{")), many0(stmt), ws(tag("}"))),
+            map(opt(terminated(keyword("const"), sp)), |kw| kw.is_some()),
+            fn_signature,
+            block,
         )),
-        |(fn_name, fn_args, ret_type, body)| {
-            if let None = ret_type {
-                println!("{} function missing return type", fn_name);
-                process::exit(1);
+        |(is_const, decl, body)| {
+            let mut stmts = body.stmts;
+            stmts.extend(body.value.map(|value| {
+                let span = value.span();
+                Stmt::Return(Return {
+                    value: *value,
+                    span,
+                })
+            }));
+            FnDecl {
+                is_const,
+                body: stmts,
+                ..decl
             }
-
-            FnDecl::new(fn_name.to_string(), fn_args, ret_type.unwrap(), body)
         },
     )(input)
 }
 
+/// Parse a function declaration without a body, as found in a trait.
+/// e.g. fn area(&self) -> f64;
+pub fn fn_prototype(input: Input) -> PResult<FnDecl> {
+    terminated(fn_signature, ws(token(";")))(input)
+}
+
 /// Parse assignment in the form
 /// let <var_name>: <var_type> = <value>
 /// e.g. let x: f32 = 10.0;
-pub fn assignment(input: &str) -> IResult<&str, Assignment> {
+/// The type annotation may be omitted, in which case it is inferred.
+/// e.g. let x = fibonacci(10);
+/// The value may also be omitted, to be assigned later.
+/// e.g. let x: i32;
+pub fn assignment(input: Input) -> PResult<Assignment> {
     map(
-        preceded(
-            ws(tag("let")),
-            tuple((
+        spanned(preceded(
+            pair(sp, keyword("let")),
+            cut(tuple((
                 ws(mutable_qualifier),
                 identifier_to_obj,
-                preceded(ws(tag(":")), assign_type),
-                ws(char('=')),
-                expression,
-            )),
-        ),
-        |(mutable, target, var_type, _, value)| Assignment::new(target, var_type, value, mutable),
+                opt(preceded(ws(token(":")), type_expr)),
+                opt(preceded(ws(token("=")), cut(expression))),
+            ))),
+        )),
+        |((mutable, target, var_type, value), span)| {
+            Assignment::new(target, var_type, value, mutable, span)
+        },
     )(input)
 }
 
+/// Parse the target of a re-assignment, a variable, a field of one or the
+/// target of a pointer.
+/// e.g. x, p.x or *p
+pub fn place(input: Input) -> PResult<Expression> {
+    alt((
+        map(spanned(preceded(token("*"), identifier)), |(name, span)| {
+            Expression::DeRef(Name::new(name.to_string(), span))
+        }),
+        map(
+            pair(
+                identifier_to_obj,
+                many0(preceded(ws(token(".")), identifier_to_obj)),
+            ),
+            |(name, fields)| {
+                fields
+                    .into_iter()
+                    .fold(Expression::Identifier(name), |base, field| {
+                        Expression::Field(Box::new(base), field)
+                    })
+            },
+        ),
+    ))(input)
+}
+
 /// Parse a re-assignment.
-/// This is when an already defined variable is changed.
-pub fn reassign(input: &str) -> IResult<&str, Local> {
+/// This is when an already defined variable, or a field of one, is changed.
+pub fn reassign(input: Input) -> PResult<Local> {
+    map(
+        spanned(tuple((place, ws(token("=")), expression))),
+        |((target, _, value), span)| Local::new(target, value, span),
+    )(input)
+}
+
+fn compound_op(input: Input) -> PResult<CompoundOp> {
+    alt((
+        value(CompoundOp::Shl, tag("<<=")),
+        value(CompoundOp::Shr, tag(">>=")),
+        value(CompoundOp::Add, tag("+=")),
+        value(CompoundOp::Sub, tag("-=")),
+        value(CompoundOp::Mul, tag("*=")),
+        value(CompoundOp::Div, tag("/=")),
+        value(CompoundOp::Rem, tag("%=")),
+        value(CompoundOp::BitAnd, tag("&=")),
+        value(CompoundOp::BitOr, tag("|=")),
+        value(CompoundOp::BitXor, tag("^=")),
+    ))(input)
+}
+
+/// Parse a compound assignment.
+/// e.g. i += 1 or p.x <<= 2
+pub fn compound_assign(input: Input) -> PResult<CompoundAssign> {
+    map(
+        spanned(tuple((place, ws(compound_op), expression))),
+        |((target, op, value), span)| CompoundAssign {
+            target,
+            op,
+            value,
+            span,
+        },
+    )(input)
+}
+
+pub fn return_(input: Input) -> PResult<Return> {
     map(
-        tuple((identifier_to_obj, ws(char('=')), expression)),
-        |(target, _, value)| Local::new(target, value),
+        spanned(preceded(pair(sp, keyword("return")), cut(expression))),
+        |(value, span)| Return { value, span },
     )(input)
 }
 
-pub fn return_(input: &str) -> IResult<&str, Expression> {
-    preceded(ws(tag("return")), expression)(input)
+pub fn stmt(input: Input) -> PResult<Stmt> {
+    context("statement", stmt_kind)(input)
 }
 
-pub fn stmt(input: &str) -> IResult<&str, Stmt> {
+fn stmt_kind(input: Input) -> PResult<Stmt> {
     alt((
-        map(if_stmt, |(comparison, body)| Stmt::If(comparison, body)),
-        map(terminated(return_, ws(char(';'))), |x| Stmt::Return(x)),
-        map(terminated(func_call, ws(char(';'))), |x| Stmt::Call(x)),
-        map(terminated(reassign, ws(char(';'))), |x| Stmt::ReAssign(x)),
-        map(terminated(assignment, ws(char(';'))), |x| Stmt::Assign(x)),
+        map(if_expr, Stmt::If),
+        map(block, Stmt::Block),
+        map(terminated(return_, ws(token(";"))), Stmt::Return),
+        map(terminated(func_call, ws(token(";"))), Stmt::Call),
+        map_opt(terminated(expression, ws(token(";"))), |expr| match expr {
+            Expression::MethodCall(call) => Some(Stmt::MethodCall(call)),
+            _ => None,
+        }),
+        map(terminated(reassign, ws(token(";"))), Stmt::ReAssign),
+        map(
+            terminated(compound_assign, ws(token(";"))),
+            Stmt::CompoundAssign,
+        ),
+        map(terminated(assignment, ws(token(";"))), Stmt::Assign),
     ))(input)
 }
 
 #[cfg(test)]
 mod tests {
     use crate::parser::ast;
-    use crate::parser::stmts::{assignment, function, reassign};
+    use crate::parser::helpers::input;
+    use crate::parser::stmts::{
+        assignment, block, compound_assign, fn_prototype, function, if_expr, reassign, stmt,
+        type_expr,
+    };
 
     #[test]
     fn test_parse_reassign() {
         let code = "x = 10";
-        reassign(code).unwrap();
+        reassign(input(code)).unwrap();
+
+        let (_, ast) = reassign(input("self.origin.x = 10")).unwrap();
+        assert!(matches!(ast.target, ast::Expression::Field(_, ref field) if field.ident == "x"));
+    }
+
+    #[test]
+    fn test_parse_compound_assign() {
+        let (_, ast) = compound_assign(input("i += 1")).unwrap();
+        assert_eq!(ast.op, ast::CompoundOp::Add);
+
+        let (_, ast) = compound_assign(input("p.x <<= 2")).unwrap();
+        assert_eq!(ast.op, ast::CompoundOp::Shl);
+        assert!(matches!(ast.target, ast::Expression::Field(_, ref field) if field.ident == "x"));
+
+        let (remaining, ast) = stmt(input("*p %= 3;")).unwrap();
+        assert_eq!(remaining.len(), 0);
+        assert!(matches!(
+            ast,
+            ast::Stmt::CompoundAssign(ast::CompoundAssign {
+                target: ast::Expression::DeRef(_),
+                op: ast::CompoundOp::Rem,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_parse_method_call_stmt() {
+        let (remaining, ast) = stmt(input("p.scale(2.0);")).unwrap();
+        assert_eq!(remaining.len(), 0);
+        assert!(matches!(ast, ast::Stmt::MethodCall(ref call) if call.method == "scale"));
+    }
+
+    #[test]
+    fn test_parse_block() {
+        let (remaining, body) = block(input(
+            "{ let x: i32 = 10; { let x: i64 = 20; } { } x = 5; }",
+        ))
+        .unwrap();
+        assert_eq!(remaining.len(), 0);
+        assert_eq!(body.stmts.len(), 4);
+        assert!(body.value.is_none());
+        assert!(matches!(body.stmts[1], ast::Stmt::Block(ref inner) if inner.stmts.len() == 1));
+        assert!(matches!(body.stmts[2], ast::Stmt::Block(ref inner) if inner.stmts.is_empty()));
+    }
+
+    #[test]
+    fn test_parse_block_value() {
+        let (remaining, body) = block(input("{ let x: i32 = 10; iadd(x, 1) }")).unwrap();
+        assert_eq!(remaining.len(), 0);
+        assert_eq!(body.stmts.len(), 1);
+        assert!(matches!(
+            body.value.as_deref(),
+            Some(ast::Expression::Call(_))
+        ));
+
+        // A trailing `if` whose branches have values is the value of the block.
+        let (_, body) = block(input("{ if c { 1 } else { 2 } }")).unwrap();
+        assert!(body.stmts.is_empty());
+        assert!(matches!(
+            body.value.as_deref(),
+            Some(ast::Expression::If(_))
+        ));
+
+        let (_, body) = block(input("{ if c { return 1; } }")).unwrap();
+        assert_eq!(body.stmts.len(), 1);
+        assert!(body.value.is_none());
+    }
+
+    #[test]
+    fn test_parse_if_else() {
+        let (remaining, ast) = if_expr(input("if a { 1 } else if b { 2 } else { 3 }")).unwrap();
+        assert_eq!(remaining.len(), 0);
+        let else_body = ast.else_body.unwrap();
+        assert!(else_body.stmts.is_empty());
+        assert!(matches!(
+            else_body.value.as_deref(),
+            Some(ast::Expression::If(ast::If {
+                else_body: Some(_),
+                ..
+            }))
+        ));
+
+        let (_, ast) = assignment(input("let x: i32 = if c { 1 } else { 2 }")).unwrap();
+        assert!(matches!(ast.value, Some(ast::Expression::If(_))));
+        let (_, ast) = assignment(input("let x = { let y = 2; y }")).unwrap();
+        assert!(matches!(ast.value, Some(ast::Expression::Block(_))));
+
+        // A function body's value is returned.
+        let (_, ast) = function(input("fn one() -> i32 { 1 }")).unwrap();
+        assert!(matches!(ast.body[..], [ast::Stmt::Return(_)]));
     }
 
     #[test]
@@ -185,10 +541,18 @@ mod tests {
             "fn main(x: i32, y: i32, z: i32) -> i32 {}",
             "fn main(x: *i32) -> i32 {}",
             "fn main(x: *mut i32) -> i32 {}",
+            "fn main(mut x: i32) -> i32 {}",
+            "fn main(x: i32, mut y: *i32) -> i32 {}",
+            "fn max<T>(a: T, b: T) -> T {}",
+            "fn len(&self) -> f64 {}",
+            "fn scale(&mut self, k: f64) -> i32 {}",
+            "fn into(self) -> i32 {}",
+            "fn into_mut(mut self) -> i32 {}",
+            "fn pair<A, B>(a: *A, b: B) -> i32 {}",
         ];
 
         for declaration in declarations {
-            match function(declaration) {
+            match function(input(declaration)) {
                 Ok((remaining, _)) => {
                     assert_eq!(remaining.len(), 0);
                 }
@@ -209,10 +573,12 @@ mod tests {
             "let mut y: *i32 = &x",
             "let mut y: *mut i32 = &x",
             "let mut y: *mut i32 = &mut x",
+            "let x = 10",
+            "let mut x = fibonacci(10)",
         ];
 
         for assign in assignments {
-            match assignment(assign) {
+            match assignment(input(assign)) {
                 Ok((remaining, _)) => {
                     assert_eq!(remaining.len(), 0);
                 }
@@ -224,12 +590,65 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_generic_decl() {
+        let (_, ast) = function(input("fn max<T>(a: T, b: *mut T) -> T {}")).unwrap();
+        assert_eq!(ast.type_params.len(), 1);
+        assert_eq!(ast.type_params[0].name, "T");
+        assert_eq!(ast.ret_type, ast::EmptyType::Named("T".to_string()));
+        assert_eq!(ast.args[1].t.to_string(), "*mut T");
+    }
+
+    #[test]
+    fn test_trait_bounds() {
+        let (_, ast) =
+            function(input("fn total<T: Shape + Named, U>(a: T, b: U) -> i32 {}")).unwrap();
+        assert_eq!(ast.type_params[0].bounds, vec!["Shape", "Named"]);
+        assert!(ast.type_params[1].bounds.is_empty());
+
+        let (remaining, ast) = fn_prototype(input("fn area(&self) -> i32;")).unwrap();
+        assert_eq!(remaining.len(), 0);
+        assert!(ast.body.is_empty());
+    }
+
+    #[test]
+    fn test_mutable_arg() {
+        let (_, ast) = function(input("fn main(mut x: i32, mutex: i32) -> i32 {}")).unwrap();
+        assert!(ast.args[0].mutable);
+        assert!(!ast.args[1].mutable);
+        assert_eq!(ast.args[1].name, "mutex");
+    }
+
+    #[test]
+    fn test_untyped_assignment() {
+        let (_, ast) = assignment(input("let x = y")).unwrap();
+        assert!(ast.var_type.is_none());
+        assert_eq!(ast.target.ident, "x");
+    }
+
+    #[test]
+    fn test_uninitialized_assignment() {
+        let (_, ast) = assignment(input("let x: i32")).unwrap();
+        assert!(ast.value.is_none());
+        assert!(ast.var_type.is_some());
+        let (remaining, body) = block(input("{ let mut x: i32; x = 1; }")).unwrap();
+        assert_eq!(remaining.len(), 0);
+        assert!(matches!(
+            &body.stmts[0],
+            ast::Stmt::Assign(ast::Assignment {
+                value: None,
+                mutable: true,
+                ..
+            })
+        ));
+    }
+
     #[test]
     fn test_mutable_ptr() {
-        let (_, ast) = assignment("let mut y: *mut i32 = &x").unwrap();
+        let (_, ast) = assignment(input("let mut y: *mut i32 = &x")).unwrap();
         assert!(ast.mutable);
         match ast.var_type {
-            ast::EmptyType::Pointer(ptr) => {
+            Some(ast::EmptyType::Pointer(ptr)) => {
                 assert!(ptr.mutable);
             }
             _ => {
@@ -237,4 +656,68 @@ mod tests {
             }
         };
     }
+
+    #[test]
+    fn test_stmt_spans() {
+        let code = "fn one(mut x: i32) -> i32 {\n    x = 2;\n    x\n}";
+        let (_, ast) = function(input(code)).unwrap();
+        let text = |span: ast::Span| &code[span.start..span.end];
+        assert_eq!(text(ast.span), "fn one(mut x: i32) -> i32");
+        assert_eq!(text(ast.args[0].span), "mut x: i32");
+        match &ast.body[..] {
+            [ast::Stmt::ReAssign(local), ast::Stmt::Return(ret)] => {
+                assert_eq!(text(local.span), "x = 2");
+                assert_eq!(text(ret.span), "x");
+            }
+            body => unreachable!("function body parsed incorrectly: {:?}", body),
+        }
+    }
+
+    #[test]
+    fn test_missing_return_type() {
+        let code = "fn main() {\n    return 0;\n}";
+        match function(input(code)) {
+            Err(nom::Err::Failure(err)) => {
+                let err = &err.into_errors()[0];
+                assert_eq!(err.message, "Function `main` is missing a return type");
+                let span = err.span.unwrap();
+                assert_eq!(&code[span.start..span.end], "fn main()");
+            }
+            result => unreachable!("expected a missing return type, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_block_recovery() {
+        let code = "{\n    let x = 5\n    let y = ;\n    if x { y = 1 }\n    z = 2;\n}";
+        let errors = match block(input(code)) {
+            Err(nom::Err::Failure(err)) => err.into_errors(),
+            result => unreachable!("expected syntax errors, got {:?}", result),
+        };
+        let messages: Vec<_> = errors.iter().map(|err| err.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "Expected `;`, found `let`",
+                "Expected expression, found `;`",
+                "Expected `;`, found `}`",
+            ]
+        );
+        let span = errors[1].span.unwrap();
+        assert_eq!(&code[span.start..span.end], ";");
+    }
+
+    #[test]
+    fn test_parse_type() {
+        let (remaining, ty) = type_expr(input("*mut *i64")).unwrap();
+        assert_eq!(remaining.len(), 0);
+        assert_eq!(ty.to_string(), "*mut *i64");
+
+        // A primitive type name is not a prefix of a longer name.
+        let (_, ty) = type_expr(input("i32x")).unwrap();
+        assert_eq!(ty, ast::EmptyType::Named("i32x".to_string()));
+
+        let (_, ast) = function(input("fn first(p: *Point) -> *i64 { return &p.x; }")).unwrap();
+        assert_eq!(ast.ret_type.to_string(), "*i64");
+    }
 }
@@ -0,0 +1,911 @@
+use super::consteval::{Const, Consts};
+use super::functions::{lookup_signature, FnSignature};
+use super::layout;
+use super::mangle;
+use super::structs::{struct_layout, StructLayout, Structs};
+use super::typed::{self, Expr, ExprKind, LocalId};
+use crate::diagnostics::{error, CompileError, CompileResult};
+use crate::parser::ast;
+use crate::parser::ast::{EmptyType, FloatType, IntegerType, Pointer};
+use std::collections::{HashMap, HashSet};
+
+/// What a name refers to within a function.
+#[derive(Clone, Copy)]
+enum Binding<'a> {
+    Local(LocalId),
+    Const(&'a Const),
+}
+
+/// What is expected of the value of a block or `if`.
+#[derive(Clone, Copy)]
+enum Expect<'t> {
+    /// The value is discarded.
+    Nothing,
+    /// The value is used, as a value of the given type if there is one.
+    Value(Option<&'t EmptyType>),
+}
+
+/// Check the body of a function, whose `let` types have been inferred, and
+/// build its typed tree. Every type error which code generation would
+/// otherwise run into is reported here, in terms of Wellick types.
+pub(crate) fn check_function(
+    func: ast::FnDecl,
+    functions: &HashMap<String, ast::FnDecl>,
+    structs: &Structs,
+    consts: &Consts,
+) -> CompileResult<typed::Function> {
+    let checker = Checker::new(functions, structs, consts, func.ret_type.clone());
+    check_body(checker, func)
+}
+
+/// Check the body of a generic function with its type parameters in place,
+/// calling the functions in `signatures` as well as those of the program.
+/// What a value of a type parameter may be used as depends on the type
+/// argument, so such uses are only checked in each instance.
+pub(crate) fn check_template(
+    func: ast::FnDecl,
+    signatures: &[ast::FnDecl],
+    functions: &HashMap<String, ast::FnDecl>,
+    structs: &Structs,
+    consts: &Consts,
+) -> CompileResult<()> {
+    let mut checker = Checker::new(functions, structs, consts, func.ret_type.clone());
+    checker.type_params = func
+        .type_params
+        .iter()
+        .map(|param| param.name.clone())
+        .collect();
+    checker.signatures = signatures
+        .iter()
+        .map(|decl| (decl.name.clone(), FnSignature::from(decl)))
+        .collect();
+    check_body(checker, func).map(drop)
+}
+
+fn check_body(mut checker: Checker, func: ast::FnDecl) -> CompileResult<typed::Function> {
+    let params = func
+        .args
+        .iter()
+        .map(|arg| checker.declare(&arg.name, arg.t.clone(), arg.mutable, arg.span))
+        .collect();
+    let body = func
+        .body
+        .iter()
+        .map(|stmt| checker.stmt(stmt))
+        .collect::<CompileResult<_>>()?;
+
+    Ok(typed::Function {
+        name: func.name,
+        params,
+        ret_type: func.ret_type,
+        locals: checker.locals,
+        body,
+        attributes: func.attributes,
+        span: func.span,
+    })
+}
+
+struct Checker<'a> {
+    functions: &'a HashMap<String, ast::FnDecl>,
+    structs: &'a Structs,
+    ret_type: EmptyType,
+    /// The type parameters, when checking the body of a generic function
+    /// rather than an instance of it.
+    type_params: Vec<String>,
+    /// The functions which only exist for `type_params`.
+    signatures: HashMap<String, FnSignature>,
+    locals: Vec<typed::Local>,
+    /// The variables declared without a value, which may be assigned once
+    /// even if they are immutable. That they are assigned exactly once is
+    /// checked by [`super::init`].
+    deferred: HashSet<LocalId>,
+    scopes: Vec<HashMap<String, Binding<'a>>>,
+}
+
+impl<'a> Checker<'a> {
+    /// Create a checker for a function body, with the program's constants
+    /// in the outermost scope so that any variable may shadow them.
+    fn new(
+        functions: &'a HashMap<String, ast::FnDecl>,
+        structs: &'a Structs,
+        consts: &'a Consts,
+        ret_type: EmptyType,
+    ) -> Self {
+        let consts = consts
+            .iter()
+            .map(|(name, value)| (name.clone(), Binding::Const(value)))
+            .collect();
+        Self {
+            functions,
+            structs,
+            ret_type,
+            type_params: Vec::new(),
+            signatures: HashMap::new(),
+            locals: Vec::new(),
+            deferred: HashSet::new(),
+            scopes: vec![consts, HashMap::new()],
+        }
+    }
+
+    /// Declare a variable in the innermost scope, shadowing any variable
+    /// with the same name.
+    fn declare(&mut self, name: &str, ty: EmptyType, mutable: bool, span: ast::Span) -> LocalId {
+        let id = LocalId(self.locals.len());
+        self.locals.push(typed::Local {
+            name: name.to_string(),
+            ty,
+            mutable,
+            address_taken: false,
+            span,
+        });
+        self.scopes
+            .last_mut()
+            .expect("there is always at least one scope")
+            .insert(name.to_string(), Binding::Local(id));
+        id
+    }
+
+    /// Find the innermost variable or constant with the given name.
+    fn lookup(&self, name: &str, span: ast::Span) -> CompileResult<Binding<'a>> {
+        match self.scopes.iter().rev().find_map(|scope| scope.get(name)) {
+            Some(binding) => Ok(*binding),
+            None => error(span, format!("Cannot find value `{name}` in this scope")),
+        }
+    }
+
+    fn stmt(&mut self, stmt: &ast::Stmt) -> CompileResult<typed::Stmt> {
+        let kind = match stmt {
+            ast::Stmt::Assign(assign) => self.assign(assign)?,
+            ast::Stmt::ReAssign(local) => {
+                if !self.is_deferred(&local.target) {
+                    self.check_place_mutable(&local.target)?;
+                }
+                let target = self.place(&local.target)?;
+                let value = self.value(&local.value, &target.ty)?;
+                typed::StmtKind::Assign(target, value)
+            }
+            ast::Stmt::CompoundAssign(assign) => self.compound_assign(assign)?,
+            ast::Stmt::Return(ret) => {
+                let ret_type = self.ret_type.clone();
+                typed::StmtKind::Return(self.value(&ret.value, &ret_type)?)
+            }
+            ast::Stmt::Call(call) => typed::StmtKind::Expr(self.call(call, None)?),
+            ast::Stmt::MethodCall(call) => typed::StmtKind::Expr(self.method_call(call)?),
+            ast::Stmt::If(if_stmt) => typed::StmtKind::If(self.if_expr(if_stmt, Expect::Nothing)?),
+            ast::Stmt::Block(block) => typed::StmtKind::Block(self.block(block, Expect::Nothing)?),
+        };
+        Ok(typed::Stmt::new(kind, stmt.span()))
+    }
+
+    fn assign(&mut self, assign: &ast::Assignment) -> CompileResult<typed::StmtKind> {
+        let ty = assign
+            .var_type
+            .clone()
+            .expect("variable types are inferred before checking");
+
+        // A mutable pointer may only be made to a mutable variable.
+        if let Some(ast::Expression::AddressOf(addr_of)) = &assign.value {
+            if let Binding::Local(id) = self.lookup(&addr_of.name, addr_of.span)? {
+                let is_mutable_ptr =
+                    matches!(ty.resolved(), EmptyType::Pointer(ptr) if ptr.mutable);
+                if is_mutable_ptr && !self.locals[id.0].mutable {
+                    return error(
+                        addr_of.span,
+                        format!(
+                            "Cannot declare mutable pointer to {}, as it has not been declared mutable",
+                            addr_of.name
+                        ),
+                    );
+                }
+            }
+        }
+        let value = match &assign.value {
+            Some(value) => Some(self.value(value, &ty)?),
+            None => None,
+        };
+
+        // The variable is declared only after its initializer has been
+        // checked, so `let x = f(x);` refers to any outer `x`.
+        let id = self.declare(&assign.target, ty, assign.mutable, assign.target.span);
+        if value.is_none() {
+            self.deferred.insert(id);
+        }
+        Ok(typed::StmtKind::Let(id, value))
+    }
+
+    /// Whether a place is a variable which was declared without a value.
+    fn is_deferred(&self, place: &ast::Expression) -> bool {
+        match place {
+            ast::Expression::Identifier(name) => matches!(
+                self.lookup(name, name.span),
+                Ok(Binding::Local(id)) if self.deferred.contains(&id)
+            ),
+            _ => false,
+        }
+    }
+
+    /// Check an assignment such as `x += 1`, which reads the place, applies
+    /// the operator and writes the result back.
+    fn compound_assign(&mut self, assign: &ast::CompoundAssign) -> CompileResult<typed::StmtKind> {
+        self.check_place_mutable(&assign.target)?;
+        let target = self.place(&assign.target)?;
+
+        let supported = match target.ty.resolved() {
+            EmptyType::Integer(_) => true,
+            EmptyType::Float(_) => matches!(
+                assign.op,
+                ast::CompoundOp::Add
+                    | ast::CompoundOp::Sub
+                    | ast::CompoundOp::Mul
+                    | ast::CompoundOp::Div
+            ),
+            ty => self.is_generic(ty),
+        };
+        if !supported {
+            return error(
+                assign.span,
+                format!(
+                    "Cannot apply `{}` to a value of type `{}`",
+                    assign.op, target.ty
+                ),
+            );
+        }
+
+        let value = self.value(&assign.value, &target.ty)?;
+        Ok(typed::StmtKind::CompoundAssign(target, assign.op, value))
+    }
+
+    /// Check the target of an assignment, which the parser only allows to
+    /// be a variable, a field of one or the target of a pointer.
+    fn place(&mut self, place: &ast::Expression) -> CompileResult<Expr> {
+        let place = self.expr(place, None)?;
+        match place.kind {
+            ExprKind::Local(_) | ExprKind::Field(..) | ExprKind::Deref(_) => Ok(place),
+            _ => error(place.span, "Cannot assign to this expression"),
+        }
+    }
+
+    /// Check that a place, such as `p` or `p.x`, may be written to.
+    fn check_place_mutable(&self, place: &ast::Expression) -> CompileResult<()> {
+        match place {
+            ast::Expression::Identifier(name) => match self.lookup(name, name.span)? {
+                Binding::Const(_) => {
+                    return error(name.span, format!("Cannot assign to constant `{name}`"));
+                }
+                Binding::Local(id) if !self.locals[id.0].mutable => {
+                    return error(
+                        name.span,
+                        format!("Cannot mutate immutable variable {name}"),
+                    );
+                }
+                Binding::Local(_) => {}
+            },
+            ast::Expression::Field(base, _) => match self.peek_type(base)? {
+                Some(ty) => match ty.resolved() {
+                    EmptyType::Pointer(ptr) if !ptr.mutable => {
+                        return error(
+                            place.span(),
+                            format!("Cannot assign through `{ty}`, as it is not a mutable pointer"),
+                        )
+                    }
+                    EmptyType::Pointer(_) => {}
+                    _ => self.check_place_mutable(base)?,
+                },
+                None => self.check_place_mutable(base)?,
+            },
+            ast::Expression::DeRef(name) => {
+                if let Binding::Local(id) = self.lookup(name, name.span)? {
+                    let ty = &self.locals[id.0].ty;
+                    if let EmptyType::Pointer(ptr) = ty.resolved() {
+                        if !ptr.mutable {
+                            return error(
+                                name.span,
+                                format!(
+                                    "Cannot assign through `{ty}`, as it is not a mutable pointer"
+                                ),
+                            );
+                        }
+                    }
+                }
+            }
+            // Temporaries, such as the result of a call, may be freely modified.
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// The type of a place, without checking it. Only variables and fields
+    /// of them are looked at, as those are all a place may be made of.
+    fn peek_type(&self, place: &ast::Expression) -> CompileResult<Option<EmptyType>> {
+        Ok(match place {
+            ast::Expression::Identifier(name) => match self.lookup(name, name.span)? {
+                Binding::Local(id) => Some(self.locals[id.0].ty.clone()),
+                Binding::Const(value) => Some(value.ty.clone()),
+            },
+            ast::Expression::Field(base, name) => self
+                .peek_type(base)?
+                .and_then(|ty| self.struct_of(&ty))
+                .and_then(|layout| layout.field(name))
+                .map(|field| field.ty.clone()),
+            _ => None,
+        })
+    }
+
+    /// Check a block, the variables declared within it are not visible
+    /// after the closing brace.
+    fn block(&mut self, block: &ast::Block, expect: Expect) -> CompileResult<typed::Block> {
+        self.scopes.push(HashMap::new());
+        let result = self.block_contents(block, expect);
+        self.scopes.pop();
+        result
+    }
+
+    fn block_contents(
+        &mut self,
+        block: &ast::Block,
+        expect: Expect,
+    ) -> CompileResult<typed::Block> {
+        let mut stmts = block
+            .stmts
+            .iter()
+            .map(|stmt| self.stmt(stmt))
+            .collect::<CompileResult<Vec<_>>>()?;
+        let value = match (block.value.as_deref(), expect) {
+            (Some(value), Expect::Nothing) => {
+                stmts.push(self.unused(value)?);
+                None
+            }
+            (Some(value), Expect::Value(ty)) => Some(Box::new(self.expr(value, ty)?)),
+            // Blocks which must have a value but do not are reported during
+            // type inference, unless they return.
+            (None, _) => None,
+        };
+        Ok(typed::Block { stmts, value })
+    }
+
+    /// Check an expression whose value is discarded, such as the final
+    /// expression of a block used as a statement.
+    fn unused(&mut self, expr: &ast::Expression) -> CompileResult<typed::Stmt> {
+        let kind = match expr {
+            ast::Expression::If(if_expr) => {
+                typed::StmtKind::If(self.if_expr(if_expr, Expect::Nothing)?)
+            }
+            ast::Expression::Block(block) => {
+                typed::StmtKind::Block(self.block(block, Expect::Nothing)?)
+            }
+            expr => typed::StmtKind::Expr(self.expr(expr, None)?),
+        };
+        Ok(typed::Stmt::new(kind, expr.span()))
+    }
+
+    /// Check an `if`. When its value is used, the `else` branch is expected
+    /// to have the same type as the `then` branch.
+    fn if_expr(&mut self, if_expr: &ast::If, expect: Expect) -> CompileResult<typed::If> {
+        let condition = self.expr(&if_expr.condition, None)?;
+        // The condition is true when it is non-zero, which only makes sense
+        // for integers.
+        if !matches!(condition.ty.resolved(), EmptyType::Integer(_)) {
+            return Err(CompileError::new(
+                condition.span,
+                format!(
+                    "Mismatched types, expected an integer, found `{}`",
+                    condition.ty
+                ),
+            )
+            .with_note("the condition of an `if` is an integer, which is true when non-zero"));
+        }
+        let then_body = self.block(&if_expr.then_body, expect)?;
+        let then_type = then_body.value.as_ref().map(|value| value.ty.clone());
+        let else_body = match &if_expr.else_body {
+            Some(else_body) => {
+                let expect = match (expect, &then_type) {
+                    (Expect::Value(_), Some(ty)) => Expect::Value(Some(ty)),
+                    (expect, _) => expect,
+                };
+                Some(self.block(else_body, expect)?)
+            }
+            None => None,
+        };
+
+        let else_value = else_body.as_ref().and_then(|body| body.value.as_ref());
+        if let (Some(expected), Some(found)) = (&then_type, else_value) {
+            // The `if` has the type of whichever branch the other may be
+            // used as, e.g. `*T` for a `*mut T` and a `*T`.
+            if !self.compatible(expected, &found.ty) {
+                return error(
+                    if_expr.span,
+                    format!(
+                        "`if` and `else` have incompatible types, expected `{expected}`, found `{}`",
+                        found.ty
+                    ),
+                );
+            }
+        }
+
+        Ok(typed::If {
+            condition,
+            then_body,
+            else_body,
+        })
+    }
+
+    /// Check an expression which is expected to have type `ty`, giving
+    /// literals that type and reporting any mismatch.
+    fn value(&mut self, expr: &ast::Expression, ty: &EmptyType) -> CompileResult<Expr> {
+        let value = self.expr(expr, Some(ty))?;
+        if assignable(ty, &value.ty) || self.is_generic(ty) || self.is_generic(&value.ty) {
+            return Ok(value);
+        }
+        let note = pointer_mutability_note(ty, &value.ty);
+        match expr {
+            ast::Expression::AddressOf(addr_of)
+                if note.is_some()
+                    && matches!(ty.resolved(), EmptyType::Pointer(ptr) if ptr.mutable) =>
+            {
+                let name = &addr_of.name;
+                error(addr_of.span, format!("Expected &mut {name}, got &{name}"))
+            }
+            _ => {
+                let mut err = CompileError::new(
+                    value.span,
+                    format!("Mismatched types, expected `{ty}`, found `{}`", value.ty),
+                );
+                if let Some(note) = note {
+                    err = err.with_note(note);
+                }
+                Err(err)
+            }
+        }
+    }
+
+    /// Check an expression. `hint` is the type the value is wanted as, if
+    /// known, which literals and `null` take on. Whether the value actually
+    /// has that type is up to the caller.
+    fn expr(&mut self, expr: &ast::Expression, hint: Option<&EmptyType>) -> CompileResult<Expr> {
+        let span = expr.span();
+        let (kind, ty) = match expr {
+            ast::Expression::Literal(literal) => {
+                let ty = literal_type(literal, hint);
+                (literal_value(literal, &ty)?, ty)
+            }
+            ast::Expression::Identifier(name) => match self.lookup(name, name.span)? {
+                Binding::Local(id) => (ExprKind::Local(id), self.locals[id.0].ty.clone()),
+                // A constant is replaced by its value.
+                Binding::Const(value) => {
+                    (literal_value(&value.value, &value.ty)?, value.ty.clone())
+                }
+            },
+            ast::Expression::AddressOf(addr_of) => {
+                let id = match self.lookup(&addr_of.name, addr_of.span)? {
+                    Binding::Local(id) => id,
+                    // Constants have no storage, so they have no address.
+                    Binding::Const(_) => {
+                        return error(
+                            addr_of.span,
+                            format!("Cannot take the address of constant `{}`", addr_of.name),
+                        )
+                    }
+                };
+                let local = &mut self.locals[id.0];
+                local.address_taken = true;
+                let ty =
+                    EmptyType::Pointer(Box::new(Pointer::new(local.ty.clone(), addr_of.mutable)));
+                (ExprKind::AddressOf(id), ty)
+            }
+            ast::Expression::DeRef(name) => {
+                let ptr = self.expr(&ast::Expression::Identifier(name.clone()), None)?;
+                let pointee = match ptr.ty.resolved() {
+                    EmptyType::Pointer(ptr) => ptr.ty.clone(),
+                    _ => {
+                        return error(
+                            name.span,
+                            format!("Cannot dereference `{name}`, as it has type `{}`", ptr.ty),
+                        )
+                    }
+                };
+                (ExprKind::Deref(Box::new(ptr)), pointee)
+            }
+            ast::Expression::StructLiteral(literal) => {
+                let layout = &self.structs[&literal.name];
+                let mut fields = Vec::new();
+                for (name, value) in &literal.fields {
+                    let field = layout
+                        .field(name)
+                        .expect("struct literals are checked during type inference");
+                    fields.push((field.offset, self.value(value, &field.ty)?));
+                }
+                (
+                    ExprKind::StructLiteral(fields),
+                    EmptyType::Named(literal.name.clone()),
+                )
+            }
+            ast::Expression::Field(base, name) => {
+                let base = self.expr(base, None)?;
+                let field = self
+                    .struct_of(&base.ty)
+                    .and_then(|layout| layout.field(name))
+                    .expect("field accesses are checked during type inference");
+                let (offset, ty) = (field.offset, field.ty.clone());
+                (ExprKind::Field(Box::new(base), offset), ty)
+            }
+            ast::Expression::Call(call) => return self.call(call, hint),
+            ast::Expression::MethodCall(call) => return self.method_call(call),
+            ast::Expression::If(if_expr) => {
+                let if_expr = self.if_expr(if_expr, Expect::Value(hint))?;
+                let ty = value_type([Some(&if_expr.then_body), if_expr.else_body.as_ref()], hint);
+                (ExprKind::If(Box::new(if_expr)), ty)
+            }
+            ast::Expression::Block(block) => {
+                let block = self.block(block, Expect::Value(hint))?;
+                let ty = value_type([Some(&block)], hint);
+                (ExprKind::Block(Box::new(block)), ty)
+            }
+            ast::Expression::Null(span) => match hint {
+                Some(ty) if matches!(ty.resolved(), EmptyType::Pointer(_)) => {
+                    (ExprKind::Null, ty.clone())
+                }
+                Some(ty) if !self.is_generic(ty) => {
+                    return error(
+                        *span,
+                        format!("Mismatched types, expected `{ty}`, found `null`"),
+                    )
+                }
+                _ => (ExprKind::Null, any_pointer()),
+            },
+            ast::Expression::Comparison(comparison) => self.comparison(comparison)?,
+        };
+        Ok(Expr::new(kind, ty, span))
+    }
+
+    /// Check a comparison of two values of the same type, giving an `i32`.
+    /// The type of the operands comes from whichever side has a type of its
+    /// own, rather than being a literal or `null`.
+    fn comparison(&mut self, comparison: &ast::Comparison) -> CompileResult<(ExprKind, EmptyType)> {
+        let (lhs, rhs) = if !self.is_untyped(&comparison.lhs)? {
+            let lhs = self.expr(&comparison.lhs, None)?;
+            let rhs = self.expr(&comparison.rhs, Some(&lhs.ty))?;
+            (lhs, rhs)
+        } else if !self.is_untyped(&comparison.rhs)? {
+            let rhs = self.expr(&comparison.rhs, None)?;
+            let lhs = self.expr(&comparison.lhs, Some(&rhs.ty))?;
+            (lhs, rhs)
+        } else {
+            // Literals default to their usual types, and `null` to any pointer.
+            let ty = match (&*comparison.lhs, &*comparison.rhs) {
+                (ast::Expression::Literal(ast::Literal::Float(_)), _)
+                | (_, ast::Expression::Literal(ast::Literal::Float(_))) => {
+                    EmptyType::Float(FloatType::F64)
+                }
+                (ast::Expression::Null(_), _) | (_, ast::Expression::Null(_)) => any_pointer(),
+                _ => EmptyType::Integer(IntegerType::I32),
+            };
+            let lhs = self.expr(&comparison.lhs, Some(&ty))?;
+            let rhs = self.expr(&comparison.rhs, Some(&ty))?;
+            (lhs, rhs)
+        };
+
+        if struct_layout(&lhs.ty, self.structs).is_some() {
+            return error(
+                comparison.span,
+                format!(
+                    "Cannot compare values of type `{}` with `{}`",
+                    lhs.ty, comparison.op
+                ),
+            );
+        }
+        // Pointers are compared by address, whether or not either may be
+        // written through.
+        if !self.compatible(&lhs.ty, &rhs.ty) {
+            return error(
+                rhs.span,
+                format!(
+                    "Mismatched types, expected `{}`, found `{}`",
+                    lhs.ty, rhs.ty
+                ),
+            );
+        }
+        Ok((
+            ExprKind::Comparison(comparison.op, Box::new(lhs), Box::new(rhs)),
+            EmptyType::Integer(IntegerType::I32),
+        ))
+    }
+
+    /// Whether an expression takes its type from where it is used.
+    fn is_untyped(&self, expr: &ast::Expression) -> CompileResult<bool> {
+        Ok(match expr {
+            ast::Expression::Literal(_) | ast::Expression::Null(_) => true,
+            ast::Expression::Call(call) => self.intrinsic(call)?.is_some(),
+            _ => false,
+        })
+    }
+
+    fn call(&mut self, call: &ast::Call, hint: Option<&EmptyType>) -> CompileResult<Expr> {
+        // `size_of::<T>()` and `align_of::<T>()` are constants, which take
+        // their type from where they are used like an integer literal.
+        if let Some(value) = self.intrinsic(call)? {
+            let literal =
+                ast::Literal::Integer(ast::IntegerLiteral::new(&value.to_string(), call.span));
+            let ty = literal_type(&literal, hint);
+            return Ok(Expr::new(literal_value(&literal, &ty)?, ty, call.span));
+        }
+
+        let sig = self.signature(&call.func, call.span)?;
+        self.check_arg_count(
+            &call.func,
+            "Function",
+            sig.params.len(),
+            call.args.len(),
+            call.span,
+        )?;
+
+        let args = call
+            .args
+            .iter()
+            .zip(&sig.params)
+            .map(|(arg, ty)| self.value(arg, ty))
+            .collect::<CompileResult<_>>()?;
+        Ok(Expr::new(
+            ExprKind::Call(call.func.clone(), args),
+            sig.ret_type,
+            call.span,
+        ))
+    }
+
+    /// Check a method call, which becomes a call passing the receiver as the
+    /// `self` argument. The receiver may be a struct or a pointer to one.
+    fn method_call(&mut self, call: &ast::MethodCall) -> CompileResult<Expr> {
+        let func = call
+            .func
+            .as_deref()
+            .expect("methods are resolved during type inference");
+        let receiver = self.expr(&call.receiver, None)?;
+        let i32_ty = EmptyType::Integer(IntegerType::I32);
+        if func == mangle::IS_NULL {
+            return Ok(Expr::new(
+                ExprKind::IsNull(Box::new(receiver)),
+                i32_ty,
+                call.span,
+            ));
+        }
+        // A pointer is already its address, so only the type changes.
+        if func == mangle::ADDR {
+            return Ok(Expr::new(
+                receiver.kind,
+                EmptyType::Integer(IntegerType::PointerSize),
+                call.span,
+            ));
+        }
+
+        let sig = self.signature(func, call.span)?;
+        if let EmptyType::Pointer(self_ptr) = sig.params[0].resolved() {
+            if self_ptr.mutable {
+                match receiver.ty.resolved() {
+                    EmptyType::Pointer(ptr) if !ptr.mutable => {
+                        return error(
+                            call.span,
+                            format!(
+                                "Cannot call `{}`, which takes `&mut self`, through `{}`",
+                                call.method, receiver.ty
+                            ),
+                        )
+                    }
+                    EmptyType::Pointer(_) => {}
+                    _ => self.check_place_mutable(&call.receiver)?,
+                }
+            }
+        }
+
+        self.check_arg_count(
+            &call.method,
+            "Method",
+            sig.params.len() - 1,
+            call.args.len(),
+            call.span,
+        )?;
+        let mut args = vec![receiver];
+        for (arg, ty) in call.args.iter().zip(&sig.params[1..]) {
+            args.push(self.value(arg, ty)?);
+        }
+        Ok(Expr::new(
+            ExprKind::Call(func.to_string(), args),
+            sig.ret_type,
+            call.span,
+        ))
+    }
+
+    fn check_arg_count(
+        &self,
+        name: &str,
+        what: &str,
+        expected: usize,
+        found: usize,
+        span: ast::Span,
+    ) -> CompileResult<()> {
+        if expected != found {
+            return error(
+                span,
+                format!("{what} `{name}` takes {expected} argument(s) but {found} were supplied"),
+            );
+        }
+        Ok(())
+    }
+
+    fn signature(&self, name: &str, span: ast::Span) -> CompileResult<FnSignature> {
+        let sig =
+            lookup_signature(self.functions, name).or_else(|| self.signatures.get(name).cloned());
+        match sig {
+            Some(sig) => Ok(sig),
+            None => error(span, format!("Cannot find function `{name}` in this scope")),
+        }
+    }
+
+    /// Evaluate a call to `size_of::<T>()` or `align_of::<T>()`. The layout
+    /// of a type parameter is only known in each instance, so stands in as
+    /// 0 until then.
+    fn intrinsic(&self, call: &ast::Call) -> CompileResult<Option<u32>> {
+        match call.type_args.as_slice() {
+            [ty] if layout::is_intrinsic(&call.func) && self.is_generic(ty) => Ok(Some(0)),
+            _ => layout::intrinsic(call, self.structs),
+        }
+    }
+
+    /// Whether a type is, or points to, a type parameter.
+    fn is_generic(&self, ty: &EmptyType) -> bool {
+        match ty.resolved() {
+            EmptyType::Named(name) => self.type_params.contains(name),
+            EmptyType::Pointer(ptr) => self.is_generic(&ptr.ty),
+            _ => false,
+        }
+    }
+
+    /// Whether values of two types may be compared, or be the branches of
+    /// an `if`, as one of them may be used as the other.
+    fn compatible(&self, a: &EmptyType, b: &EmptyType) -> bool {
+        assignable(a, b) || assignable(b, a) || self.is_generic(a) || self.is_generic(b)
+    }
+
+    /// The layout of the struct a value of type `ty` refers to, looking
+    /// through a pointer if necessary.
+    fn struct_of(&self, ty: &EmptyType) -> Option<&'a StructLayout> {
+        match ty.resolved() {
+            EmptyType::Pointer(ptr) => struct_layout(&ptr.ty, self.structs),
+            ty => struct_layout(ty, self.structs),
+        }
+    }
+}
+
+/// Whether a value of type `found` may be used where a value of type
+/// `expected` is needed. `i64` and `isize` may be used in place of each
+/// other, but a pointer is only converted to an integer explicitly, with
+/// `p.addr()`, and never back.
+fn assignable(expected: &EmptyType, found: &EmptyType) -> bool {
+    use IntegerType::*;
+    match (expected.resolved(), found.resolved()) {
+        (EmptyType::Pointer(expected), EmptyType::Pointer(found)) => {
+            pointer_assignable(expected, found)
+        }
+        (EmptyType::Integer(I32), EmptyType::Integer(found)) => *found == I32,
+        (EmptyType::Integer(I64 | PointerSize), found) => {
+            matches!(found, EmptyType::Integer(I64 | PointerSize))
+        }
+        (expected, found) => expected == found,
+    }
+}
+
+/// Whether a pointer of type `found` may be used where `expected` is needed.
+/// A `*mut T` may be used as a `*T`, but not the other way around. Through
+/// a `*mut` the pointee may be replaced, so it must be exactly the same
+/// type, otherwise a `**mut T` could store a `*T` where a `*mut T` belongs.
+fn pointer_assignable(expected: &Pointer, found: &Pointer) -> bool {
+    if expected.mutable && !found.mutable {
+        return false;
+    }
+    match (expected.ty.resolved(), found.ty.resolved()) {
+        (EmptyType::Pointer(expected_inner), EmptyType::Pointer(found_inner))
+            if !expected.mutable =>
+        {
+            pointer_assignable(expected_inner, found_inner)
+        }
+        _ => expected.ty == found.ty,
+    }
+}
+
+/// Explain why one pointer type cannot be used as another, where they
+/// differ only in which pointers may be written through.
+fn pointer_mutability_note(expected: &EmptyType, found: &EmptyType) -> Option<&'static str> {
+    let (EmptyType::Pointer(expected), EmptyType::Pointer(found)) =
+        (expected.resolved(), found.resolved())
+    else {
+        return None;
+    };
+    if erase_mutability(&expected.ty) != erase_mutability(&found.ty) {
+        return None;
+    }
+    if expected.mutable && !found.mutable {
+        Some("`*T` cannot be used as `*mut T`, as it may not be written through")
+    } else if expected.mutable {
+        Some("the type behind a `*mut` must match exactly, as it may be replaced through it")
+    } else {
+        pointer_mutability_note(&expected.ty, &found.ty)
+    }
+}
+
+/// A type with every pointer in it made a `*T`.
+fn erase_mutability(ty: &EmptyType) -> EmptyType {
+    match ty.resolved() {
+        EmptyType::Pointer(ptr) => {
+            EmptyType::Pointer(Box::new(Pointer::new(erase_mutability(&ptr.ty), false)))
+        }
+        ty => ty.clone(),
+    }
+}
+
+/// The type of a literal wanted as a value of type `hint`. Literals have
+/// their usual type unless one of the same kind is wanted.
+fn literal_type(literal: &ast::Literal, hint: Option<&EmptyType>) -> EmptyType {
+    match (literal, hint.map(EmptyType::resolved)) {
+        (ast::Literal::Integer(_), Some(EmptyType::Integer(_)))
+        | (ast::Literal::Float(_), Some(EmptyType::Float(_))) => {
+            hint.expect("matched above").clone()
+        }
+        (ast::Literal::Integer(_), _) => EmptyType::Integer(IntegerType::I32),
+        (ast::Literal::Float(_), _) => EmptyType::Float(FloatType::F64),
+    }
+}
+
+/// The value of a literal, or constant, of the given type. An integer must
+/// fit in the type, `i64` and `isize` being the widest.
+fn literal_value(literal: &ast::Literal, ty: &EmptyType) -> CompileResult<ExprKind> {
+    let value = match literal {
+        ast::Literal::Float(value) => value.base10_parse().ok().map(ExprKind::Float),
+        ast::Literal::Integer(value) => value
+            .base10_parse()
+            .ok()
+            .filter(|&int| match ty.resolved() {
+                EmptyType::Integer(IntegerType::I32) => i32::try_from(int).is_ok(),
+                _ => true,
+            })
+            .map(ExprKind::Int),
+    };
+    match value {
+        Some(value) => Ok(value),
+        None => error(
+            literal.span(),
+            format!("Cannot represent the literal as a value of type `{ty}`"),
+        ),
+    }
+}
+
+/// The type of a block, or an `if` with the given branches, from the values
+/// of the branches which have one. That is the type of the first, unless
+/// it may be used as the type of a later one, such as a `*mut T` where
+/// another branch is a `*T`. Where every branch returns, there is no value
+/// and any type will do.
+fn value_type<const N: usize>(
+    branches: [Option<&typed::Block>; N],
+    hint: Option<&EmptyType>,
+) -> EmptyType {
+    branches
+        .into_iter()
+        .flatten()
+        .filter_map(|block| block.value.as_ref().map(|value| &value.ty))
+        .reduce(|ty, next| match (ty.resolved(), next.resolved()) {
+            (EmptyType::Pointer(ptr), EmptyType::Pointer(next_ptr))
+                if pointer_assignable(next_ptr, ptr) =>
+            {
+                next
+            }
+            _ => ty,
+        })
+        .or(hint)
+        .cloned()
+        .unwrap_or(EmptyType::Integer(IntegerType::I32))
+}
+
+/// The type given to `null` where nothing says which pointer it is.
+fn any_pointer() -> EmptyType {
+    EmptyType::Pointer(Box::new(Pointer::new(
+        EmptyType::Integer(IntegerType::I64),
+        false,
+    )))
+}
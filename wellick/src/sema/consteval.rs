@@ -0,0 +1,781 @@
+use super::layout;
+use super::mangle;
+use super::structs::Structs;
+use crate::diagnostics::{error, CompileError, CompileResult};
+use crate::parser::ast;
+use crate::parser::ast::{EmptyType, FloatType, IntegerType};
+use std::collections::HashMap;
+
+/// The number of expressions and statements the evaluation of a single
+/// constant may run, so that a `const fn` which never returns is reported
+/// rather than hanging the compiler.
+const STEP_LIMIT: usize = 1_000_000;
+
+/// How deeply calls to `const fn`s may nest while evaluating a constant.
+const CALL_DEPTH_LIMIT: usize = 128;
+
+/// The value of a constant, which is used in place of its name.
+#[derive(Debug, Clone)]
+pub(crate) struct Const {
+    pub ty: EmptyType,
+    pub value: ast::Literal,
+}
+
+/// Every constant in the program, by name.
+pub(crate) type Consts = HashMap<String, Const>;
+
+/// Check the constants of a program and the `const fn`s they may call,
+/// before their types are checked and they are evaluated.
+///
+/// Constant initializers may use literals, other constants, `if`s and blocks,
+/// the arithmetic and comparison builtins, `size_of` and `align_of`, and
+/// calls to `const fn`s. The bodies of `const fn`s are held to the same
+/// rules, whether or not they are called from a constant, as they must be
+/// evaluable at compile time.
+///
+/// Returns every constant with a placeholder value, enough to check the
+/// types of code which uses them.
+pub(crate) fn check_consts(
+    decls: &[ast::ConstDecl],
+    functions: &[ast::FnDecl],
+) -> CompileResult<Consts> {
+    let mut placeholders = Consts::new();
+    for decl in decls {
+        let value = match decl.ty.resolved() {
+            EmptyType::Integer(_) => {
+                ast::Literal::Integer(ast::IntegerLiteral::new("0", decl.span))
+            }
+            EmptyType::Float(_) => ast::Literal::Float(ast::FloatLiteral::new("0.0", decl.span)),
+            _ => {
+                return error(
+                    decl.span,
+                    format!(
+                        "Constants of type `{}` are not supported, found in `{}`",
+                        decl.ty, decl.name
+                    ),
+                );
+            }
+        };
+        let placeholder = Const {
+            ty: decl.ty.clone(),
+            value,
+        };
+        if placeholders
+            .insert(decl.name.clone(), placeholder)
+            .is_some()
+        {
+            return error(
+                decl.span,
+                format!("The constant `{}` is defined multiple times", decl.name),
+            );
+        }
+    }
+
+    let functions: HashMap<&str, &ast::FnDecl> = functions
+        .iter()
+        .map(|func| (func.name.as_str(), func))
+        .collect();
+    for func in functions.values().filter(|func| func.is_const) {
+        let checker = ConstChecker {
+            functions: &functions,
+            context: format!("const fn `{}`", func.name),
+        };
+        for stmt in &func.body {
+            checker.check_stmt(stmt)?;
+        }
+    }
+
+    let mut decls: Vec<&ast::ConstDecl> = decls.iter().collect();
+    decls.sort_by(|a, b| a.name.cmp(&b.name));
+    for decl in decls {
+        ConstChecker {
+            functions: &functions,
+            context: format!("constant `{}`", decl.name),
+        }
+        .check_expr(&decl.value)?;
+    }
+    Ok(placeholders)
+}
+
+/// A function returning the initializer of a constant, so that its types
+/// are checked by the same rules as any other code.
+pub(crate) fn initializer_fn(decl: &ast::ConstDecl) -> ast::FnDecl {
+    let ret = ast::Return {
+        value: decl.value.clone(),
+        span: decl.value.span(),
+    };
+    ast::FnDecl::new(
+        mangle::const_initializer(&decl.name),
+        Vec::new(),
+        Vec::new(),
+        decl.ty.clone(),
+        vec![ast::Stmt::Return(ret)],
+        decl.span,
+    )
+}
+
+/// Compute the value of every constant at compile time, once they have been
+/// checked.
+///
+/// Constant initializers are interpreted directly from the AST, calling the
+/// given `const fn`s.
+pub(crate) fn evaluate_consts(
+    decls: Vec<ast::ConstDecl>,
+    functions: &[ast::FnDecl],
+    structs: &Structs,
+) -> CompileResult<Consts> {
+    let by_name: HashMap<String, ast::ConstDecl> = decls
+        .into_iter()
+        .map(|decl| (decl.name.clone(), decl))
+        .collect();
+    let functions: HashMap<&str, &ast::FnDecl> = functions
+        .iter()
+        .map(|func| (func.name.as_str(), func))
+        .collect();
+
+    let mut evaluator = Evaluator {
+        decls: &by_name,
+        functions: &functions,
+        structs,
+        consts: Consts::new(),
+        evaluating: Vec::new(),
+        steps: 0,
+        depth: 0,
+        frames: Vec::new(),
+    };
+    let mut names: Vec<&String> = by_name.keys().collect();
+    names.sort();
+    for name in names {
+        evaluator.evaluate(name)?;
+    }
+    Ok(evaluator.consts)
+}
+
+/// Check that code to be run at compile time only does what the evaluator
+/// supports, reporting the first thing which it does not.
+struct ConstChecker<'a> {
+    functions: &'a HashMap<&'a str, &'a ast::FnDecl>,
+    /// Where the code is, for diagnostics, e.g. "const fn `square`".
+    context: String,
+}
+
+impl ConstChecker<'_> {
+    fn error<T>(&self, span: ast::Span, what: &str) -> CompileResult<T> {
+        error(span, format!("{what} is not supported in {}", self.context))
+    }
+
+    fn check_stmt(&self, stmt: &ast::Stmt) -> CompileResult<()> {
+        match stmt {
+            ast::Stmt::Assign(assign) => match &assign.value {
+                Some(value) => self.check_expr(value),
+                None => Ok(()),
+            },
+            ast::Stmt::ReAssign(local) => {
+                self.check_place(&local.target)?;
+                self.check_expr(&local.value)
+            }
+            ast::Stmt::CompoundAssign(assign) => {
+                self.check_place(&assign.target)?;
+                self.check_expr(&assign.value)
+            }
+            ast::Stmt::Return(ast::Return { value: expr, .. }) => self.check_expr(expr),
+            ast::Stmt::Call(call) => self.check_call(call),
+            ast::Stmt::MethodCall(call) => self.error(call.span, "Calling a method"),
+            ast::Stmt::If(if_stmt) => self.check_if(if_stmt),
+            ast::Stmt::Block(block) => self.check_block(block),
+        }
+    }
+
+    fn check_place(&self, place: &ast::Expression) -> CompileResult<()> {
+        match place {
+            ast::Expression::Identifier(_) => Ok(()),
+            ast::Expression::Field(_, _) => self.error(place.span(), "Assigning to a field"),
+            _ => self.error(place.span(), "Assigning through a pointer"),
+        }
+    }
+
+    fn check_block(&self, block: &ast::Block) -> CompileResult<()> {
+        for stmt in &block.stmts {
+            self.check_stmt(stmt)?;
+        }
+        match &block.value {
+            Some(value) => self.check_expr(value),
+            None => Ok(()),
+        }
+    }
+
+    fn check_if(&self, if_expr: &ast::If) -> CompileResult<()> {
+        self.check_expr(&if_expr.condition)?;
+        self.check_block(&if_expr.then_body)?;
+        match &if_expr.else_body {
+            Some(else_body) => self.check_block(else_body),
+            None => Ok(()),
+        }
+    }
+
+    fn check_expr(&self, expr: &ast::Expression) -> CompileResult<()> {
+        match expr {
+            ast::Expression::Literal(_) | ast::Expression::Identifier(_) => Ok(()),
+            ast::Expression::Call(call) => self.check_call(call),
+            ast::Expression::If(if_expr) => self.check_if(if_expr),
+            ast::Expression::Block(block) => self.check_block(block),
+            ast::Expression::Comparison(comparison) => {
+                self.check_expr(&comparison.lhs)?;
+                self.check_expr(&comparison.rhs)
+            }
+            ast::Expression::AddressOf(_) => {
+                self.error(expr.span(), "Taking the address of a value")
+            }
+            ast::Expression::DeRef(_) => self.error(expr.span(), "Dereferencing a pointer"),
+            ast::Expression::Null(_) => self.error(expr.span(), "A pointer"),
+            ast::Expression::StructLiteral(_) | ast::Expression::Field(_, _) => {
+                self.error(expr.span(), "A struct")
+            }
+            ast::Expression::MethodCall(_) => self.error(expr.span(), "Calling a method"),
+        }
+    }
+
+    fn check_call(&self, call: &ast::Call) -> CompileResult<()> {
+        let is_const = match self.functions.get(call.func.as_str()) {
+            Some(func) if !func.type_params.is_empty() => {
+                return self.error(
+                    call.span,
+                    &format!("Calling the generic function `{}`", call.func),
+                )
+            }
+            Some(func) => func.is_const,
+            None => {
+                is_const_builtin(&call.func) || matches!(call.func.as_str(), "size_of" | "align_of")
+            }
+        };
+        if !is_const {
+            return error(
+                call.span,
+                format!(
+                    "Cannot call non-const fn `{}` in {}",
+                    call.func, self.context
+                ),
+            );
+        }
+        for arg in &call.args {
+            self.check_expr(arg)?;
+        }
+        Ok(())
+    }
+}
+
+/// Whether a builtin function can be evaluated at compile time, which is the
+/// case for those which only compute a result from their arguments.
+fn is_const_builtin(name: &str) -> bool {
+    matches!(
+        name,
+        "iadd"
+            | "isub"
+            | "imul"
+            | "idiv"
+            | "imod"
+            | "ieq"
+            | "ilt"
+            | "ilteq"
+            | "igt"
+            | "fadd"
+            | "fsub"
+            | "fmul"
+            | "fdiv"
+            | "feq"
+            | "flt"
+            | "flteq"
+            | "fgt"
+            | "f32_to_f64"
+            | "f64_to_f32"
+    )
+}
+
+/// A value computed at compile time.
+#[derive(Debug, Clone, Copy)]
+enum Value {
+    Int(i64),
+    Float(f64),
+}
+
+/// Control leaving the code being evaluated early, through a `return` out of
+/// a function or an error which stops evaluation altogether.
+enum Unwind {
+    Return(Value),
+    Error(CompileError),
+}
+
+impl From<CompileError> for Unwind {
+    fn from(err: CompileError) -> Self {
+        Unwind::Error(err)
+    }
+}
+
+/// A variable of a `const fn` being evaluated.
+struct Local {
+    /// The current value, or `None` before a `let` without one is assigned.
+    value: Option<Value>,
+    ty: Option<EmptyType>,
+    mutable: bool,
+}
+
+/// The variables of one call, organised as a stack of lexical scopes.
+type Frame = Vec<HashMap<String, Local>>;
+
+struct Evaluator<'a> {
+    decls: &'a HashMap<String, ast::ConstDecl>,
+    functions: &'a HashMap<&'a str, &'a ast::FnDecl>,
+    structs: &'a Structs,
+    consts: Consts,
+    /// The constants being evaluated, innermost last, to detect cycles.
+    evaluating: Vec<String>,
+    steps: usize,
+    depth: usize,
+    frames: Vec<Frame>,
+}
+
+impl Evaluator<'_> {
+    /// An error in evaluating the innermost constant, which is reported at
+    /// the constant.
+    fn error(&self, message: &str) -> CompileError {
+        let name = self
+            .evaluating
+            .last()
+            .expect("errors happen while evaluating a constant");
+        CompileError::new(
+            self.decls[name].span,
+            format!("Cannot evaluate constant `{name}`: {message}"),
+        )
+    }
+
+    /// Evaluate a constant, if it has not been already.
+    fn evaluate(&mut self, name: &str) -> CompileResult<Value> {
+        if let Some(value) = self.consts.get(name) {
+            return literal_value(&value.value);
+        }
+        if self.evaluating.iter().any(|other| other == name) {
+            return error(
+                self.decls[name].span,
+                format!("The constant `{name}` depends on its own value"),
+            );
+        }
+
+        let decl = &self.decls[name];
+        self.evaluating.push(name.to_string());
+        let (steps, frames) = (self.steps, std::mem::take(&mut self.frames));
+        self.steps = 0;
+        self.frames.push(vec![HashMap::new()]);
+        let value = match self.eval_expr(&decl.value) {
+            Ok(value) | Err(Unwind::Return(value)) => value,
+            Err(Unwind::Error(err)) => return Err(err),
+        };
+        let value = self.fit(value, &decl.ty)?;
+        self.steps = steps;
+        self.frames = frames;
+        self.evaluating.pop();
+
+        let literal = match value {
+            Value::Int(value) => {
+                ast::Literal::Integer(ast::IntegerLiteral::new(&value.to_string(), decl.span))
+            }
+            Value::Float(value) => {
+                ast::Literal::Float(ast::FloatLiteral::new(&format!("{value:?}"), decl.span))
+            }
+        };
+        self.consts.insert(
+            name.to_string(),
+            Const {
+                ty: decl.ty.clone(),
+                value: literal,
+            },
+        );
+        Ok(value)
+    }
+
+    /// Count a step of evaluation, stopping once there have been too many.
+    fn step(&mut self) -> CompileResult<()> {
+        self.steps += 1;
+        if self.steps > STEP_LIMIT {
+            return Err(self.error(&format!(
+                "evaluation took more than {STEP_LIMIT} steps, it may never finish"
+            )));
+        }
+        Ok(())
+    }
+
+    /// Check a value has the given type, rounding floats to its precision.
+    fn fit(&self, value: Value, ty: &EmptyType) -> CompileResult<Value> {
+        match (value, ty.resolved()) {
+            (Value::Int(int), EmptyType::Integer(IntegerType::I32)) => {
+                if i32::try_from(int).is_err() {
+                    return Err(self.error(&format!("the value {int} does not fit in `i32`")));
+                }
+                Ok(value)
+            }
+            (Value::Int(_), EmptyType::Integer(_)) => Ok(value),
+            (Value::Float(float), EmptyType::Float(FloatType::F32)) => {
+                Ok(Value::Float(float as f32 as f64))
+            }
+            (Value::Float(_), EmptyType::Float(FloatType::F64)) => Ok(value),
+            (Value::Int(_), ty) => Err(self.error(&format!("expected `{ty}`, found an integer"))),
+            (Value::Float(_), ty) => Err(self.error(&format!("expected `{ty}`, found a float"))),
+        }
+    }
+
+    fn frame(&mut self) -> &mut Frame {
+        self.frames.last_mut().expect("there is always a frame")
+    }
+
+    fn lookup(&mut self, name: &str) -> CompileResult<Value> {
+        let local = self
+            .frame()
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .map(|local| local.value);
+        match local {
+            Some(Some(value)) => Ok(value),
+            Some(None) => Err(self.error(&format!("use of uninitialized variable `{name}`"))),
+            None if self.decls.contains_key(name) => self.evaluate(name),
+            None => Err(self.error(&format!("cannot find value `{name}` in this scope"))),
+        }
+    }
+
+    fn eval_block(&mut self, block: &ast::Block) -> Result<Option<Value>, Unwind> {
+        self.frame().push(HashMap::new());
+        let mut result = Ok(None);
+        for stmt in &block.stmts {
+            if let Err(unwind) = self.eval_stmt(stmt) {
+                result = Err(unwind);
+                break;
+            }
+        }
+        if let (Ok(_), Some(value)) = (&result, &block.value) {
+            result = self.eval_expr(value).map(Some);
+        }
+        self.frame().pop();
+        result
+    }
+
+    fn eval_stmt(&mut self, stmt: &ast::Stmt) -> Result<(), Unwind> {
+        self.step()?;
+        match stmt {
+            ast::Stmt::Assign(assign) => {
+                let value = match (&assign.value, &assign.var_type) {
+                    (Some(value), Some(ty)) => {
+                        let value = self.eval_expr(value)?;
+                        Some(self.fit(value, ty)?)
+                    }
+                    (Some(value), None) => Some(self.eval_expr(value)?),
+                    (None, _) => None,
+                };
+                let local = Local {
+                    value,
+                    ty: assign.var_type.clone(),
+                    mutable: assign.mutable,
+                };
+                self.frame()
+                    .last_mut()
+                    .expect("there is always a scope")
+                    .insert(assign.target.ident.clone(), local);
+            }
+            ast::Stmt::ReAssign(local) => {
+                let value = self.eval_expr(&local.value)?;
+                self.assign(&local.target, value)?;
+            }
+            ast::Stmt::CompoundAssign(assign) => {
+                let value = self.eval_expr(&assign.value)?;
+                let current = self.eval_expr(&assign.target)?;
+                let result = self.compound(assign.op, current, value)?;
+                self.assign(&assign.target, result)?;
+            }
+            ast::Stmt::Return(ast::Return { value: expr, .. }) => {
+                return Err(Unwind::Return(self.eval_expr(expr)?))
+            }
+            ast::Stmt::Call(call) => {
+                self.eval_call(call)?;
+            }
+            ast::Stmt::If(if_stmt) => {
+                self.eval_if(if_stmt)?;
+            }
+            ast::Stmt::Block(block) => {
+                self.eval_block(block)?;
+            }
+            ast::Stmt::MethodCall(_) => unreachable!("checked before evaluation"),
+        }
+        Ok(())
+    }
+
+    /// Overwrite the value of a variable.
+    fn assign(&mut self, target: &ast::Expression, value: Value) -> CompileResult<()> {
+        let name = match target {
+            ast::Expression::Identifier(name) => &name.ident,
+            _ => unreachable!("checked before evaluation"),
+        };
+        let local = self
+            .frames
+            .last()
+            .expect("there is always a frame")
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name));
+        // A variable declared without a value may be assigned once, even if
+        // it is immutable.
+        let (mutable, ty) = match local {
+            Some(local) => (local.mutable || local.value.is_none(), local.ty.clone()),
+            None if self.decls.contains_key(name) => {
+                return Err(self.error(&format!("cannot assign to constant `{name}`")))
+            }
+            None => return Err(self.error(&format!("cannot find value `{name}` in this scope"))),
+        };
+        if !mutable {
+            return Err(self.error(&format!("cannot mutate immutable variable {name}")));
+        }
+        let value = match &ty {
+            Some(ty) => self.fit(value, ty)?,
+            None => value,
+        };
+        let local = self
+            .frame()
+            .iter_mut()
+            .rev()
+            .find_map(|scope| scope.get_mut(name))
+            .expect("the variable was found above");
+        local.value = Some(value);
+        Ok(())
+    }
+
+    fn eval_if(&mut self, if_expr: &ast::If) -> Result<Option<Value>, Unwind> {
+        let condition = self.eval_expr(&if_expr.condition)?;
+        let taken = match condition {
+            Value::Int(value) => value != 0,
+            Value::Float(_) => {
+                return Err(self
+                    .error("the condition of an `if` must be an integer")
+                    .into())
+            }
+        };
+        if taken {
+            self.eval_block(&if_expr.then_body)
+        } else {
+            match &if_expr.else_body {
+                Some(else_body) => self.eval_block(else_body),
+                None => Ok(None),
+            }
+        }
+    }
+
+    fn eval_expr(&mut self, expr: &ast::Expression) -> Result<Value, Unwind> {
+        self.step()?;
+        match expr {
+            ast::Expression::Literal(literal) => Ok(literal_value(literal)?),
+            ast::Expression::Identifier(name) => Ok(self.lookup(name)?),
+            ast::Expression::Call(call) => self.eval_call(call),
+            ast::Expression::If(if_expr) => match self.eval_if(if_expr)? {
+                Some(value) => Ok(value),
+                None => Err(self
+                    .error("an `if` used as a value did not produce one")
+                    .into()),
+            },
+            ast::Expression::Block(block) => match self.eval_block(block)? {
+                Some(value) => Ok(value),
+                None => Err(self
+                    .error("a block used as a value did not produce one")
+                    .into()),
+            },
+            ast::Expression::Comparison(comparison) => {
+                let lhs = self.eval_expr(&comparison.lhs)?;
+                let rhs = self.eval_expr(&comparison.rhs)?;
+                let equal = match (lhs, rhs) {
+                    (Value::Int(lhs), Value::Int(rhs)) => lhs == rhs,
+                    (Value::Float(lhs), Value::Float(rhs)) => lhs == rhs,
+                    _ => return Err(self.error("cannot compare an integer with a float").into()),
+                };
+                let holds = match comparison.op {
+                    ast::ComparisonOp::Eq => equal,
+                    ast::ComparisonOp::Ne => !equal,
+                };
+                Ok(Value::Int(i64::from(holds)))
+            }
+            _ => unreachable!("checked before evaluation"),
+        }
+    }
+
+    fn eval_call(&mut self, call: &ast::Call) -> Result<Value, Unwind> {
+        if let Some(value) = layout::intrinsic(call, self.structs)? {
+            return Ok(Value::Int(i64::from(value)));
+        }
+
+        let mut args = Vec::new();
+        for arg in &call.args {
+            args.push(self.eval_expr(arg)?);
+        }
+
+        let func = match self.functions.get(call.func.as_str()) {
+            Some(func) => *func,
+            None => return Ok(self.eval_builtin(&call.func, &args)?),
+        };
+        if args.len() != func.args.len() {
+            return Err(self
+                .error(&format!(
+                    "function `{}` takes {} argument(s) but {} were supplied",
+                    func.name,
+                    func.args.len(),
+                    args.len()
+                ))
+                .into());
+        }
+
+        self.depth += 1;
+        if self.depth > CALL_DEPTH_LIMIT {
+            return Err(self
+                .error(&format!(
+                    "calls to const fns were nested more than {CALL_DEPTH_LIMIT} deep"
+                ))
+                .into());
+        }
+        let mut params = HashMap::new();
+        for (arg, value) in func.args.iter().zip(args) {
+            let local = Local {
+                value: Some(self.fit(value, &arg.t)?),
+                ty: Some(arg.t.clone()),
+                mutable: arg.mutable,
+            };
+            params.insert(arg.name.clone(), local);
+        }
+        self.frames.push(vec![params]);
+
+        let mut result = None;
+        for stmt in &func.body {
+            match self.eval_stmt(stmt) {
+                Ok(()) => {}
+                Err(Unwind::Return(value)) => {
+                    result = Some(value);
+                    break;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+
+        self.frames.pop();
+        self.depth -= 1;
+        match result {
+            Some(value) => Ok(self.fit(value, &func.ret_type)?),
+            None => Err(self
+                .error(&format!("function `{}` did not return a value", func.name))
+                .into()),
+        }
+    }
+
+    /// Evaluate one of the builtins which are allowed at compile time.
+    fn eval_builtin(&self, name: &str, args: &[Value]) -> CompileResult<Value> {
+        let expected = match name {
+            "f32_to_f64" | "f64_to_f32" => 1,
+            _ => 2,
+        };
+        if args.len() != expected {
+            return Err(self.error(&format!(
+                "function `{name}` takes {expected} argument(s) but {} were supplied",
+                args.len()
+            )));
+        }
+
+        if name.starts_with('i') {
+            let int = |value: Value| match value {
+                Value::Int(int) => i32::try_from(int)
+                    .map_err(|_| self.error(&format!("the value {int} does not fit in `i32`"))),
+                Value::Float(_) => Err(self.error(&format!("`{name}` expects an integer"))),
+            };
+            let (a, b) = (int(args[0])?, int(args[1])?);
+            let result = match name {
+                "iadd" => a.checked_add(b),
+                "isub" => a.checked_sub(b),
+                "imul" => a.checked_mul(b),
+                "idiv" | "imod" if b == 0 => return Err(self.error("attempt to divide by zero")),
+                "idiv" => a.checked_div(b),
+                "imod" => a.checked_rem(b),
+                "ieq" => Some(i32::from(a == b)),
+                "ilt" => Some(i32::from(a < b)),
+                "ilteq" => Some(i32::from(a <= b)),
+                "igt" => Some(i32::from(a > b)),
+                _ => unreachable!("only const builtins pass the checks"),
+            };
+            match result {
+                Some(result) => Ok(Value::Int(i64::from(result))),
+                None => Err(self.error(&format!("`{name}({a}, {b})` overflows `i32`"))),
+            }
+        } else {
+            let float = |value: Value| match value {
+                Value::Float(float) => Ok(float),
+                Value::Int(_) => Err(self.error(&format!("`{name}` expects a float"))),
+            };
+            let a = float(args[0])?;
+            if name == "f32_to_f64" || name == "f64_to_f32" {
+                return Ok(Value::Float(a as f32 as f64));
+            }
+            let b = float(args[1])?;
+            Ok(match name {
+                "fadd" => Value::Float(a + b),
+                "fsub" => Value::Float(a - b),
+                "fmul" => Value::Float(a * b),
+                "fdiv" => Value::Float(a / b),
+                "feq" => Value::Int(i64::from(a == b)),
+                "flt" => Value::Int(i64::from(a < b)),
+                "flteq" => Value::Int(i64::from(a <= b)),
+                "fgt" => Value::Int(i64::from(a > b)),
+                _ => unreachable!("only const builtins pass the checks"),
+            })
+        }
+    }
+
+    /// Apply the operator of a compound assignment.
+    fn compound(&self, op: ast::CompoundOp, current: Value, value: Value) -> CompileResult<Value> {
+        use ast::CompoundOp::*;
+        match (current, value) {
+            (Value::Int(a), Value::Int(b)) => {
+                let result = match op {
+                    Add => a.checked_add(b),
+                    Sub => a.checked_sub(b),
+                    Mul => a.checked_mul(b),
+                    Div | Rem if b == 0 => return Err(self.error("attempt to divide by zero")),
+                    Div => a.checked_div(b),
+                    Rem => a.checked_rem(b),
+                    BitAnd => Some(a & b),
+                    BitOr => Some(a | b),
+                    BitXor => Some(a ^ b),
+                    Shl => u32::try_from(b).ok().and_then(|b| a.checked_shl(b)),
+                    Shr => u32::try_from(b).ok().and_then(|b| a.checked_shr(b)),
+                };
+                match result {
+                    Some(result) => Ok(Value::Int(result)),
+                    None => Err(self.error(&format!("`{a} {op} {b}` overflows"))),
+                }
+            }
+            (Value::Float(a), Value::Float(b)) => match op {
+                Add => Ok(Value::Float(a + b)),
+                Sub => Ok(Value::Float(a - b)),
+                Mul => Ok(Value::Float(a * b)),
+                Div => Ok(Value::Float(a / b)),
+                _ => Err(self.error(&format!("cannot apply `{op}` to a float"))),
+            },
+            _ => Err(self.error(&format!("cannot apply `{op}` to an integer and a float"))),
+        }
+    }
+}
+
+/// The value of a literal, which must fit in 64 bits.
+fn literal_value(literal: &ast::Literal) -> CompileResult<Value> {
+    match literal {
+        ast::Literal::Integer(literal) => match literal.base10_parse() {
+            Ok(value) => Ok(Value::Int(value)),
+            Err(_) => error(
+                literal.span,
+                format!("The literal {literal:?} is too large"),
+            ),
+        },
+        ast::Literal::Float(literal) => match literal.base10_parse() {
+            Ok(value) => Ok(Value::Float(value)),
+            Err(_) => error(literal.span, format!("Invalid float literal {literal:?}")),
+        },
+    }
+}
@@ -0,0 +1,131 @@
+use super::mangle;
+use super::typed::{Block, Expr, ExprKind, Function, If, Stmt, StmtKind};
+use crate::diagnostics::CompileError;
+use crate::lints::{LintReporter, UNREACHABLE_CODE};
+use crate::parser::ast::Attribute;
+
+/// Check that every path through a function reaches a `return`. A function
+/// body has no value of its own, a final expression has already been made
+/// into a `return` by the parser.
+pub(crate) fn check_returns(func: &Function) -> Option<CompileError> {
+    if func.body.iter().any(Stmt::diverges) {
+        return None;
+    }
+    Some(CompileError::new(
+        func.span,
+        format!(
+            "Function `{}` may not return a value",
+            mangle::declared_name(&func.name)
+        ),
+    ))
+}
+
+/// Remove the code following a statement which returns, in every block of
+/// a function, reporting it as a lint. Such code can never run, and code
+/// generation cannot add instructions after a `return`.
+pub(crate) fn remove_unreachable(func: &mut Function, lints: &mut LintReporter) {
+    let mut remover = Remover {
+        attributes: &func.attributes,
+        lints,
+    };
+    let mut value = None;
+    remover.stmts(&mut func.body, &mut value);
+}
+
+struct Remover<'a, 'b> {
+    /// The attributes of the function, which may allow the lint.
+    attributes: &'a [Attribute],
+    lints: &'a mut LintReporter<'b>,
+}
+
+impl Remover<'_, '_> {
+    /// Remove the statements after the first which returns, along with the
+    /// value of the block they are in, then look inside those which remain.
+    fn stmts(&mut self, stmts: &mut Vec<Stmt>, value: &mut Option<Box<Expr>>) {
+        if let Some(end) = stmts.iter().position(Stmt::diverges) {
+            let unreachable: Vec<_> = stmts[end + 1..]
+                .iter()
+                .map(|stmt| (stmt.span, "statement"))
+                .chain(value.as_ref().map(|value| (value.span, "expression")))
+                .collect();
+            if let (Some((first, kind)), Some((last, _))) =
+                (unreachable.first(), unreachable.last())
+            {
+                self.lints.report(
+                    &UNREACHABLE_CODE,
+                    self.attributes,
+                    first.to(*last),
+                    format!("Unreachable {kind}"),
+                );
+            }
+            stmts.truncate(end + 1);
+            *value = None;
+        }
+
+        for stmt in stmts {
+            self.stmt(stmt);
+        }
+        if let Some(value) = value {
+            self.expr(value);
+        }
+    }
+
+    fn stmt(&mut self, stmt: &mut Stmt) {
+        match &mut stmt.kind {
+            StmtKind::Let(_, value) => {
+                if let Some(value) = value {
+                    self.expr(value);
+                }
+            }
+            StmtKind::Assign(place, value) | StmtKind::CompoundAssign(place, _, value) => {
+                self.expr(place);
+                self.expr(value);
+            }
+            StmtKind::Return(expr) | StmtKind::Expr(expr) => self.expr(expr),
+            StmtKind::If(if_stmt) => self.if_expr(if_stmt),
+            StmtKind::Block(block) => self.block(block),
+        }
+    }
+
+    fn block(&mut self, block: &mut Block) {
+        self.stmts(&mut block.stmts, &mut block.value);
+    }
+
+    fn if_expr(&mut self, if_expr: &mut If) {
+        self.expr(&mut if_expr.condition);
+        self.block(&mut if_expr.then_body);
+        if let Some(else_body) = &mut if_expr.else_body {
+            self.block(else_body);
+        }
+    }
+
+    /// Look for blocks within an expression, such as the value of a `let`.
+    fn expr(&mut self, expr: &mut Expr) {
+        match &mut expr.kind {
+            ExprKind::Deref(inner) | ExprKind::Field(inner, _) | ExprKind::IsNull(inner) => {
+                self.expr(inner)
+            }
+            ExprKind::StructLiteral(fields) => {
+                for (_, value) in fields {
+                    self.expr(value);
+                }
+            }
+            ExprKind::Call(_, args) => {
+                for arg in args {
+                    self.expr(arg);
+                }
+            }
+            ExprKind::Comparison(_, lhs, rhs) => {
+                self.expr(lhs);
+                self.expr(rhs);
+            }
+            ExprKind::If(if_expr) => self.if_expr(if_expr),
+            ExprKind::Block(block) => self.block(block),
+            ExprKind::Int(_)
+            | ExprKind::Float(_)
+            | ExprKind::Null
+            | ExprKind::Local(_)
+            | ExprKind::AddressOf(_) => {}
+        }
+    }
+}
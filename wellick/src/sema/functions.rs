@@ -0,0 +1,78 @@
+use crate::parser::ast;
+use crate::parser::ast::{EmptyType, FloatType, IntegerType};
+use std::collections::HashMap;
+
+/// The parameter and return types of a function, as written in Wellick.
+#[derive(Debug, Clone)]
+pub(crate) struct FnSignature {
+    pub params: Vec<EmptyType>,
+    pub ret_type: EmptyType,
+}
+
+impl FnSignature {
+    fn new(params: Vec<EmptyType>, ret_type: EmptyType) -> Self {
+        Self { params, ret_type }
+    }
+}
+
+impl From<&ast::FnDecl> for FnSignature {
+    fn from(func: &ast::FnDecl) -> Self {
+        Self::new(
+            func.args.iter().map(|arg| arg.t.clone()).collect(),
+            func.ret_type.clone(),
+        )
+    }
+}
+
+pub(crate) fn build_fn_map(nodes: &[ast::FnDecl]) -> HashMap<String, ast::FnDecl> {
+    nodes
+        .iter()
+        .map(|node| (node.name.clone(), node.clone()))
+        .collect()
+}
+
+/// Signatures of the builtin functions, which are implemented in C and
+/// linked against the compiled Wellick program.
+pub(crate) fn builtin_signature(name: &str) -> Option<FnSignature> {
+    let i32_ty = || EmptyType::Integer(IntegerType::I32);
+    let i64_ty = || EmptyType::Integer(IntegerType::I64);
+    let f32_ty = || EmptyType::Float(FloatType::F32);
+    let f64_ty = || EmptyType::Float(FloatType::F64);
+
+    match name {
+        "iadd" | "isub" | "idiv" | "imul" | "ieq" | "ilteq" | "ilt" | "igt" | "imod" => {
+            Some(FnSignature::new(vec![i32_ty(), i32_ty()], i32_ty()))
+        }
+        "println" | "print" => Some(FnSignature::new(vec![i32_ty()], i32_ty())),
+        "print_addr" => Some(FnSignature::new(vec![i64_ty()], i64_ty())),
+        "fadd" | "fsub" | "fmul" | "fdiv" => {
+            Some(FnSignature::new(vec![f64_ty(), f64_ty()], f64_ty()))
+        }
+        "feq" | "flt" | "flteq" | "fgt" => {
+            Some(FnSignature::new(vec![f64_ty(), f64_ty()], i32_ty()))
+        }
+        "println_f64" | "print_f64" => Some(FnSignature::new(vec![f64_ty()], i32_ty())),
+        "println_f32" => Some(FnSignature::new(vec![f32_ty()], i32_ty())),
+        "f32_to_f64" => Some(FnSignature::new(vec![f32_ty()], f64_ty())),
+        "f64_to_f32" => Some(FnSignature::new(vec![f64_ty()], f32_ty())),
+        _ => None,
+    }
+}
+
+/// Whether the function is one of the builtins which print their argument.
+/// They return a value only because every function must, so it is fine to
+/// discard.
+pub(crate) fn is_printing_builtin(name: &str) -> bool {
+    matches!(
+        name,
+        "println" | "print" | "print_addr" | "println_f64" | "print_f64" | "println_f32"
+    )
+}
+
+/// Find the signature of a builtin or user defined function.
+pub(crate) fn lookup_signature(
+    functions: &HashMap<String, ast::FnDecl>,
+    name: &str,
+) -> Option<FnSignature> {
+    builtin_signature(name).or_else(|| functions.get(name).map(FnSignature::from))
+}
@@ -0,0 +1,259 @@
+use super::functions::FnSignature;
+use super::mangle;
+use crate::diagnostics::{error, CompileError, CompileResult};
+use crate::parser::ast;
+use crate::parser::ast::EmptyType;
+use std::collections::HashMap;
+
+/// How deep instances may be created from within other instances, which is
+/// only reached by a generic function calling itself with ever larger type
+/// arguments, e.g. `r::<*T>` from `r::<T>`.
+const RECURSION_LIMIT: usize = 128;
+
+/// Generic functions, and the concrete instances of them used by the program.
+///
+/// Generic functions are never translated directly. Each distinct list of type
+/// arguments produces a copy of the function with the type parameters
+/// substituted, under a mangled name so that instances do not collide in the
+/// object file.
+pub(crate) struct Generics {
+    generics: HashMap<String, ast::FnDecl>,
+    instances: HashMap<String, FnSignature>,
+    /// How each instance is written, e.g. `max::<i64>` for `max$i64`.
+    paths: HashMap<String, String>,
+    /// How many instances each instance was created through.
+    depths: HashMap<String, usize>,
+    pending: Vec<ast::FnDecl>,
+}
+
+impl Generics {
+    pub fn new(generics: Vec<ast::FnDecl>) -> Self {
+        Self {
+            generics: generics
+                .into_iter()
+                .map(|func| (func.name.clone(), func))
+                .collect(),
+            instances: HashMap::new(),
+            paths: HashMap::new(),
+            depths: HashMap::new(),
+            pending: Vec::new(),
+        }
+    }
+
+    /// Find the generic function with the given name.
+    pub fn get(&self, name: &str) -> Option<&ast::FnDecl> {
+        self.generics.get(name)
+    }
+
+    /// Get the name of the instance of a generic function for the given type
+    /// arguments, creating the instance if it does not exist yet. `caller` is
+    /// the function the call at `span` is made from.
+    pub fn instantiate(
+        &mut self,
+        name: &str,
+        type_args: &[EmptyType],
+        caller: &str,
+        span: ast::Span,
+    ) -> CompileResult<String> {
+        let mangled = mangle::generic_instance(name, type_args);
+        if !self.instances.contains_key(&mangled) {
+            let args: Vec<String> = type_args.iter().map(EmptyType::to_string).collect();
+            let path = format!("{}::<{}>", mangle::call_path(name), args.join(", "));
+            let depth = self.depths.get(caller).map_or(0, |depth| depth + 1);
+            if depth >= RECURSION_LIMIT {
+                return error(
+                    span,
+                    format!("Reached the recursion limit while instantiating `{path}`"),
+                );
+            }
+
+            let instance = instance_of(self.generics[name].clone(), type_args);
+            self.paths.insert(mangled.clone(), path);
+            self.depths.insert(mangled.clone(), depth);
+            self.instances
+                .insert(mangled.clone(), FnSignature::from(&instance));
+            self.pending.push(instance);
+        }
+        Ok(mangled)
+    }
+
+    /// The declaration of an instance, without its body, for checking calls
+    /// to it from a generic function whose type arguments include its own
+    /// type parameters. No instance is created.
+    pub fn declaration(&self, name: &str, type_args: &[EmptyType]) -> ast::FnDecl {
+        let mut generic = self.generics[name].clone();
+        generic.body.clear();
+        instance_of(generic, type_args)
+    }
+
+    /// Add the instance a function is, if it is one, to an error found in
+    /// its body, as it may only be an error for some type arguments.
+    pub fn in_instance(&self, err: CompileError, name: &str) -> CompileError {
+        match self.paths.get(name) {
+            Some(path) => err.with_note(format!("in the instance `{path}`")),
+            None => err,
+        }
+    }
+
+    /// Find the signature of an instance which has already been created.
+    pub fn instance(&self, name: &str) -> Option<&FnSignature> {
+        self.instances.get(name)
+    }
+
+    /// Take the instances created since the last call, which still need their
+    /// bodies to be checked.
+    pub fn take_pending(&mut self) -> Vec<ast::FnDecl> {
+        std::mem::take(&mut self.pending)
+    }
+}
+
+/// Turn a generic function into the instance for the given type arguments.
+fn instance_of(mut generic: ast::FnDecl, type_args: &[EmptyType]) -> ast::FnDecl {
+    let bindings = generic
+        .type_params
+        .iter()
+        .map(|param| param.name.clone())
+        .zip(type_args.iter().cloned())
+        .collect();
+    generic.name = mangle::generic_instance(&generic.name, type_args);
+    generic.type_params.clear();
+    substitute_fn(&mut generic, &bindings);
+    generic
+}
+
+/// Replace every type parameter in `ty` with its bound type.
+pub(crate) fn substitute(ty: &EmptyType, bindings: &HashMap<String, EmptyType>) -> EmptyType {
+    match ty {
+        EmptyType::Named(name) => bindings.get(name).cloned().unwrap_or_else(|| ty.clone()),
+        EmptyType::Pointer(ptr) => EmptyType::Pointer(Box::new(ast::Pointer::new(
+            substitute(&ptr.ty, bindings),
+            ptr.mutable,
+        ))),
+        _ => ty.clone(),
+    }
+}
+
+/// Bind the type parameters which appear in `pattern` by matching it against
+/// the concrete type `actual`. Parameters which are already bound are kept.
+pub(crate) fn bind_type_params(
+    pattern: &EmptyType,
+    actual: &EmptyType,
+    params: &[ast::TypeParam],
+    bindings: &mut HashMap<String, EmptyType>,
+) {
+    match (pattern.resolved(), actual.resolved()) {
+        (EmptyType::Named(name), _) if params.iter().any(|param| param.name == *name) => {
+            bindings
+                .entry(name.clone())
+                .or_insert_with(|| actual.clone());
+        }
+        (EmptyType::Pointer(pattern), EmptyType::Pointer(actual)) => {
+            bind_type_params(&pattern.ty, &actual.ty, params, bindings)
+        }
+        _ => {}
+    }
+}
+
+/// Replace every type parameter used in a function, including in its body.
+pub(crate) fn substitute_fn(func: &mut ast::FnDecl, bindings: &HashMap<String, EmptyType>) {
+    for arg in &mut func.args {
+        arg.t = substitute(&arg.t, bindings);
+    }
+    func.ret_type = substitute(&func.ret_type, bindings);
+    for stmt in &mut func.body {
+        substitute_stmt(stmt, bindings);
+    }
+}
+
+fn substitute_stmt(stmt: &mut ast::Stmt, bindings: &HashMap<String, EmptyType>) {
+    match stmt {
+        ast::Stmt::Assign(assign) => {
+            if let Some(ty) = &assign.var_type {
+                assign.var_type = Some(substitute(ty, bindings));
+            }
+            if let Some(value) = &mut assign.value {
+                substitute_expr(value, bindings);
+            }
+        }
+        ast::Stmt::ReAssign(local) => {
+            substitute_expr(&mut local.target, bindings);
+            substitute_expr(&mut local.value, bindings);
+        }
+        ast::Stmt::CompoundAssign(assign) => {
+            substitute_expr(&mut assign.target, bindings);
+            substitute_expr(&mut assign.value, bindings);
+        }
+        ast::Stmt::Return(ast::Return { value: expr, .. }) => substitute_expr(expr, bindings),
+        ast::Stmt::Call(call) => substitute_call(call, bindings),
+        ast::Stmt::MethodCall(call) => substitute_method_call(call, bindings),
+        ast::Stmt::If(if_stmt) => substitute_if(if_stmt, bindings),
+        ast::Stmt::Block(block) => substitute_block(block, bindings),
+    }
+}
+
+fn substitute_block(block: &mut ast::Block, bindings: &HashMap<String, EmptyType>) {
+    for stmt in &mut block.stmts {
+        substitute_stmt(stmt, bindings);
+    }
+    if let Some(value) = &mut block.value {
+        substitute_expr(value, bindings);
+    }
+}
+
+fn substitute_if(if_expr: &mut ast::If, bindings: &HashMap<String, EmptyType>) {
+    substitute_expr(&mut if_expr.condition, bindings);
+    substitute_block(&mut if_expr.then_body, bindings);
+    if let Some(else_body) = &mut if_expr.else_body {
+        substitute_block(else_body, bindings);
+    }
+}
+
+fn substitute_expr(expr: &mut ast::Expression, bindings: &HashMap<String, EmptyType>) {
+    match expr {
+        ast::Expression::Call(call) => substitute_call(call, bindings),
+        ast::Expression::MethodCall(call) => substitute_method_call(call, bindings),
+        ast::Expression::StructLiteral(literal) => {
+            literal.name = substitute_name(&literal.name, bindings);
+            for (_, value) in &mut literal.fields {
+                substitute_expr(value, bindings);
+            }
+        }
+        ast::Expression::Field(base, _) => substitute_expr(base, bindings),
+        ast::Expression::If(if_expr) => substitute_if(if_expr, bindings),
+        ast::Expression::Block(block) => substitute_block(block, bindings),
+        ast::Expression::Comparison(comparison) => {
+            substitute_expr(&mut comparison.lhs, bindings);
+            substitute_expr(&mut comparison.rhs, bindings);
+        }
+        _ => {}
+    }
+}
+
+fn substitute_call(call: &mut ast::Call, bindings: &HashMap<String, EmptyType>) {
+    // The type in a path to an associated function, e.g. `T::new()`.
+    if let Some((ty, name)) = call.func.split_once("::") {
+        call.func = format!("{}::{name}", substitute_name(ty, bindings));
+    }
+    for ty in &mut call.type_args {
+        *ty = substitute(ty, bindings);
+    }
+    for arg in &mut call.args {
+        substitute_expr(arg, bindings);
+    }
+}
+
+fn substitute_method_call(call: &mut ast::MethodCall, bindings: &HashMap<String, EmptyType>) {
+    substitute_expr(&mut call.receiver, bindings);
+    for arg in &mut call.args {
+        substitute_expr(arg, bindings);
+    }
+}
+
+/// Substitute a type parameter or alias used as the name of a struct, e.g.
+/// `Self` in `Self { x: 1 }`.
+fn substitute_name(name: &str, bindings: &HashMap<String, EmptyType>) -> String {
+    match bindings.get(name).map(EmptyType::resolved) {
+        Some(EmptyType::Named(ty)) => ty.clone(),
+        _ => name.to_string(),
+    }
+}
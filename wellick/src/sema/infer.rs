@@ -0,0 +1,1032 @@
+use super::consteval::Consts;
+use super::functions::{build_fn_map, lookup_signature, FnSignature};
+use super::generics::{bind_type_params, substitute, substitute_fn, Generics};
+use super::layout;
+use super::mangle;
+use super::structs::Structs;
+use super::traits::Traits;
+use crate::diagnostics::{error, CompileError, CompileResult};
+use crate::parser::ast;
+use crate::parser::ast::{EmptyType, FloatType, IntegerType, Pointer};
+use std::collections::{HashMap, VecDeque};
+
+/// What is known about the type of a `let` without a type annotation.
+#[derive(Debug, Clone)]
+enum Inferred {
+    /// The type has been fully determined.
+    Known(EmptyType),
+    /// Initialised from an unsuffixed integer literal, any integer type will do.
+    IntLiteral,
+    /// Initialised from an unsuffixed float literal, any float type will do.
+    FloatLiteral,
+    /// The type is the same as the type of another slot.
+    Link(usize),
+}
+
+/// The type of a name or expression while inference is running.
+#[derive(Debug, Clone)]
+enum Binding {
+    Known(EmptyType),
+    Slot(usize),
+    IntLiteral,
+    FloatLiteral,
+}
+
+/// Infer the type of every `let` statement which was written without a type
+/// annotation, filling in `Assignment::var_type`.
+///
+/// The type comes from the initializer: a call's return type, a variable's
+/// type, or a default for literals. A variable initialised from an unsuffixed
+/// literal takes its type from the first use which requires a specific type,
+/// such as being passed as an `i64` argument.
+///
+/// Calls to generic functions are rewritten to call an instance of the
+/// function for the inferred type arguments. The returned functions are all
+/// concrete, and include every instance that is used.
+pub(crate) fn infer_types(
+    code: Vec<ast::FnDecl>,
+    structs: &Structs,
+    traits: &Traits,
+    consts: &Consts,
+) -> CompileResult<Program> {
+    let (generic, concrete): (Vec<_>, Vec<_>) = code
+        .into_iter()
+        .partition(|func| !func.type_params.is_empty());
+    let functions = build_fn_map(&concrete);
+    let templates = generic.clone();
+    let mut generics = Generics::new(generic);
+
+    // The body of a generic function is checked once with its type
+    // parameters in place, so it is checked even if it is never used.
+    let templates = templates
+        .into_iter()
+        .map(|mut func| {
+            let signatures = infer_function(
+                &mut func,
+                &functions,
+                &mut generics,
+                structs,
+                traits,
+                consts,
+            )?;
+            Ok(Template { func, signatures })
+        })
+        .collect::<CompileResult<_>>()?;
+
+    let mut queue = VecDeque::from(concrete);
+    let mut done = Vec::new();
+    while let Some(mut func) = queue.pop_front() {
+        infer_function(
+            &mut func,
+            &functions,
+            &mut generics,
+            structs,
+            traits,
+            consts,
+        )
+        .map_err(|err| generics.in_instance(err, &func.name))?;
+        queue.extend(generics.take_pending());
+        done.push(func);
+    }
+    Ok(Program {
+        functions: done,
+        templates,
+        generics,
+    })
+}
+
+/// The functions of a program once the types of their `let`s are inferred.
+pub(crate) struct Program {
+    /// Every concrete function, including each instance of a generic
+    /// function which is used.
+    pub functions: Vec<ast::FnDecl>,
+    pub templates: Vec<Template>,
+    pub generics: Generics,
+}
+
+/// A generic function with its type parameters in place, whose body is
+/// checked once for all of its instances.
+pub(crate) struct Template {
+    pub func: ast::FnDecl,
+    /// The functions called from the body which only exist for its type
+    /// parameters, such as the methods of their trait bounds, without bodies.
+    pub signatures: Vec<ast::FnDecl>,
+}
+
+/// Infer the types of the `let`s in a function, returning the signatures it
+/// calls which only exist for its type parameters.
+fn infer_function(
+    func: &mut ast::FnDecl,
+    functions: &HashMap<String, ast::FnDecl>,
+    generics: &mut Generics,
+    structs: &Structs,
+    traits: &Traits,
+    consts: &Consts,
+) -> CompileResult<Vec<ast::FnDecl>> {
+    for arg in &func.args {
+        check_type(&arg.t, structs, &func.type_params, arg.span)?;
+    }
+    check_type(&func.ret_type, structs, &func.type_params, func.span)?;
+
+    let mut inference = TypeInference::new(functions, generics, structs, traits, consts, func);
+    for stmt in &mut func.body {
+        inference.visit_stmt(stmt)?;
+    }
+
+    let mut resolved = inference.resolve_all().into_iter();
+    let signatures = inference.signatures;
+    for stmt in &mut func.body {
+        fill_stmt(stmt, &mut resolved);
+    }
+    Ok(signatures)
+}
+
+/// Report types which do not name anything. Any named type must be a struct
+/// or one of `type_params`, which is empty in an instance of a generic
+/// function as its type parameters have been substituted.
+fn check_type(
+    ty: &EmptyType,
+    structs: &Structs,
+    type_params: &[ast::TypeParam],
+    span: ast::Span,
+) -> CompileResult<()> {
+    match ty.resolved() {
+        EmptyType::Named(name)
+            if !structs.contains_key(name) && !type_params.iter().any(|p| p.name == *name) =>
+        {
+            error(span, format!("Cannot find type `{name}` in this scope"))
+        }
+        EmptyType::Pointer(ptr) => check_type(&ptr.ty, structs, type_params, span),
+        _ => Ok(()),
+    }
+}
+
+struct TypeInference<'a> {
+    functions: &'a HashMap<String, ast::FnDecl>,
+    generics: &'a mut Generics,
+    structs: &'a Structs,
+    traits: &'a Traits,
+    /// The name of the function whose body is inferred.
+    name: String,
+    ret_type: EmptyType,
+    /// The type parameters of a generic function whose body is checked
+    /// without being instantiated. Their values may only be used through
+    /// the methods of their trait bounds.
+    type_params: Vec<ast::TypeParam>,
+    /// The functions called which only exist for `type_params`.
+    signatures: Vec<ast::FnDecl>,
+    /// The module the function is defined in, `None` for the root file.
+    module: Option<String>,
+    slots: Vec<Inferred>,
+    /// The slot of each un-annotated `let`, in the order they appear.
+    lets: Vec<usize>,
+    scopes: Vec<HashMap<String, Binding>>,
+}
+
+impl<'a> TypeInference<'a> {
+    fn new(
+        functions: &'a HashMap<String, ast::FnDecl>,
+        generics: &'a mut Generics,
+        structs: &'a Structs,
+        traits: &'a Traits,
+        consts: &Consts,
+        func: &ast::FnDecl,
+    ) -> Self {
+        let consts = consts
+            .iter()
+            .map(|(name, value)| (name.clone(), Binding::Known(value.ty.clone())))
+            .collect();
+        let params = func
+            .args
+            .iter()
+            .map(|arg| (arg.name.clone(), Binding::Known(arg.t.clone())))
+            .collect();
+
+        Self {
+            functions,
+            generics,
+            structs,
+            traits,
+            name: func.name.clone(),
+            ret_type: func.ret_type.clone(),
+            type_params: func.type_params.clone(),
+            signatures: Vec::new(),
+            module: mangle::module_of(&func.name).map(str::to_string),
+            slots: Vec::new(),
+            lets: Vec::new(),
+            scopes: vec![consts, params],
+        }
+    }
+
+    /// Visit a block, returning the type of its value. `needs_value` is set
+    /// when the value is used, in which case the block must have one.
+    fn visit_block(
+        &mut self,
+        block: &mut ast::Block,
+        needs_value: bool,
+    ) -> CompileResult<Option<Binding>> {
+        self.scopes.push(HashMap::new());
+        for stmt in &mut block.stmts {
+            self.visit_stmt(stmt)?;
+        }
+        let diverges = block.diverges();
+        let value = match block.value.as_deref_mut() {
+            Some(value) if needs_value => self.visit_expr(value)?,
+            Some(value) => {
+                self.visit_unused(value)?;
+                None
+            }
+            // A block which returns never produces a value, so it fits anywhere.
+            None if needs_value && !diverges => {
+                return error(
+                    block.span,
+                    "Expected a value, but the block has no final expression",
+                );
+            }
+            None => None,
+        };
+        self.scopes.pop();
+        Ok(value)
+    }
+
+    /// Visit an `if`, returning the type of its value if `needs_value` is set.
+    fn visit_if(
+        &mut self,
+        if_expr: &mut ast::If,
+        needs_value: bool,
+    ) -> CompileResult<Option<Binding>> {
+        self.visit_expr(&mut if_expr.condition)?;
+        let then_value = self.visit_block(&mut if_expr.then_body, needs_value)?;
+        let else_value = match &mut if_expr.else_body {
+            Some(else_body) => self.visit_block(else_body, needs_value)?,
+            None if needs_value => {
+                return error(
+                    if_expr.span,
+                    "`if` may be missing an `else` clause, an `if` without an `else` has no value",
+                );
+            }
+            None => None,
+        };
+
+        Ok(match (then_value, else_value) {
+            (Some(then_value), Some(else_value)) => {
+                self.unify(&Some(then_value.clone()), &else_value);
+                Some(then_value)
+            }
+            (then_value, else_value) => then_value.or(else_value),
+        })
+    }
+
+    /// Visit an expression whose value is discarded, such as the final
+    /// expression of a block used as a statement.
+    fn visit_unused(&mut self, expr: &mut ast::Expression) -> CompileResult<()> {
+        match expr {
+            ast::Expression::If(if_expr) => {
+                self.visit_if(if_expr, false)?;
+            }
+            ast::Expression::Block(block) => {
+                self.visit_block(block, false)?;
+            }
+            expr => {
+                self.visit_expr(expr)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn visit_stmt(&mut self, stmt: &mut ast::Stmt) -> CompileResult<()> {
+        match stmt {
+            ast::Stmt::Assign(assign) => {
+                let value = match &mut assign.value {
+                    Some(value) => self.visit_expr(value)?,
+                    None => None,
+                };
+                let binding = match &assign.var_type {
+                    Some(ty) => {
+                        check_type(ty, self.structs, &self.type_params, assign.span)?;
+                        self.unify(&value, &Binding::Known(ty.clone()));
+                        Binding::Known(ty.clone())
+                    }
+                    None => {
+                        let inferred = match value {
+                            Some(Binding::Known(ty)) => Inferred::Known(ty),
+                            Some(Binding::Slot(slot)) => Inferred::Link(slot),
+                            Some(Binding::IntLiteral) => Inferred::IntLiteral,
+                            Some(Binding::FloatLiteral) => Inferred::FloatLiteral,
+                            None => {
+                                return error(
+                                    assign.target.span,
+                                    format!(
+                                        "Cannot infer the type of `{}`, consider adding a type annotation",
+                                        assign.target
+                                    ),
+                                );
+                            }
+                        };
+                        self.slots.push(inferred);
+                        self.lets.push(self.slots.len() - 1);
+                        Binding::Slot(self.slots.len() - 1)
+                    }
+                };
+                self.scopes
+                    .last_mut()
+                    .expect("there is always at least one scope")
+                    .insert(assign.target.ident.clone(), binding);
+            }
+            ast::Stmt::ReAssign(local) => {
+                let value = self.visit_expr(&mut local.value)?;
+                if let Some(target) = self.visit_expr(&mut local.target)? {
+                    self.unify(&value, &target);
+                }
+            }
+            ast::Stmt::CompoundAssign(assign) => {
+                let value = self.visit_expr(&mut assign.value)?;
+                if let Some(target) = self.visit_expr(&mut assign.target)? {
+                    self.unify(&value, &target);
+                }
+            }
+            ast::Stmt::Return(ast::Return { value: expr, .. }) => {
+                let value = self.visit_expr(expr)?;
+                self.unify(&value, &Binding::Known(self.ret_type.clone()));
+            }
+            ast::Stmt::Call(call) => {
+                self.visit_call(call)?;
+            }
+            ast::Stmt::MethodCall(call) => {
+                self.visit_method_call(call)?;
+            }
+            ast::Stmt::If(if_stmt) => {
+                self.visit_if(if_stmt, false)?;
+            }
+            ast::Stmt::Block(block) => {
+                self.visit_block(block, false)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn visit_call(&mut self, call: &mut ast::Call) -> CompileResult<Option<Binding>> {
+        // `size_of::<T>()` and `align_of::<T>()` are constants, which take
+        // their type from where they are used like an integer literal. The
+        // layout of a type parameter is only known in each instance.
+        let of_type_param = match call.type_args.as_slice() {
+            [ty] => self.type_param(ty).is_some(),
+            _ => false,
+        };
+        if layout::is_intrinsic(&call.func) && of_type_param
+            || layout::intrinsic(call, self.structs)?.is_some()
+        {
+            return Ok(Some(Binding::IntLiteral));
+        }
+
+        // An associated function of a type parameter comes from its trait
+        // bounds, e.g. `T::new()`.
+        let bound_fn = call
+            .func
+            .split_once("::")
+            .and_then(|(ty, name)| Some((self.type_params.iter().find(|p| p.name == ty)?, name)));
+        if let Some((param, name)) = bound_fn {
+            let func = self.bound_method(&param.clone(), name, call.span)?;
+            let sig = FnSignature::from(&func);
+            for (arg, param) in call.args.iter_mut().zip(&sig.params) {
+                let value = self.visit_expr(arg)?;
+                self.unify(&value, &Binding::Known(param.clone()));
+            }
+            call.func = func.name;
+            return Ok(Some(Binding::Known(sig.ret_type)));
+        }
+
+        // Associated functions are called by their path, e.g. `Point::new`.
+        // Functions defined in other modules also have a path, e.g. `math::gcd`.
+        let is_function =
+            self.signature(&call.func).is_some() || self.generics.get(&call.func).is_some();
+        if let Some((ty, name)) = call.func.rsplit_once("::").filter(|_| !is_function) {
+            if !self.structs.contains_key(ty) {
+                return error(call.span, format!("Cannot find type `{ty}` in this scope"));
+            }
+            let func = mangle::method(ty, name);
+            if self.signature(&func).is_none() && self.generics.get(&func).is_none() {
+                return error(
+                    call.span,
+                    format!("No function or associated item named `{name}` found for `{ty}`"),
+                );
+            }
+            call.func = func;
+        }
+
+        if let Some(generic) = self.generics.get(&call.func).cloned() {
+            return self.visit_generic_call(call, &generic);
+        }
+
+        if !call.type_args.is_empty() {
+            return error(
+                call.span,
+                format!("Function `{}` does not take type arguments", call.func),
+            );
+        }
+
+        let sig = self.signature(&call.func);
+        for (i, arg) in call.args.iter_mut().enumerate() {
+            let value = self.visit_expr(arg)?;
+            if let Some(param) = sig.as_ref().and_then(|sig| sig.params.get(i)) {
+                self.unify(&value, &Binding::Known(param.clone()));
+            }
+        }
+        Ok(sig.map(|sig| Binding::Known(sig.ret_type)))
+    }
+
+    /// Resolve a method call to the function implementing it, based on the
+    /// type of the receiver. The receiver may be a struct or a pointer to one.
+    fn visit_method_call(&mut self, call: &mut ast::MethodCall) -> CompileResult<Option<Binding>> {
+        let receiver = self.visit_expr(&mut call.receiver)?;
+        let receiver_ty = match receiver.and_then(|receiver| self.binding_type(&receiver)) {
+            Some(ty) => ty,
+            None => {
+                for arg in &mut call.args {
+                    self.visit_expr(arg)?;
+                }
+                return Ok(None);
+            }
+        };
+
+        // Every pointer has the `is_null` and `addr` methods, even one to a
+        // struct with its own methods of those names.
+        let builtin = match call.method.as_str() {
+            "is_null" => Some((mangle::IS_NULL, IntegerType::I32)),
+            "addr" => Some((mangle::ADDR, IntegerType::PointerSize)),
+            _ => None,
+        };
+        if let (EmptyType::Pointer(_), Some((func, ty))) = (receiver_ty.resolved(), builtin) {
+            if !call.args.is_empty() {
+                return error(
+                    call.span,
+                    format!(
+                        "Method `{}` takes 0 argument(s) but {} were supplied",
+                        call.method,
+                        call.args.len()
+                    ),
+                );
+            }
+            call.func = Some(func.to_string());
+            return Ok(Some(Binding::Known(EmptyType::Integer(ty))));
+        }
+
+        // A type parameter only has the methods declared by its trait bounds,
+        // whatever type the instances are for.
+        let pointee = match receiver_ty.resolved() {
+            EmptyType::Pointer(ptr) => ptr.ty.resolved(),
+            ty => ty,
+        };
+        let (ty, func, sig, is_method, public) = match self.type_param(pointee).cloned() {
+            Some(param) => {
+                let method = self.bound_method(&param, &call.method, call.span)?;
+                if !method.type_params.is_empty() {
+                    return error(
+                        call.span,
+                        format!(
+                            "Generic methods cannot be called with method syntax, use `{}::{}::<...>(...)` instead",
+                            param.name, call.method
+                        ),
+                    );
+                }
+                let (sig, is_method) = (FnSignature::from(&method), takes_self(&method));
+                (param.name, method.name, sig, is_method, true)
+            }
+            None => {
+                let ty = match self.struct_name(&receiver_ty) {
+                    Some(ty) => ty,
+                    None => {
+                        return error(
+                            call.span,
+                            format!(
+                                "No method named `{}` found for type `{receiver_ty}`",
+                                call.method
+                            ),
+                        )
+                    }
+                };
+
+                let func = mangle::method(&ty, &call.method);
+                if self.generics.get(&func).is_some() {
+                    return error(
+                        call.span,
+                        format!(
+                            "Generic methods cannot be called with method syntax, use `{ty}::{}::<...>(...)` instead",
+                            call.method
+                        ),
+                    );
+                }
+                let (sig, is_method, public) = match self.functions.get(&func) {
+                    Some(decl) => (FnSignature::from(decl), takes_self(decl), decl.public),
+                    None => {
+                        return error(
+                            call.span,
+                            format!("No method named `{}` found for `{ty}`", call.method),
+                        )
+                    }
+                };
+                (ty, func, sig, is_method, public)
+            }
+        };
+        if !is_method {
+            return error(
+                call.span,
+                format!(
+                    "`{}` is an associated function of `{ty}`, not a method, call it with `{ty}::{}(...)`",
+                    call.method, call.method
+                ),
+            );
+        }
+
+        // Calling by path checks visibility when modules are resolved, but
+        // the type of a receiver is only known here.
+        let module = mangle::module_of(&ty);
+        if let Some(module) =
+            module.filter(|module| !public && Some(*module) != self.module.as_deref())
+        {
+            return error(
+                call.span,
+                format!(
+                    "The method `{}::{}` is private to module `{module}`",
+                    ty, call.method
+                ),
+            );
+        }
+
+        for (arg, param) in call.args.iter_mut().zip(sig.params.iter().skip(1)) {
+            let value = self.visit_expr(arg)?;
+            self.unify(&value, &Binding::Known(param.clone()));
+        }
+
+        call.func = Some(func);
+        Ok(Some(Binding::Known(sig.ret_type)))
+    }
+
+    /// Find the signature of a builtin, user defined function or generic instance.
+    fn signature(&self, name: &str) -> Option<FnSignature> {
+        lookup_signature(self.functions, name).or_else(|| self.generics.instance(name).cloned())
+    }
+
+    /// The type parameter `ty` is, while checking the body of a generic
+    /// function.
+    fn type_param(&self, ty: &EmptyType) -> Option<&ast::TypeParam> {
+        match ty.resolved() {
+            EmptyType::Named(name) => self.type_params.iter().find(|param| param.name == *name),
+            _ => None,
+        }
+    }
+
+    /// Find the method `name` of a type parameter among the methods of its
+    /// trait bounds, with `Self` standing for the parameter.
+    fn bound_method(
+        &mut self,
+        param: &ast::TypeParam,
+        name: &str,
+        span: ast::Span,
+    ) -> CompileResult<ast::FnDecl> {
+        let mut found = param
+            .bounds
+            .iter()
+            .filter_map(|bound| self.traits.method(bound, name));
+        let method = match (found.next(), found.next()) {
+            (Some(method), None) => method,
+            (Some(_), Some(_)) => {
+                return error(
+                    span,
+                    format!(
+                        "Multiple methods named `{name}` found in the trait bounds of `{}`",
+                        param.name
+                    ),
+                )
+            }
+            (None, _) => {
+                return Err(CompileError::new(
+                    span,
+                    format!(
+                        "No method named `{name}` found for type parameter `{}`",
+                        param.name
+                    ),
+                )
+                .with_note(format!(
+                    "the methods of a type parameter come from its trait bounds, e.g. `{}: Trait`",
+                    param.name
+                )))
+            }
+        };
+
+        let bindings = HashMap::from([("Self".to_string(), EmptyType::Named(param.name.clone()))]);
+        let mut method = method.clone();
+        method.name = mangle::method(&param.name, name);
+        substitute_fn(&mut method, &bindings);
+        self.signatures.push(method.clone());
+        Ok(method)
+    }
+
+    /// The name of the struct a value of type `ty` refers to, looking through
+    /// a pointer if necessary.
+    fn struct_name(&self, ty: &EmptyType) -> Option<String> {
+        match ty.resolved() {
+            EmptyType::Named(name) if self.structs.contains_key(name) => Some(name.clone()),
+            EmptyType::Pointer(ptr) => match ptr.ty.resolved() {
+                EmptyType::Named(name) if self.structs.contains_key(name) => Some(name.clone()),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Work out the type arguments of a call to a generic function, either
+    /// from the explicit `::<...>` list or from the argument types, and point
+    /// the call at the matching instance.
+    fn visit_generic_call(
+        &mut self,
+        call: &mut ast::Call,
+        generic: &ast::FnDecl,
+    ) -> CompileResult<Option<Binding>> {
+        let args: Vec<Option<Binding>> = call
+            .args
+            .iter_mut()
+            .map(|arg| self.visit_expr(arg))
+            .collect::<CompileResult<_>>()?;
+
+        let mut bindings = HashMap::new();
+        if !call.type_args.is_empty() {
+            if call.type_args.len() != generic.type_params.len() {
+                return error(
+                    call.span,
+                    format!(
+                        "Function `{}` takes {} type argument(s) but {} were supplied",
+                        call.func,
+                        generic.type_params.len(),
+                        call.type_args.len()
+                    ),
+                );
+            }
+            for (param, ty) in generic.type_params.iter().zip(&call.type_args) {
+                check_type(ty, self.structs, &self.type_params, call.span)?;
+                bindings.insert(param.name.clone(), ty.clone());
+            }
+        } else {
+            // Arguments with a known type take priority, literals only decide
+            // a type parameter when nothing else does.
+            for (param, arg) in generic.args.iter().zip(&args) {
+                if let Some(ty) = arg.as_ref().and_then(|arg| self.known_type(arg)) {
+                    bind_type_params(&param.t, &ty, &generic.type_params, &mut bindings);
+                }
+            }
+            for (param, arg) in generic.args.iter().zip(&args) {
+                if let Some(ty) = arg.as_ref().and_then(|arg| self.binding_type(arg)) {
+                    bind_type_params(&param.t, &ty, &generic.type_params, &mut bindings);
+                }
+            }
+        }
+
+        let mut type_args = Vec::new();
+        for param in &generic.type_params {
+            match bindings.get(&param.name) {
+                Some(ty) => type_args.push(ty.clone()),
+                None => return error(
+                    call.span,
+                    format!(
+                        "Cannot infer type argument `{}` for `{}`, consider specifying it with `{}::<...>`",
+                        param.name, call.func, call.func
+                    ),
+                ),
+            }
+        }
+
+        for (param, ty) in generic.type_params.iter().zip(&type_args) {
+            for bound in &param.bounds {
+                let satisfied = match self.type_param(ty) {
+                    Some(arg) => arg.bounds.contains(bound),
+                    None => self.traits.implements(ty, bound),
+                };
+                if !satisfied {
+                    return error(
+                        call.span,
+                        format!(
+                            "The trait bound `{ty}: {bound}` is not satisfied, required by `{}`",
+                            call.func
+                        ),
+                    );
+                }
+            }
+        }
+
+        for (param, arg) in generic.args.iter().zip(&args) {
+            self.unify(arg, &Binding::Known(substitute(&param.t, &bindings)));
+        }
+
+        // Instances are only created for concrete type arguments.
+        if self.type_params.is_empty() {
+            call.func = self
+                .generics
+                .instantiate(&call.func, &type_args, &self.name, call.span)?;
+        } else {
+            let decl = self.generics.declaration(&call.func, &type_args);
+            call.func = decl.name.clone();
+            self.signatures.push(decl);
+        }
+        call.type_args.clear();
+        Ok(Some(Binding::Known(substitute(
+            &generic.ret_type,
+            &bindings,
+        ))))
+    }
+
+    fn visit_expr(&mut self, expr: &mut ast::Expression) -> CompileResult<Option<Binding>> {
+        Ok(match expr {
+            ast::Expression::Call(call) => self.visit_call(call)?,
+            ast::Expression::Literal(ast::Literal::Integer(_)) => Some(Binding::IntLiteral),
+            ast::Expression::Literal(ast::Literal::Float(_)) => Some(Binding::FloatLiteral),
+            ast::Expression::Identifier(name) => Some(self.lookup(name, name.span)?),
+            ast::Expression::AddressOf(addr_of) => {
+                // A pointer needs a concrete pointee type, so the type of the
+                // variable is fixed at the point its address is taken.
+                let binding = self.lookup(&addr_of.name, addr_of.span)?;
+                self.binding_type(&binding).map(|ty| {
+                    Binding::Known(EmptyType::Pointer(Box::new(Pointer::new(
+                        ty,
+                        addr_of.mutable,
+                    ))))
+                })
+            }
+            ast::Expression::DeRef(name) => {
+                let binding = self.lookup(name, name.span)?;
+                match self.binding_type(&binding) {
+                    Some(ty) => match ty.resolved() {
+                        EmptyType::Pointer(ptr) => Some(Binding::Known(ptr.ty.clone())),
+                        _ => None,
+                    },
+                    None => None,
+                }
+            }
+            ast::Expression::StructLiteral(literal) => {
+                let layout = match self.structs.get(&literal.name) {
+                    Some(layout) => layout.clone(),
+                    None => {
+                        return error(
+                            literal.span,
+                            format!("Cannot find struct `{}` in this scope", literal.name),
+                        )
+                    }
+                };
+
+                for field in &layout.fields {
+                    let count = literal
+                        .fields
+                        .iter()
+                        .filter(|(name, _)| *name == field.name)
+                        .count();
+                    if count == 0 {
+                        return error(
+                            literal.span,
+                            format!(
+                                "Missing field `{}` in initializer of `{}`",
+                                field.name, literal.name
+                            ),
+                        );
+                    }
+                    if count > 1 {
+                        return error(
+                            literal.span,
+                            format!(
+                                "Field `{}` specified more than once in initializer of `{}`",
+                                field.name, literal.name
+                            ),
+                        );
+                    }
+                }
+
+                for (name, value) in &mut literal.fields {
+                    let field = match layout.field(name) {
+                        Some(field) => field,
+                        None => {
+                            return error(
+                                value.span(),
+                                format!("Struct `{}` has no field named `{name}`", literal.name),
+                            )
+                        }
+                    };
+                    let value = self.visit_expr(value)?;
+                    self.unify(&value, &Binding::Known(field.ty.clone()));
+                }
+                Some(Binding::Known(EmptyType::Named(literal.name.clone())))
+            }
+            ast::Expression::Field(base, name) => {
+                let base_ty = match self.visit_expr(base)? {
+                    Some(base) => self.binding_type(&base),
+                    None => None,
+                };
+                let base_ty = match base_ty {
+                    Some(base_ty) => base_ty,
+                    None => return Ok(None),
+                };
+                let field = self
+                    .struct_name(&base_ty)
+                    .and_then(|ty| self.structs[&ty].field(name));
+                match field {
+                    Some(field) => Some(Binding::Known(field.ty.clone())),
+                    None => {
+                        return error(name.span, format!("No field `{name}` on type `{base_ty}`"))
+                    }
+                }
+            }
+            ast::Expression::MethodCall(call) => self.visit_method_call(call)?,
+            ast::Expression::If(if_expr) => self.visit_if(if_expr, true)?,
+            ast::Expression::Block(block) => self.visit_block(block, true)?,
+            // `null` takes its type from where it is used, so says nothing about it.
+            ast::Expression::Null(_) => None,
+            ast::Expression::Comparison(comparison) => {
+                let lhs = self.visit_expr(&mut comparison.lhs)?;
+                if let Some(rhs) = self.visit_expr(&mut comparison.rhs)? {
+                    self.unify(&lhs, &rhs);
+                }
+                Some(Binding::Known(EmptyType::Integer(IntegerType::I32)))
+            }
+        })
+    }
+
+    /// Record that two types must be equal, refining literal types where possible.
+    fn unify(&mut self, a: &Option<Binding>, b: &Binding) {
+        let a = match a {
+            Some(a) => a,
+            None => return,
+        };
+
+        match (a, b) {
+            (Binding::Slot(slot), other) | (other, Binding::Slot(slot)) => {
+                let root = self.root(*slot);
+                match (&self.slots[root], other) {
+                    (Inferred::IntLiteral, Binding::Known(ty))
+                        if matches!(ty.resolved(), EmptyType::Integer(_)) =>
+                    {
+                        self.slots[root] = Inferred::Known(ty.clone());
+                    }
+                    (Inferred::FloatLiteral, Binding::Known(ty))
+                        if matches!(ty.resolved(), EmptyType::Float(_)) =>
+                    {
+                        self.slots[root] = Inferred::Known(ty.clone());
+                    }
+                    (Inferred::IntLiteral | Inferred::FloatLiteral, Binding::Slot(other)) => {
+                        let other = self.root(*other);
+                        if other != root {
+                            self.slots[root] = Inferred::Link(other);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Follow links until reaching the slot which holds the type.
+    fn root(&self, mut slot: usize) -> usize {
+        while let Inferred::Link(next) = self.slots[slot] {
+            slot = next;
+        }
+        slot
+    }
+
+    /// The type of a binding, if it is known without defaulting any literals.
+    fn known_type(&self, binding: &Binding) -> Option<EmptyType> {
+        match binding {
+            Binding::Known(ty) => Some(ty.clone()),
+            Binding::Slot(slot) => match &self.slots[self.root(*slot)] {
+                Inferred::Known(ty) => Some(ty.clone()),
+                _ => None,
+            },
+            Binding::IntLiteral | Binding::FloatLiteral => None,
+        }
+    }
+
+    /// The concrete type of a binding. Literal types are defaulted, and
+    /// stay fixed from then on.
+    fn binding_type(&mut self, binding: &Binding) -> Option<EmptyType> {
+        match binding {
+            Binding::Known(ty) => Some(ty.clone()),
+            Binding::IntLiteral => Some(default_type(&Inferred::IntLiteral)),
+            Binding::FloatLiteral => Some(default_type(&Inferred::FloatLiteral)),
+            Binding::Slot(slot) => {
+                let root = self.root(*slot);
+                let ty = default_type(&self.slots[root]);
+                self.slots[root] = Inferred::Known(ty.clone());
+                Some(ty)
+            }
+        }
+    }
+
+    fn lookup(&self, name: &str, span: ast::Span) -> CompileResult<Binding> {
+        match self.scopes.iter().rev().find_map(|scope| scope.get(name)) {
+            Some(binding) => Ok(binding.clone()),
+            None => error(span, format!("Cannot find value `{name}` in this scope")),
+        }
+    }
+
+    /// The final type of every un-annotated `let`, in the order they appear.
+    fn resolve_all(&self) -> Vec<EmptyType> {
+        self.lets
+            .iter()
+            .map(|slot| default_type(&self.slots[self.root(*slot)]))
+            .collect()
+    }
+}
+
+/// Whether a function is a method, taking `self` as its first parameter.
+fn takes_self(func: &ast::FnDecl) -> bool {
+    func.args.first().is_some_and(|arg| arg.name == "self")
+}
+
+/// The type of a slot once no more information is available about it.
+fn default_type(inferred: &Inferred) -> EmptyType {
+    match inferred {
+        Inferred::Known(ty) => ty.clone(),
+        Inferred::IntLiteral => EmptyType::Integer(IntegerType::I32),
+        Inferred::FloatLiteral => EmptyType::Float(FloatType::F64),
+        Inferred::Link(_) => unreachable!("links are followed to their root"),
+    }
+}
+
+/// Write the inferred types back into the AST, visiting the un-annotated
+/// `let` statements in the same order as `TypeInference`.
+fn fill_stmt(stmt: &mut ast::Stmt, resolved: &mut impl Iterator<Item = EmptyType>) {
+    match stmt {
+        ast::Stmt::Assign(assign) => {
+            if let Some(value) = &mut assign.value {
+                fill_expr(value, resolved);
+            }
+            if assign.var_type.is_none() {
+                assign.var_type = resolved.next();
+            }
+        }
+        ast::Stmt::ReAssign(local) => {
+            fill_expr(&mut local.value, resolved);
+            fill_expr(&mut local.target, resolved);
+        }
+        ast::Stmt::CompoundAssign(assign) => {
+            fill_expr(&mut assign.value, resolved);
+            fill_expr(&mut assign.target, resolved);
+        }
+        ast::Stmt::Return(ast::Return { value: expr, .. }) => fill_expr(expr, resolved),
+        ast::Stmt::Call(call) => {
+            for arg in &mut call.args {
+                fill_expr(arg, resolved);
+            }
+        }
+        ast::Stmt::MethodCall(call) => {
+            fill_expr(&mut call.receiver, resolved);
+            for arg in &mut call.args {
+                fill_expr(arg, resolved);
+            }
+        }
+        ast::Stmt::If(if_stmt) => fill_if(if_stmt, resolved),
+        ast::Stmt::Block(block) => fill_block(block, resolved),
+    }
+}
+
+fn fill_expr(expr: &mut ast::Expression, resolved: &mut impl Iterator<Item = EmptyType>) {
+    match expr {
+        ast::Expression::Call(call) => {
+            for arg in &mut call.args {
+                fill_expr(arg, resolved);
+            }
+        }
+        ast::Expression::MethodCall(call) => {
+            fill_expr(&mut call.receiver, resolved);
+            for arg in &mut call.args {
+                fill_expr(arg, resolved);
+            }
+        }
+        ast::Expression::StructLiteral(literal) => {
+            for (_, value) in &mut literal.fields {
+                fill_expr(value, resolved);
+            }
+        }
+        ast::Expression::Field(base, _) => fill_expr(base, resolved),
+        ast::Expression::If(if_expr) => fill_if(if_expr, resolved),
+        ast::Expression::Block(block) => fill_block(block, resolved),
+        ast::Expression::Comparison(comparison) => {
+            fill_expr(&mut comparison.lhs, resolved);
+            fill_expr(&mut comparison.rhs, resolved);
+        }
+        _ => {}
+    }
+}
+
+fn fill_block(block: &mut ast::Block, resolved: &mut impl Iterator<Item = EmptyType>) {
+    for stmt in &mut block.stmts {
+        fill_stmt(stmt, resolved);
+    }
+    if let Some(value) = &mut block.value {
+        fill_expr(value, resolved);
+    }
+}
+
+fn fill_if(if_expr: &mut ast::If, resolved: &mut impl Iterator<Item = EmptyType>) {
+    fill_expr(&mut if_expr.condition, resolved);
+    fill_block(&mut if_expr.then_body, resolved);
+    if let Some(else_body) = &mut if_expr.else_body {
+        fill_block(else_body, resolved);
+    }
+}
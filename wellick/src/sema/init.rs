@@ -0,0 +1,182 @@
+use super::typed::{Block, Expr, ExprKind, Function, If, LocalId, Stmt, StmtKind};
+use crate::diagnostics::CompileError;
+use std::mem;
+
+/// Check that every variable of a function is assigned before it is read,
+/// on every path through the function, and that an immutable variable
+/// declared without a value is assigned at most once.
+///
+/// Every such error in the function is reported.
+pub(crate) fn check_initialized(func: &Function) -> Vec<CompileError> {
+    let mut state = State::new(func.locals.len());
+    for param in &func.params {
+        state.assign(*param);
+    }
+
+    let mut checker = InitChecker {
+        func,
+        state,
+        errors: Vec::new(),
+    };
+    for stmt in &func.body {
+        checker.stmt(stmt);
+    }
+    checker.errors
+}
+
+/// What is known about each variable at a point in a function.
+#[derive(Clone)]
+struct State {
+    /// Whether the variable has been assigned on every path to this point.
+    assigned: Vec<bool>,
+    /// Whether the variable has been assigned on any path to this point.
+    maybe_assigned: Vec<bool>,
+}
+
+impl State {
+    /// The state at the start of a function, where nothing is assigned.
+    fn new(locals: usize) -> Self {
+        Self {
+            assigned: vec![false; locals],
+            maybe_assigned: vec![false; locals],
+        }
+    }
+
+    /// The state after a `return`, which no path reaches. Everything is
+    /// both assigned and unassigned, so that it has no effect when joined
+    /// with the state of another path.
+    fn unreachable(locals: usize) -> Self {
+        Self {
+            assigned: vec![true; locals],
+            maybe_assigned: vec![false; locals],
+        }
+    }
+
+    fn assign(&mut self, id: LocalId) {
+        self.assigned[id.0] = true;
+        self.maybe_assigned[id.0] = true;
+    }
+
+    /// Combine the state at the end of two paths which meet, such as the
+    /// branches of an `if`.
+    fn join(&mut self, other: &State) {
+        for (assigned, other) in self.assigned.iter_mut().zip(&other.assigned) {
+            *assigned &= other;
+        }
+        for (maybe, other) in self.maybe_assigned.iter_mut().zip(&other.maybe_assigned) {
+            *maybe |= other;
+        }
+    }
+}
+
+struct InitChecker<'a> {
+    func: &'a Function,
+    state: State,
+    errors: Vec<CompileError>,
+}
+
+impl InitChecker<'_> {
+    fn stmt(&mut self, stmt: &Stmt) {
+        match &stmt.kind {
+            StmtKind::Let(id, Some(value)) => {
+                self.expr(value);
+                self.state.assign(*id);
+            }
+            StmtKind::Let(_, None) => {}
+            StmtKind::Assign(place, value) => {
+                self.expr(value);
+                match place.kind {
+                    ExprKind::Local(id) => self.assign(id, place),
+                    // Assigning to a field or through a pointer reads the
+                    // variable it is part of.
+                    _ => self.expr(place),
+                }
+            }
+            StmtKind::CompoundAssign(place, _, value) => {
+                self.expr(place);
+                self.expr(value);
+            }
+            StmtKind::Return(value) => {
+                self.expr(value);
+                self.state = State::unreachable(self.func.locals.len());
+            }
+            StmtKind::Expr(expr) => self.expr(expr),
+            StmtKind::If(if_stmt) => self.if_expr(if_stmt),
+            StmtKind::Block(block) => self.block(block),
+        }
+    }
+
+    fn assign(&mut self, id: LocalId, place: &Expr) {
+        let local = self.func.local(id);
+        if !local.mutable && self.state.maybe_assigned[id.0] {
+            self.errors.push(CompileError::new(
+                place.span,
+                format!("Cannot assign twice to immutable variable `{}`", local.name),
+            ));
+        }
+        self.state.assign(id);
+    }
+
+    fn read(&mut self, id: LocalId, expr: &Expr) {
+        if self.state.assigned[id.0] {
+            return;
+        }
+        let name = &self.func.local(id).name;
+        let message = if self.state.maybe_assigned[id.0] {
+            format!("Use of possibly uninitialized variable `{name}`")
+        } else {
+            format!("Use of uninitialized variable `{name}`")
+        };
+        self.errors.push(CompileError::new(expr.span, message));
+        // Report each variable once, rather than at every later use.
+        self.state.assign(id);
+    }
+
+    fn block(&mut self, block: &Block) {
+        for stmt in &block.stmts {
+            self.stmt(stmt);
+        }
+        if let Some(value) = &block.value {
+            self.expr(value);
+        }
+    }
+
+    /// Check both branches from the state after the condition, continuing
+    /// with what holds at the end of both.
+    fn if_expr(&mut self, if_expr: &If) {
+        self.expr(&if_expr.condition);
+        let before = self.state.clone();
+        self.block(&if_expr.then_body);
+        let after_then = mem::replace(&mut self.state, before);
+        if let Some(else_body) = &if_expr.else_body {
+            self.block(else_body);
+        }
+        self.state.join(&after_then);
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match &expr.kind {
+            ExprKind::Local(id) | ExprKind::AddressOf(id) => self.read(*id, expr),
+            ExprKind::Deref(inner) | ExprKind::Field(inner, _) | ExprKind::IsNull(inner) => {
+                self.expr(inner)
+            }
+            ExprKind::StructLiteral(fields) => {
+                for (_, value) in fields {
+                    self.expr(value);
+                }
+            }
+            ExprKind::Call(_, args) => {
+                for arg in args {
+                    self.expr(arg);
+                }
+            }
+            ExprKind::Comparison(_, lhs, rhs) => {
+                self.expr(lhs);
+                self.expr(rhs);
+            }
+            ExprKind::If(if_expr) => self.if_expr(if_expr),
+            ExprKind::Block(block) => self.block(block),
+            ExprKind::Int(_) | ExprKind::Float(_) | ExprKind::Null => {}
+        }
+    }
+}
@@ -0,0 +1,275 @@
+use super::functions::builtin_signature;
+use super::generics::{substitute, substitute_fn};
+use super::mangle;
+use super::structs::{build_struct_layouts, Structs};
+use super::traits::Traits;
+use super::typed;
+use crate::diagnostics::{error, CompileError, CompileResult};
+use crate::parser::ast;
+use crate::parser::ast::EmptyType;
+use std::collections::{HashMap, HashSet};
+
+/// The items of a program, once lowered.
+pub(crate) struct Lowered {
+    pub structs: Structs,
+    pub traits: Traits,
+    pub consts: Vec<ast::ConstDecl>,
+    pub functions: Vec<ast::FnDecl>,
+    /// The names of the methods which implement a trait.
+    pub trait_methods: HashSet<String>,
+}
+
+/// Split a program into its struct layouts, traits, constants and a flat list
+/// of functions.
+///
+/// Methods and associated functions in `impl` blocks become ordinary functions
+/// with a mangled name, e.g. `Point::new` becomes `Point.new`, and with `Self`
+/// replaced by the type of the `impl` block. Methods implementing a trait are
+/// treated the same way, after checking them against the trait.
+///
+/// Every use of a type alias is replaced by an [`ast::Alias`] holding the
+/// type it names.
+///
+/// Errors in functions and `impl` blocks are all reported together, other
+/// errors stop lowering at the first one found.
+pub(crate) fn lower_items(items: Vec<ast::Item>) -> Result<Lowered, Vec<CompileError>> {
+    let mut decls = Vec::new();
+    let mut consts = Vec::new();
+    let mut aliases = Vec::new();
+    let mut trait_decls = Vec::new();
+    let mut functions = Vec::new();
+    let mut impls = Vec::new();
+    let mut function_names = HashSet::new();
+    let mut errors = Vec::new();
+    for item in items {
+        match item {
+            ast::Item::Function(func) => match check_function(&func, &mut function_names) {
+                Ok(()) => functions.push(func),
+                Err(err) => errors.push(err),
+            },
+            ast::Item::Struct(decl) => decls.push(decl),
+            ast::Item::Trait(decl) => trait_decls.push(decl),
+            ast::Item::Impl(block) => impls.push(block),
+            ast::Item::Const(decl) => consts.push(decl),
+            ast::Item::TypeAlias(alias) => aliases.push(alias),
+            ast::Item::Import(_) => unreachable!("imports are resolved before lowering"),
+        }
+    }
+
+    let aliases = resolve_aliases(aliases).map_err(|err| vec![err])?;
+    for decl in &mut decls {
+        for field in &mut decl.fields {
+            field.ty = substitute(&field.ty, &aliases);
+        }
+    }
+    for decl in &mut trait_decls {
+        for method in &mut decl.methods {
+            substitute_aliases(method, &aliases);
+        }
+    }
+    for func in &mut functions {
+        substitute_aliases(func, &aliases);
+    }
+    for decl in &mut consts {
+        decl.ty = substitute(&decl.ty, &aliases);
+    }
+
+    let structs = build_struct_layouts(&decls).map_err(|err| vec![err])?;
+    let mut traits = Traits::new(trait_decls).map_err(|err| vec![err])?;
+
+    // The trait each method of each type implements, if any.
+    let mut methods = HashMap::new();
+    let mut trait_methods = HashSet::new();
+    for mut block in impls {
+        // An alias of a struct may be used to add methods to the struct.
+        if let Some(EmptyType::Named(name)) = aliases.get(&block.ty).map(EmptyType::resolved) {
+            block.ty = name.clone();
+        }
+        for method in &mut block.methods {
+            substitute_aliases(method, &aliases);
+        }
+        if !structs.contains_key(&block.ty) {
+            errors.push(CompileError::new(
+                block.span,
+                format!("Cannot find type `{}` in this scope", block.ty),
+            ));
+            continue;
+        }
+        if let Some(trait_name) = &block.trait_name {
+            if let Err(err) = traits.add_impl(trait_name, &block.ty, &block.methods, block.span) {
+                errors.push(err);
+            }
+        }
+
+        let bindings = HashMap::from([("Self".to_string(), EmptyType::Named(block.ty.clone()))]);
+        for mut method in block.methods {
+            let key = (block.ty.clone(), method.name.clone());
+            if let Some(previous) = methods.insert(key, block.trait_name.clone()) {
+                // Methods are called by name alone, so two traits may not
+                // give a type methods of the same name.
+                let err = match (previous, &block.trait_name) {
+                    (Some(first), Some(second)) if first != *second => CompileError::new(
+                        method.span,
+                        format!(
+                            "The traits `{first}` and `{second}` both define a method `{}` for `{}`",
+                            method.name, block.ty
+                        ),
+                    )
+                    .with_note("a type may only have one method of each name"),
+                    _ => CompileError::new(
+                        method.span,
+                        format!(
+                            "Duplicate definitions with name `{}` for `{}`",
+                            method.name, block.ty
+                        ),
+                    ),
+                };
+                errors.push(err);
+                continue;
+            }
+
+            method.name = mangle::method(&block.ty, &method.name);
+            if block.trait_name.is_some() {
+                // Methods implementing a trait are as visible as the trait.
+                method.public = true;
+                trait_methods.insert(method.name.clone());
+            }
+            substitute_fn(&mut method, &bindings);
+            functions.push(method);
+        }
+    }
+
+    for func in &functions {
+        for param in &func.type_params {
+            for bound in &param.bounds {
+                if !traits.contains(bound) {
+                    errors.push(CompileError::new(
+                        param.span,
+                        format!("Cannot find trait `{bound}` in this scope"),
+                    ));
+                }
+            }
+        }
+    }
+
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    Ok(Lowered {
+        structs,
+        traits,
+        consts,
+        functions,
+        trait_methods,
+    })
+}
+
+/// Check a function defined outside of an `impl` block, whose name must not
+/// be taken by another function or the runtime.
+fn check_function(func: &ast::FnDecl, names: &mut HashSet<String>) -> CompileResult<()> {
+    if func.args.iter().any(|arg| arg.name == "self") {
+        return error(
+            func.span,
+            format!(
+                "`self` parameter is only allowed in associated functions, found in `{}`",
+                func.name
+            ),
+        );
+    }
+    // The builtins are linked in under their own names, so a function of
+    // the same name would be a second definition of the symbol.
+    if func.name == typed::NULL_CHECK_HOOK || builtin_signature(&func.name).is_some() {
+        return error(
+            func.span,
+            format!("The name `{}` is reserved for the runtime", func.name),
+        );
+    }
+    if !names.insert(func.name.clone()) {
+        return error(
+            func.span,
+            format!("The function `{}` is defined multiple times", func.name),
+        );
+    }
+    Ok(())
+}
+
+/// Resolve every type alias to the type it names, following aliases of other
+/// aliases, returning the type each alias name stands for.
+fn resolve_aliases(decls: Vec<ast::TypeAlias>) -> CompileResult<HashMap<String, EmptyType>> {
+    let mut targets = HashMap::new();
+    for alias in decls {
+        let (name, span) = (alias.name.clone(), alias.span);
+        if targets.insert(alias.name.clone(), alias).is_some() {
+            return error(
+                span,
+                format!("The type alias `{name}` is defined multiple times"),
+            );
+        }
+    }
+
+    let mut resolved = HashMap::new();
+    for name in targets.keys() {
+        resolve_alias(name, &targets, &mut resolved, &mut Vec::new())?;
+    }
+    Ok(resolved)
+}
+
+fn resolve_alias(
+    name: &str,
+    targets: &HashMap<String, ast::TypeAlias>,
+    resolved: &mut HashMap<String, EmptyType>,
+    resolving: &mut Vec<String>,
+) -> CompileResult<EmptyType> {
+    if let Some(ty) = resolved.get(name) {
+        return Ok(ty.clone());
+    }
+    if resolving.iter().any(|other| other == name) {
+        return error(
+            targets[name].span,
+            format!("The type alias `{name}` refers to itself"),
+        );
+    }
+
+    resolving.push(name.to_string());
+    let ty = expand_aliases(&targets[name].ty, targets, resolved, resolving)?;
+    resolving.pop();
+
+    let alias = EmptyType::Alias(Box::new(ast::Alias {
+        name: name.to_string(),
+        ty,
+    }));
+    resolved.insert(name.to_string(), alias.clone());
+    Ok(alias)
+}
+
+fn expand_aliases(
+    ty: &EmptyType,
+    targets: &HashMap<String, ast::TypeAlias>,
+    resolved: &mut HashMap<String, EmptyType>,
+    resolving: &mut Vec<String>,
+) -> CompileResult<EmptyType> {
+    Ok(match ty {
+        EmptyType::Named(name) if targets.contains_key(name) => {
+            resolve_alias(name, targets, resolved, resolving)?
+        }
+        EmptyType::Pointer(ptr) => EmptyType::Pointer(Box::new(ast::Pointer::new(
+            expand_aliases(&ptr.ty, targets, resolved, resolving)?,
+            ptr.mutable,
+        ))),
+        ty => ty.clone(),
+    })
+}
+
+/// Replace the aliases used in a function. A type parameter hides an alias
+/// with the same name.
+fn substitute_aliases(func: &mut ast::FnDecl, aliases: &HashMap<String, EmptyType>) {
+    if func.type_params.is_empty() {
+        substitute_fn(func, aliases);
+        return;
+    }
+    let mut aliases = aliases.clone();
+    for param in &func.type_params {
+        aliases.remove(&param.name);
+    }
+    substitute_fn(func, &aliases);
+}
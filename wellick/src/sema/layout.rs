@@ -0,0 +1,102 @@
+use super::structs::{struct_layout, Structs};
+use crate::diagnostics::{error, CompileResult};
+use crate::parser::ast;
+use crate::parser::ast::{EmptyType, FloatType, IntegerType};
+
+// The sizes and alignments of types in memory. Everything which allocates
+// or addresses memory, such as stack slots and struct fields, gets its
+// numbers from here.
+
+/// The size of a pointer, in bytes.
+// TODO: This only works for platforms with a 64bit pointer size.
+pub(crate) const POINTER_SIZE: u32 = 8;
+
+/// The size and alignment, in bytes, of a value of some type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Layout {
+    pub size: u32,
+    pub align: u32,
+}
+
+impl Layout {
+    /// The layout of a scalar, which is aligned to its own size.
+    fn scalar(size: u32) -> Self {
+        Self { size, align: size }
+    }
+}
+
+/// The layout of a value of the given type.
+pub(crate) fn layout_of(ty: &EmptyType, structs: &Structs) -> Layout {
+    match ty.resolved() {
+        EmptyType::Float(FloatType::F32) => Layout::scalar(4),
+        EmptyType::Float(FloatType::F64) => Layout::scalar(8),
+        EmptyType::Integer(IntegerType::I32) => Layout::scalar(4),
+        EmptyType::Integer(IntegerType::I64) => Layout::scalar(8),
+        EmptyType::Integer(IntegerType::PointerSize) => Layout::scalar(POINTER_SIZE),
+        EmptyType::Pointer(_) => Layout::scalar(POINTER_SIZE),
+        EmptyType::Named(_) => match struct_layout(ty, structs) {
+            Some(layout) => Layout {
+                size: layout.size,
+                align: layout.align,
+            },
+            None => unreachable!("types are checked before their layout is needed"),
+        },
+        EmptyType::Alias(_) => unreachable!("aliases are resolved"),
+    }
+}
+
+/// The number of bytes needed to store a value of the given type.
+pub(crate) fn size_of(ty: &EmptyType, structs: &Structs) -> u32 {
+    layout_of(ty, structs).size
+}
+
+/// The alignment, in bytes, of a value of the given type.
+pub(crate) fn align_of(ty: &EmptyType, structs: &Structs) -> u32 {
+    layout_of(ty, structs).align
+}
+
+/// Round `offset` up to the next multiple of `align`.
+pub(crate) fn align_to(offset: u32, align: u32) -> u32 {
+    offset.div_ceil(align) * align
+}
+
+/// Whether a function is one of the intrinsics evaluated by [`intrinsic`].
+pub(crate) fn is_intrinsic(name: &str) -> bool {
+    matches!(name, "size_of" | "align_of")
+}
+
+/// Evaluate a call to `size_of::<T>()` or `align_of::<T>()`, which are
+/// constants known at compile time. Returns `None` for any other call.
+pub(crate) fn intrinsic(call: &ast::Call, structs: &Structs) -> CompileResult<Option<u32>> {
+    let query: fn(&EmptyType, &Structs) -> u32 = match call.func.as_str() {
+        "size_of" => size_of,
+        "align_of" => align_of,
+        _ => return Ok(None),
+    };
+
+    if call.type_args.len() != 1 {
+        return error(
+            call.span,
+            format!(
+                "Function `{}` takes 1 type argument(s) but {} were supplied",
+                call.func,
+                call.type_args.len()
+            ),
+        );
+    }
+    if !call.args.is_empty() {
+        return error(
+            call.span,
+            format!(
+                "Function `{}` takes 0 argument(s) but {} were supplied",
+                call.func,
+                call.args.len()
+            ),
+        );
+    }
+    let ty = &call.type_args[0];
+    if matches!(ty.resolved(), EmptyType::Named(_)) && struct_layout(ty, structs).is_none() {
+        return error(call.span, format!("Cannot find type `{ty}` in this scope"));
+    }
+    Ok(Some(query(ty, structs)))
+}
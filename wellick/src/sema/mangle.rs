@@ -0,0 +1,64 @@
+use crate::parser::ast::EmptyType;
+
+// Symbol names for functions which do not map directly onto a Wellick
+// identifier. `$`, `.` and `::` cannot appear in a Wellick identifier, so
+// mangled names never collide with user defined functions.
+
+/// Build the name of an item defined in a module, e.g. `math::gcd`.
+pub(crate) fn module_item(module: &str, name: &str) -> String {
+    format!("{module}::{name}")
+}
+
+/// The module an item was defined in, from its path, e.g. `math` for
+/// `math::gcd` or `geometry::Rect.area`. Items of the root file have none.
+pub(crate) fn module_of(name: &str) -> Option<&str> {
+    name.split_once("::").map(|(module, _)| module)
+}
+
+/// Build the symbol name of a generic function instance, e.g. `max$i32` or
+/// `swap$pm_i64` for `swap::<*mut i64>`.
+pub(crate) fn generic_instance(name: &str, type_args: &[EmptyType]) -> String {
+    let args: Vec<String> = type_args.iter().map(mangle_type).collect();
+    format!("{name}${}", args.join("$"))
+}
+
+/// The name a function was declared with, for use in diagnostics. This is
+/// its symbol name without the type arguments of a generic instance, e.g.
+/// `max` for `max$i32`.
+pub(crate) fn declared_name(symbol: &str) -> &str {
+    symbol.split('$').next().unwrap_or(symbol)
+}
+
+/// The path a function is called by, for use in diagnostics, e.g.
+/// `Point::new` for `Point.new`.
+pub(crate) fn call_path(symbol: &str) -> String {
+    declared_name(symbol).replace('.', "::")
+}
+
+/// Build the symbol name of a method or associated function, e.g. `Point.new`.
+pub(crate) fn method(ty: &str, name: &str) -> String {
+    format!("{ty}.{name}")
+}
+
+/// The name of the function holding the initializer of a constant while its
+/// types are checked, e.g. `const LIMIT`.
+pub(crate) fn const_initializer(name: &str) -> String {
+    format!("const {name}")
+}
+
+/// The name `is_null` is resolved to when called on a pointer, `p.is_null()`.
+/// It is built into the compiler rather than being a function.
+pub(crate) const IS_NULL: &str = "*.is_null";
+
+/// The name `addr` is resolved to when called on a pointer, `p.addr()`, which
+/// gives the address as an `isize`. Like `is_null` it is built in.
+pub(crate) const ADDR: &str = "*.addr";
+
+fn mangle_type(ty: &EmptyType) -> String {
+    // An alias names the same instance as the type it stands for.
+    match ty.resolved() {
+        EmptyType::Pointer(ptr) if ptr.mutable => format!("pm_{}", mangle_type(&ptr.ty)),
+        EmptyType::Pointer(ptr) => format!("p_{}", mangle_type(&ptr.ty)),
+        ty => ty.to_string(),
+    }
+}
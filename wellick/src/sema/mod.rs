@@ -0,0 +1,312 @@
+mod check;
+mod consteval;
+mod flow;
+mod functions;
+mod generics;
+mod infer;
+mod init;
+mod items;
+pub(crate) mod layout;
+mod mangle;
+mod modules;
+mod resolve;
+pub(crate) mod structs;
+mod traits;
+pub(crate) mod typed;
+mod unused;
+
+use crate::diagnostics::{CompileError, CompileResult, Warning};
+use crate::lints::{self, LintLevels, LintReporter};
+use crate::parser::ast;
+use functions::build_fn_map;
+use std::collections::HashSet;
+
+/// Check a parsed program, made up of the root file and every module it
+/// imports, producing the typed program which code generation works from.
+///
+/// Modules are flattened into a single list of items and every name used in
+/// a function is resolved. Once the program has been checked, the control
+/// flow of each function is checked: code after a `return` is removed, every
+/// path must return, and each variable must be assigned before it is read.
+/// Finally the lints are checked at the given levels, those which warn being
+/// added to `warnings`. Undefined names, paths and types, errors in the
+/// functions and `impl` blocks, control flow errors and denied lints are all
+/// reported together, other errors stop analysis at the first one found.
+pub(crate) fn analyze(
+    code: Vec<ast::Module>,
+    levels: &LintLevels,
+    warnings: &mut Vec<Warning>,
+) -> Result<typed::Program, Vec<CompileError>> {
+    let items = modules::resolve_modules(code)?;
+    let lowered = items::lower_items(items)?;
+    let attribute_errors: Vec<_> = lowered
+        .functions
+        .iter()
+        .flat_map(|func| lints::check_attributes(&func.attributes))
+        .collect();
+    if !attribute_errors.is_empty() {
+        return Err(attribute_errors);
+    }
+    resolve::resolve_names(&lowered.functions, &lowered.consts)?;
+    let declared = unused::Declared::new(&lowered);
+    let mut program = check_program(lowered).map_err(|err| vec![err])?;
+
+    let mut lints = LintReporter::new(levels);
+    let mut errors = Vec::new();
+    for func in &mut program.functions {
+        flow::remove_unreachable(func, &mut lints);
+        let found_errors = flow::check_returns(func)
+            .into_iter()
+            .chain(init::check_initialized(func));
+
+        // Each instance of a generic function has the same errors.
+        for err in found_errors {
+            if !errors.contains(&err) {
+                errors.push(err);
+            }
+        }
+    }
+    unused::check_unused(&program, &mut lints);
+    unused::check_dead_code(&declared, &program, &mut lints);
+
+    // The lints are checked one after another, so their warnings are put
+    // back in the order of the code they are about.
+    let mut lint_warnings = lints.warnings;
+    lint_warnings.sort_by_key(|warning| (warning.span.file, warning.span.start));
+    warnings.extend(lint_warnings);
+    errors.extend(lints.errors);
+    if errors.is_empty() {
+        Ok(program)
+    } else {
+        Err(errors)
+    }
+}
+
+/// Check the constants, instantiate generic functions and infer the types of
+/// `let`s, then evaluate the constants and check each function body. The
+/// body of a generic function is checked once with its type parameters in
+/// place, as well as in each instance.
+fn check_program(lowered: items::Lowered) -> CompileResult<typed::Program> {
+    let items::Lowered {
+        structs,
+        traits,
+        consts,
+        functions,
+        ..
+    } = lowered;
+    let placeholders = consteval::check_consts(&consts, &functions)?;
+    let initializers: HashSet<String> = consts
+        .iter()
+        .map(|decl| mangle::const_initializer(&decl.name))
+        .collect();
+    let functions = functions
+        .into_iter()
+        .chain(consts.iter().map(consteval::initializer_fn))
+        .collect();
+    let inferred = infer::infer_types(functions, &structs, &traits, &placeholders)?;
+    let fn_map = build_fn_map(&inferred.functions);
+
+    // Constant initializers are checked before they are evaluated, which
+    // only needs the types of other constants.
+    let (initializers, code): (Vec<_>, Vec<_>) = inferred
+        .functions
+        .into_iter()
+        .partition(|func| initializers.contains(&func.name));
+    for func in initializers {
+        check::check_function(func, &fn_map, &structs, &placeholders)?;
+    }
+    let consts = consteval::evaluate_consts(consts, &code, &structs)?;
+
+    for template in inferred.templates {
+        check::check_template(
+            template.func,
+            &template.signatures,
+            &fn_map,
+            &structs,
+            &consts,
+        )?;
+    }
+    let functions = code
+        .into_iter()
+        .map(|func| {
+            let name = func.name.clone();
+            check::check_function(func, &fn_map, &structs, &consts)
+                .map_err(|err| inferred.generics.in_instance(err, &name))
+        })
+        .collect::<CompileResult<_>>()?;
+    Ok(typed::Program { structs, functions })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lints::LintLevels;
+    use crate::parser::ast::Module;
+    use crate::parser::helpers::input;
+    use crate::parser::items::source_file;
+    use crate::sema::analyze;
+
+    /// The messages of the errors found in a program of a single file.
+    fn errors(code: &str) -> Vec<String> {
+        let items = source_file(input(code)).unwrap();
+        let module = Module { name: None, items };
+        match analyze(vec![module], &LintLevels::default(), &mut Vec::new()) {
+            Ok(_) => Vec::new(),
+            Err(errors) => errors.into_iter().map(|err| err.message).collect(),
+        }
+    }
+
+    /// The messages of the warnings for a program of a single file, which
+    /// must have no errors.
+    fn warnings(code: &str) -> Vec<String> {
+        let items = source_file(input(code)).unwrap();
+        let module = Module { name: None, items };
+        let mut warnings = Vec::new();
+        analyze(vec![module], &LintLevels::default(), &mut warnings).unwrap();
+        warnings
+            .into_iter()
+            .map(|warning| warning.message)
+            .collect()
+    }
+
+    #[test]
+    fn test_valid_program() {
+        let code = "
+            fn double(n: i32) -> i32 {
+                return imul(n, 2);
+            }
+
+            fn main() -> i32 {
+                let x = double(2);
+                return x;
+            }";
+        assert_eq!(errors(code), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_reserved_names() {
+        let code = "
+            fn iadd(a: i32, b: i32) -> i32 { return a; }
+            fn println(n: i32) -> i32 { return n; }
+            fn null_pointer_dereference() -> i32 { return 0; }
+            fn main() -> i32 { return 0; }";
+        assert_eq!(
+            errors(code),
+            [
+                "The name `iadd` is reserved for the runtime",
+                "The name `println` is reserved for the runtime",
+                "The name `null_pointer_dereference` is reserved for the runtime",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_duplicate_function() {
+        let code = "
+            fn f() -> i32 { return 0; }
+            fn f() -> i32 { return 1; }
+            fn main() -> i32 { return f(); }";
+        assert_eq!(errors(code), ["The function `f` is defined multiple times"]);
+    }
+
+    #[test]
+    fn test_trait_method_conflict() {
+        let code = "
+            trait A { fn get(&self) -> i32; }
+            trait B { fn get(&self) -> i32; }
+            struct S { x: i32, }
+            impl A for S { fn get(&self) -> i32 { return 1; } }
+            impl B for S { fn get(&self) -> i32 { return 2; } }
+            fn main() -> i32 { return 0; }";
+        assert_eq!(
+            errors(code),
+            ["The traits `A` and `B` both define a method `get` for `S`"]
+        );
+    }
+
+    #[test]
+    fn test_type_errors() {
+        let cases = [
+            (
+                "fn main() -> i32 { let x: i32 = 1.5; return x; }",
+                "Mismatched types, expected `i32`, found `f64`",
+            ),
+            (
+                "fn main() -> i32 { let x: i32 = 3000000000; return x; }",
+                "Cannot represent the literal as a value of type `i32`",
+            ),
+            (
+                "fn main() -> i32 { if 1.5 { return 1; } return 0; }",
+                "Mismatched types, expected an integer, found `f64`",
+            ),
+            (
+                "fn main() -> i32 { let p: *i32 = 0; return 0; }",
+                "Mismatched types, expected `*i32`, found `i32`",
+            ),
+        ];
+        for (code, message) in cases {
+            assert_eq!(errors(code), [message], "{code}");
+        }
+    }
+
+    #[test]
+    fn test_unresolved_names_reported_together() {
+        let code = "
+            struct Point { x: i32, y: i32, }
+
+            fn main() -> i32 {
+                foo::bar();
+                let a = Point::nwe(1, 2);
+                let b: Foo = 1;
+                let c = Pointt { x: 1, y: 2 };
+                return 0;
+            }";
+        assert_eq!(
+            errors(code),
+            [
+                "Cannot find type or module `foo` in this scope, if it is a module consider adding `import foo;`",
+                "No function or associated item named `nwe` found for `Point`",
+                "Cannot find type `Foo` in this scope",
+                "Cannot find type `Pointt` in this scope",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unreachable_after_diverging_operand() {
+        let cases = [
+            "println(if c { return 1; } else { return 2; });",
+            "let p = Pair { a: 1, b: { return 2; } };",
+            "let x = ieq(c, if c { return 1; } else { return 2; });",
+            "let y: i32 = iadd(c, { return 2; });",
+        ];
+        for stmt in cases {
+            let code = format!(
+                "struct Pair {{ a: i32, b: i32, }}
+                fn f(c: i32) -> i32 {{ {stmt} println(3); return 4; }}
+                fn main() -> i32 {{ return f(1); }}"
+            );
+            assert!(
+                warnings(&code).contains(&"Unreachable statement".to_string()),
+                "{stmt}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_warnings_in_source_order() {
+        let code = "
+            fn helper() -> i32 { return 0; }
+            fn _scratch() -> i32 { return 0; }
+            fn double(n: i32) -> i32 { let unused = 1; return imul(n, 2); }
+            fn main() -> i32 { double(2); let x = 1; return 0; }";
+        assert_eq!(
+            warnings(code),
+            [
+                "Function `helper` is never used",
+                "Unused variable `unused`",
+                "Unused result of type `i32` from `double`",
+                "Unused variable `x`",
+            ]
+        );
+    }
+}
@@ -0,0 +1,496 @@
+use super::mangle;
+use crate::diagnostics::{error, CompileError, CompileResult};
+use crate::parser::ast;
+use crate::parser::ast::EmptyType;
+use std::collections::{HashMap, HashSet};
+
+/// The items defined by a module, and whether each is visible outside of it.
+#[derive(Default)]
+struct Exports {
+    functions: HashMap<String, bool>,
+    structs: HashMap<String, bool>,
+    traits: HashMap<String, bool>,
+    consts: HashMap<String, bool>,
+    aliases: HashMap<String, bool>,
+    /// Associated functions and methods, by type and name.
+    associated: HashMap<(String, String), bool>,
+}
+
+impl Exports {
+    fn new(module: &ast::Module) -> Self {
+        let mut exports = Self::default();
+        for item in &module.items {
+            match item {
+                ast::Item::Function(func) => {
+                    exports.functions.insert(func.name.clone(), func.public);
+                }
+                ast::Item::Struct(decl) => {
+                    exports.structs.insert(decl.name.clone(), decl.public);
+                }
+                ast::Item::Trait(decl) => {
+                    exports.traits.insert(decl.name.clone(), decl.public);
+                }
+                ast::Item::Impl(block) => {
+                    for method in &block.methods {
+                        // Methods implementing a trait are as visible as the trait.
+                        let public = method.public || block.trait_name.is_some();
+                        exports
+                            .associated
+                            .insert((block.ty.clone(), method.name.clone()), public);
+                    }
+                }
+                ast::Item::Const(decl) => {
+                    exports.consts.insert(decl.name.clone(), decl.public);
+                }
+                ast::Item::TypeAlias(alias) => {
+                    exports.aliases.insert(alias.name.clone(), alias.public);
+                }
+                ast::Item::Import(_) => {}
+            }
+        }
+        exports
+    }
+
+    /// Whether the associated function `name` may be defined for the type
+    /// `ty`. A type may have methods added through an alias of it, which
+    /// are only known once aliases are resolved.
+    fn may_define(&self, ty: &str, name: &str) -> bool {
+        self.aliases.contains_key(ty)
+            || self.associated.keys().any(|(impl_ty, func)| {
+                func == name && (impl_ty == ty || self.aliases.contains_key(impl_ty))
+            })
+    }
+}
+
+/// Combine the modules of a program into a single list of items.
+///
+/// Every item defined in a module is renamed to its path from the root file,
+/// e.g. `gcd` in `math.wellick` becomes `math::gcd`, which is also its symbol
+/// name in the object file. References to items are rewritten the same way,
+/// so that `gcd(a, b)` within `math.wellick` and `math::gcd(a, b)` in a file
+/// which imports `math` both call `math::gcd`. Items of the root file keep
+/// their names, so `main` is still `main`.
+///
+/// Every path and type is checked to refer to something, continuing past
+/// each error so that they are all reported at once.
+pub(crate) fn resolve_modules(
+    modules: Vec<ast::Module>,
+) -> Result<Vec<ast::Item>, Vec<CompileError>> {
+    let exports: HashMap<Option<String>, Exports> = modules
+        .iter()
+        .map(|module| (module.name.clone(), Exports::new(module)))
+        .collect();
+
+    let mut items = Vec::new();
+    let mut errors = Vec::new();
+    for module in modules {
+        let imports = module
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                ast::Item::Import(name) => Some(name.ident.clone()),
+                _ => None,
+            })
+            .collect();
+        let mut resolver = Resolver {
+            module: module.name.as_deref(),
+            imports: &imports,
+            exports: &exports,
+            local: &exports[&module.name],
+            type_params: Vec::new(),
+            scopes: Vec::new(),
+            errors: Vec::new(),
+        };
+        for item in module.items {
+            if let Some(item) = resolver.resolve_item(item) {
+                items.push(item);
+            }
+        }
+        errors.append(&mut resolver.errors);
+    }
+
+    if errors.is_empty() {
+        Ok(items)
+    } else {
+        Err(errors)
+    }
+}
+
+struct Resolver<'a> {
+    module: Option<&'a str>,
+    imports: &'a HashSet<String>,
+    exports: &'a HashMap<Option<String>, Exports>,
+    local: &'a Exports,
+    /// Type parameters in scope, which are never renamed.
+    type_params: Vec<String>,
+    /// The parameters and variables visible at the current point of the
+    /// function being resolved, innermost scope last. They shadow constants
+    /// of the same name.
+    scopes: Vec<HashSet<String>>,
+    errors: Vec<CompileError>,
+}
+
+impl<'a> Resolver<'a> {
+    fn resolve_item(&mut self, item: ast::Item) -> Option<ast::Item> {
+        self.type_params.clear();
+        match item {
+            ast::Item::Function(mut func) => {
+                func.name = self.qualify(&func.name);
+                self.resolve_fn(&mut func);
+                Some(ast::Item::Function(func))
+            }
+            ast::Item::Struct(mut decl) => {
+                decl.name = self.qualify(&decl.name);
+                for field in &mut decl.fields {
+                    self.resolve_type(&mut field.ty, field.span);
+                }
+                Some(ast::Item::Struct(decl))
+            }
+            ast::Item::Trait(mut decl) => {
+                decl.name = self.qualify(&decl.name);
+                self.type_params.push("Self".to_string());
+                for method in &mut decl.methods {
+                    self.resolve_fn(method);
+                }
+                Some(ast::Item::Trait(decl))
+            }
+            ast::Item::Impl(mut block) => {
+                if block.trait_name.is_none() && block.ty.contains("::") {
+                    self.errors.push(CompileError::new(
+                        block.span,
+                        format!(
+                            "Cannot define methods for `{}` outside of the module which defines it",
+                            block.ty
+                        ),
+                    ));
+                    return None;
+                }
+                if let Some(name) = &block.trait_name {
+                    let resolved = self.resolve_item_name(
+                        name,
+                        |exports| &exports.traits,
+                        "trait",
+                        block.span,
+                    );
+                    block.trait_name = Some(self.report(resolved)?);
+                }
+                let ty = self.resolve_type_name(&block.ty, block.span);
+                block.ty = self.report(ty)?;
+                self.type_params.push("Self".to_string());
+                for method in &mut block.methods {
+                    self.resolve_fn(method);
+                }
+                Some(ast::Item::Impl(block))
+            }
+            ast::Item::Const(mut decl) => {
+                decl.name = self.qualify(&decl.name);
+                self.resolve_type(&mut decl.ty, decl.span);
+                self.resolve_expr(&mut decl.value);
+                Some(ast::Item::Const(decl))
+            }
+            ast::Item::TypeAlias(mut alias) => {
+                alias.name = self.qualify(&alias.name);
+                self.resolve_type(&mut alias.ty, alias.span);
+                Some(ast::Item::TypeAlias(alias))
+            }
+            ast::Item::Import(_) => None,
+        }
+    }
+
+    /// Record an error, continuing past it so that every error is reported
+    /// at once.
+    fn report<T>(&mut self, result: CompileResult<T>) -> Option<T> {
+        result.map_err(|err| self.errors.push(err)).ok()
+    }
+
+    /// The name of an item defined in this module, as seen from the root.
+    fn qualify(&self, name: &str) -> String {
+        match self.module {
+            Some(module) => mangle::module_item(module, name),
+            None => name.to_string(),
+        }
+    }
+
+    /// The items exported by an imported module.
+    fn import(&self, module: &str, span: ast::Span) -> CompileResult<&'a Exports> {
+        if !self.imports.contains(module) {
+            return error(
+                span,
+                format!(
+                    "Cannot find module `{module}` in this scope, consider adding `import {module};`"
+                ),
+            );
+        }
+        Ok(&self.exports[&Some(module.to_string())])
+    }
+
+    /// Resolve the name of a struct, trait or other item, either a local name such as
+    /// `Point` or a path into an imported module such as `geometry::Point`.
+    fn resolve_item_name(
+        &self,
+        name: &str,
+        items: impl Fn(&Exports) -> &HashMap<String, bool>,
+        kind: &str,
+        span: ast::Span,
+    ) -> CompileResult<String> {
+        match name.split_once("::") {
+            Some((module, item)) => {
+                match items(self.import(module, span)?).get(item) {
+                    Some(true) => {}
+                    Some(false) => {
+                        return error(
+                            span,
+                            format!("The {kind} `{item}` is private to module `{module}`"),
+                        )
+                    }
+                    None => {
+                        return error(
+                            span,
+                            format!("Cannot find {kind} `{item}` in module `{module}`"),
+                        )
+                    }
+                }
+                Ok(mangle::module_item(module, item))
+            }
+            None if items(self.local).contains_key(name) => Ok(self.qualify(name)),
+            None => Ok(name.to_string()),
+        }
+    }
+
+    fn resolve_type_name(&self, name: &str, span: ast::Span) -> CompileResult<String> {
+        if self.type_params.iter().any(|param| param == name) {
+            return Ok(name.to_string());
+        }
+        let is_alias = match name.split_once("::") {
+            Some((module, item)) => self.import(module, span)?.aliases.contains_key(item),
+            None => self.local.aliases.contains_key(name),
+        };
+        if is_alias {
+            self.resolve_item_name(name, |exports| &exports.aliases, "type alias", span)
+        } else if name.contains("::") || self.local.structs.contains_key(name) {
+            self.resolve_item_name(name, |exports| &exports.structs, "struct", span)
+        } else {
+            error(span, format!("Cannot find type `{name}` in this scope"))
+        }
+    }
+
+    fn resolve_type(&mut self, ty: &mut EmptyType, span: ast::Span) {
+        match ty {
+            EmptyType::Named(name) => {
+                let resolved = self.resolve_type_name(name, span);
+                if let Some(resolved) = self.report(resolved) {
+                    *name = resolved;
+                }
+            }
+            EmptyType::Pointer(ptr) => self.resolve_type(&mut ptr.ty, span),
+            _ => {}
+        }
+    }
+
+    /// Resolve the path of a called function, one of `gcd`, `Point::new`,
+    /// `math::gcd` or `geometry::Point::new`.
+    fn resolve_call_path(&self, func: &str, span: ast::Span) -> CompileResult<String> {
+        let segments: Vec<&str> = func.split("::").collect();
+        match segments.as_slice() {
+            [name] if self.local.functions.contains_key(*name) => Ok(self.qualify(name)),
+            [_] => Ok(func.to_string()),
+            [module, name] if self.imports.contains(*module) => {
+                match self.import(module, span)?.functions.get(*name) {
+                    Some(true) => Ok(mangle::module_item(module, name)),
+                    Some(false) => error(
+                        span,
+                        format!("The function `{name}` is private to module `{module}`"),
+                    ),
+                    None => error(
+                        span,
+                        format!("Cannot find function `{name}` in module `{module}`"),
+                    ),
+                }
+            }
+            [ty, name] => {
+                let is_type = self.local.structs.contains_key(*ty)
+                    || self.local.aliases.contains_key(*ty)
+                    || self.type_params.iter().any(|param| param == ty);
+                if !is_type {
+                    return error(
+                        span,
+                        format!(
+                            "Cannot find type or module `{ty}` in this scope, if it is a module consider adding `import {ty};`"
+                        ),
+                    );
+                }
+                // The functions of a type parameter come from its bounds,
+                // which are checked once the function is instantiated.
+                if self.local.structs.contains_key(*ty) && !self.local.may_define(ty, name) {
+                    return error(
+                        span,
+                        format!("No function or associated item named `{name}` found for `{ty}`"),
+                    );
+                }
+                Ok(format!("{}::{name}", self.resolve_type_name(ty, span)?))
+            }
+            [module, ty, name] => {
+                let path = format!("{module}::{ty}");
+                let resolved = self.resolve_type_name(&path, span)?;
+                let exports = self.import(module, span)?;
+                match exports.associated.get(&(ty.to_string(), name.to_string())) {
+                    Some(true) => {}
+                    Some(false) => {
+                        return error(
+                            span,
+                            format!(
+                                "The associated function `{path}::{name}` is private to module `{module}`"
+                            ),
+                        )
+                    }
+                    // This module may implement a trait for the type.
+                    None if exports.may_define(ty, name) || self.local.may_define(&path, name) => {}
+                    None => {
+                        return error(
+                            span,
+                            format!(
+                                "No function or associated item named `{name}` found for `{path}`"
+                            ),
+                        )
+                    }
+                }
+                Ok(format!("{resolved}::{name}"))
+            }
+            _ => error(span, format!("Cannot find function `{func}` in this scope")),
+        }
+    }
+
+    /// Resolve a name used as a value, which may refer to a constant, either
+    /// local such as `LIMIT` or in an imported module such as `math::PI`.
+    fn resolve_value_name(&self, name: &ast::Name) -> CompileResult<String> {
+        if self.scopes.iter().any(|scope| scope.contains(&name.ident)) {
+            return Ok(name.to_string());
+        }
+        self.resolve_item_name(name, |exports| &exports.consts, "constant", name.span)
+    }
+
+    fn resolve_fn(&mut self, func: &mut ast::FnDecl) {
+        let outer_params = self.type_params.len();
+        for param in &mut func.type_params {
+            self.type_params.push(param.name.clone());
+            for bound in &mut param.bounds {
+                let resolved =
+                    self.resolve_item_name(bound, |exports| &exports.traits, "trait", param.span);
+                if let Some(resolved) = self.report(resolved) {
+                    *bound = resolved;
+                }
+            }
+        }
+        for arg in &mut func.args {
+            self.resolve_type(&mut arg.t, arg.span);
+        }
+        self.resolve_type(&mut func.ret_type, func.span);
+        self.scopes
+            .push(func.args.iter().map(|arg| arg.name.clone()).collect());
+        for stmt in &mut func.body {
+            self.resolve_stmt(stmt);
+        }
+        self.scopes.pop();
+        self.type_params.truncate(outer_params);
+    }
+
+    fn resolve_stmt(&mut self, stmt: &mut ast::Stmt) {
+        match stmt {
+            ast::Stmt::Assign(assign) => {
+                if let Some(ty) = &mut assign.var_type {
+                    self.resolve_type(ty, assign.span);
+                }
+                if let Some(value) = &mut assign.value {
+                    self.resolve_expr(value);
+                }
+                // The variable is only in scope after its initializer.
+                self.scopes
+                    .last_mut()
+                    .expect("a function has a scope for its parameters")
+                    .insert(assign.target.ident.clone());
+            }
+            ast::Stmt::ReAssign(local) => {
+                self.resolve_expr(&mut local.target);
+                self.resolve_expr(&mut local.value);
+            }
+            ast::Stmt::CompoundAssign(assign) => {
+                self.resolve_expr(&mut assign.target);
+                self.resolve_expr(&mut assign.value);
+            }
+            ast::Stmt::Return(ast::Return { value: expr, .. }) => self.resolve_expr(expr),
+            ast::Stmt::Call(call) => self.resolve_call(call),
+            ast::Stmt::MethodCall(call) => self.resolve_method_call(call),
+            ast::Stmt::If(if_stmt) => self.resolve_if(if_stmt),
+            ast::Stmt::Block(block) => self.resolve_block(block),
+        }
+    }
+
+    fn resolve_block(&mut self, block: &mut ast::Block) {
+        self.scopes.push(HashSet::new());
+        for stmt in &mut block.stmts {
+            self.resolve_stmt(stmt);
+        }
+        if let Some(value) = &mut block.value {
+            self.resolve_expr(value);
+        }
+        self.scopes.pop();
+    }
+
+    fn resolve_if(&mut self, if_expr: &mut ast::If) {
+        self.resolve_expr(&mut if_expr.condition);
+        self.resolve_block(&mut if_expr.then_body);
+        if let Some(else_body) = &mut if_expr.else_body {
+            self.resolve_block(else_body);
+        }
+    }
+
+    fn resolve_expr(&mut self, expr: &mut ast::Expression) {
+        match expr {
+            ast::Expression::Call(call) => self.resolve_call(call),
+            ast::Expression::MethodCall(call) => self.resolve_method_call(call),
+            ast::Expression::StructLiteral(literal) => {
+                let name = self.resolve_type_name(&literal.name, literal.span);
+                if let Some(name) = self.report(name) {
+                    literal.name = name;
+                }
+                for (_, value) in &mut literal.fields {
+                    self.resolve_expr(value);
+                }
+            }
+            ast::Expression::Field(base, _) => self.resolve_expr(base),
+            ast::Expression::Identifier(name) => {
+                let resolved = self.resolve_value_name(name);
+                if let Some(resolved) = self.report(resolved) {
+                    name.ident = resolved;
+                }
+            }
+            ast::Expression::If(if_expr) => self.resolve_if(if_expr),
+            ast::Expression::Block(block) => self.resolve_block(block),
+            ast::Expression::Comparison(comparison) => {
+                self.resolve_expr(&mut comparison.lhs);
+                self.resolve_expr(&mut comparison.rhs);
+            }
+            _ => {}
+        }
+    }
+
+    fn resolve_call(&mut self, call: &mut ast::Call) {
+        let func = self.resolve_call_path(&call.func, call.span);
+        if let Some(func) = self.report(func) {
+            call.func = func;
+        }
+        for ty in &mut call.type_args {
+            self.resolve_type(ty, call.span);
+        }
+        for arg in &mut call.args {
+            self.resolve_expr(arg);
+        }
+    }
+
+    fn resolve_method_call(&mut self, call: &mut ast::MethodCall) {
+        self.resolve_expr(&mut call.receiver);
+        for arg in &mut call.args {
+            self.resolve_expr(arg);
+        }
+    }
+}
@@ -0,0 +1,165 @@
+use super::functions::builtin_signature;
+use crate::diagnostics::CompileError;
+use crate::parser::ast;
+use std::collections::HashSet;
+
+/// Report every use of a variable or function which is not defined,
+/// continuing past each one so that they are all reported at once.
+///
+/// Calls by path, such as `Point::new` or `math::gcd`, and the names of types
+/// have already been checked when the modules were combined.
+pub(crate) fn resolve_names(
+    functions: &[ast::FnDecl],
+    consts: &[ast::ConstDecl],
+) -> Result<(), Vec<CompileError>> {
+    let mut resolver = NameResolver {
+        functions: functions.iter().map(|func| func.name.as_str()).collect(),
+        scopes: vec![consts.iter().map(|decl| decl.name.clone()).collect()],
+        initializing: Vec::new(),
+        errors: Vec::new(),
+    };
+
+    for decl in consts {
+        resolver.expr(&decl.value);
+    }
+    for func in functions {
+        resolver
+            .scopes
+            .push(func.args.iter().map(|arg| arg.name.clone()).collect());
+        for stmt in &func.body {
+            resolver.stmt(stmt);
+        }
+        resolver.scopes.pop();
+    }
+
+    if resolver.errors.is_empty() {
+        Ok(())
+    } else {
+        Err(resolver.errors)
+    }
+}
+
+struct NameResolver<'a> {
+    functions: HashSet<&'a str>,
+    /// The variables visible at the current point, innermost scope last.
+    /// The outermost scope holds the constants.
+    scopes: Vec<HashSet<String>>,
+    /// The variables whose initializers are being resolved, innermost last.
+    initializing: Vec<String>,
+    errors: Vec<CompileError>,
+}
+
+impl NameResolver<'_> {
+    fn value(&mut self, name: &str, span: ast::Span) {
+        if self.scopes.iter().any(|scope| scope.contains(name)) {
+            return;
+        }
+        let message = if self.initializing.iter().any(|init| init == name) {
+            format!("Cannot use `{name}` in its own initializer")
+        } else {
+            format!("Cannot find value `{name}` in this scope")
+        };
+        self.errors.push(CompileError::new(span, message));
+    }
+
+    fn function(&mut self, call: &ast::Call) {
+        let name = call.func.as_str();
+        let defined = self.functions.contains(name)
+            || builtin_signature(name).is_some()
+            || matches!(name, "size_of" | "align_of")
+            || name.contains("::");
+        if !defined {
+            self.errors.push(CompileError::new(
+                call.span,
+                format!("Cannot find function `{name}` in this scope"),
+            ));
+        }
+    }
+
+    fn stmt(&mut self, stmt: &ast::Stmt) {
+        match stmt {
+            ast::Stmt::Assign(assign) => {
+                if let Some(value) = &assign.value {
+                    self.initializing.push(assign.target.ident.clone());
+                    self.expr(value);
+                    self.initializing.pop();
+                }
+                // The variable is only in scope after its initializer.
+                self.scopes
+                    .last_mut()
+                    .expect("there is always at least one scope")
+                    .insert(assign.target.ident.clone());
+            }
+            ast::Stmt::ReAssign(local) => {
+                self.expr(&local.target);
+                self.expr(&local.value);
+            }
+            ast::Stmt::CompoundAssign(assign) => {
+                self.expr(&assign.target);
+                self.expr(&assign.value);
+            }
+            ast::Stmt::Return(ast::Return { value: expr, .. }) => self.expr(expr),
+            ast::Stmt::Call(call) => self.call(call),
+            ast::Stmt::MethodCall(call) => self.method_call(call),
+            ast::Stmt::If(if_stmt) => self.if_expr(if_stmt),
+            ast::Stmt::Block(block) => self.block(block),
+        }
+    }
+
+    fn block(&mut self, block: &ast::Block) {
+        self.scopes.push(HashSet::new());
+        for stmt in &block.stmts {
+            self.stmt(stmt);
+        }
+        if let Some(value) = &block.value {
+            self.expr(value);
+        }
+        self.scopes.pop();
+    }
+
+    fn if_expr(&mut self, if_expr: &ast::If) {
+        self.expr(&if_expr.condition);
+        self.block(&if_expr.then_body);
+        if let Some(else_body) = &if_expr.else_body {
+            self.block(else_body);
+        }
+    }
+
+    fn call(&mut self, call: &ast::Call) {
+        self.function(call);
+        for arg in &call.args {
+            self.expr(arg);
+        }
+    }
+
+    fn method_call(&mut self, call: &ast::MethodCall) {
+        self.expr(&call.receiver);
+        for arg in &call.args {
+            self.expr(arg);
+        }
+    }
+
+    fn expr(&mut self, expr: &ast::Expression) {
+        match expr {
+            ast::Expression::Call(call) => self.call(call),
+            ast::Expression::MethodCall(call) => self.method_call(call),
+            ast::Expression::Identifier(name) | ast::Expression::DeRef(name) => {
+                self.value(name, name.span)
+            }
+            ast::Expression::AddressOf(addr_of) => self.value(&addr_of.name, addr_of.span),
+            ast::Expression::StructLiteral(literal) => {
+                for (_, value) in &literal.fields {
+                    self.expr(value);
+                }
+            }
+            ast::Expression::Field(base, _) => self.expr(base),
+            ast::Expression::If(if_expr) => self.if_expr(if_expr),
+            ast::Expression::Block(block) => self.block(block),
+            ast::Expression::Comparison(comparison) => {
+                self.expr(&comparison.lhs);
+                self.expr(&comparison.rhs);
+            }
+            ast::Expression::Literal(_) | ast::Expression::Null(_) => {}
+        }
+    }
+}
@@ -0,0 +1,127 @@
+use super::layout::{align_of, align_to, size_of};
+use crate::diagnostics::{error, CompileResult};
+use crate::parser::ast;
+use crate::parser::ast::EmptyType;
+use std::collections::{HashMap, HashSet};
+
+/// The position of a field within a struct.
+#[derive(Debug, Clone)]
+pub(crate) struct FieldLayout {
+    pub name: String,
+    pub ty: EmptyType,
+    pub offset: u32,
+}
+
+/// The memory layout of a struct. Fields are laid out in declaration order,
+/// each aligned to its own size, as a C compiler would.
+#[derive(Debug, Clone)]
+pub(crate) struct StructLayout {
+    pub fields: Vec<FieldLayout>,
+    pub size: u32,
+    pub align: u32,
+}
+
+impl StructLayout {
+    pub fn field(&self, name: &str) -> Option<&FieldLayout> {
+        self.fields.iter().find(|field| field.name == name)
+    }
+}
+
+/// Layouts of every struct in the program, by name.
+pub(crate) type Structs = HashMap<String, StructLayout>;
+
+/// Compute the layout of every struct declaration.
+pub(crate) fn build_struct_layouts(decls: &[ast::StructDecl]) -> CompileResult<Structs> {
+    let mut by_name = HashMap::new();
+    for decl in decls {
+        if by_name.insert(decl.name.clone(), decl).is_some() {
+            return error(
+                decl.span,
+                format!("The struct `{}` is defined multiple times", decl.name),
+            );
+        }
+    }
+
+    let mut structs = Structs::new();
+    for decl in decls {
+        layout_struct(decl, &by_name, &mut structs, &mut HashSet::new())?;
+    }
+    Ok(structs)
+}
+
+fn layout_struct(
+    decl: &ast::StructDecl,
+    decls: &HashMap<String, &ast::StructDecl>,
+    structs: &mut Structs,
+    visiting: &mut HashSet<String>,
+) -> CompileResult<()> {
+    if structs.contains_key(&decl.name) {
+        return Ok(());
+    }
+    if !visiting.insert(decl.name.clone()) {
+        return error(
+            decl.span,
+            format!(
+                "The struct `{}` contains itself, consider storing a pointer instead",
+                decl.name
+            ),
+        );
+    }
+
+    let mut fields: Vec<FieldLayout> = Vec::new();
+    let mut offset = 0;
+    let mut align = 1;
+    for field in &decl.fields {
+        if fields.iter().any(|other| other.name == field.name) {
+            return error(
+                field.span,
+                format!(
+                    "The field `{}` is declared multiple times in `{}`",
+                    field.name, decl.name
+                ),
+            );
+        }
+
+        // Structs stored by value must be laid out before the struct holding them.
+        if let EmptyType::Named(name) = field.ty.resolved() {
+            match decls.get(name) {
+                Some(inner) => layout_struct(inner, decls, structs, visiting)?,
+                None => {
+                    return error(
+                        field.span,
+                        format!("Cannot find type `{name}` in this scope"),
+                    )
+                }
+            }
+        }
+
+        let field_align = align_of(&field.ty, structs);
+        offset = align_to(offset, field_align);
+        fields.push(FieldLayout {
+            name: field.name.clone(),
+            ty: field.ty.clone(),
+            offset,
+        });
+        offset += size_of(&field.ty, structs);
+        align = align.max(field_align);
+    }
+
+    visiting.remove(&decl.name);
+    structs.insert(
+        decl.name.clone(),
+        StructLayout {
+            fields,
+            size: align_to(offset, align),
+            align,
+        },
+    );
+    Ok(())
+}
+
+/// The layout of a struct type, or `None` for any other type.
+pub(crate) fn struct_layout<'a>(ty: &EmptyType, structs: &'a Structs) -> Option<&'a StructLayout> {
+    match ty.resolved() {
+        EmptyType::Named(name) => structs.get(name),
+        _ => None,
+    }
+}
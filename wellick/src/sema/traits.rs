@@ -0,0 +1,156 @@
+use super::generics::substitute;
+use crate::diagnostics::{error, CompileResult};
+use crate::parser::ast;
+use crate::parser::ast::EmptyType;
+use std::collections::{HashMap, HashSet};
+
+/// The traits declared by a program, and which types implement them.
+///
+/// Trait methods are dispatched statically: a method call on a value of a
+/// type implementing a trait calls that type's implementation directly, and
+/// generic functions are instantiated for each concrete type they are used
+/// with, so no vtables are needed.
+pub(crate) struct Traits {
+    traits: HashMap<String, ast::TraitDecl>,
+    /// Pairs of trait and implementing type.
+    impls: HashSet<(String, String)>,
+}
+
+impl Traits {
+    pub fn new(decls: Vec<ast::TraitDecl>) -> CompileResult<Self> {
+        let mut traits = HashMap::new();
+        for decl in decls {
+            if traits.contains_key(&decl.name) {
+                return error(
+                    decl.span,
+                    format!("The trait `{}` is defined multiple times", decl.name),
+                );
+            }
+            traits.insert(decl.name.clone(), decl);
+        }
+
+        Ok(Self {
+            traits,
+            impls: HashSet::new(),
+        })
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.traits.contains_key(name)
+    }
+
+    /// Check that an `impl Trait for Type` block implements exactly the
+    /// methods declared by the trait, with matching signatures, and record
+    /// the implementation.
+    pub fn add_impl(
+        &mut self,
+        trait_name: &str,
+        ty: &str,
+        methods: &[ast::FnDecl],
+        span: ast::Span,
+    ) -> CompileResult<()> {
+        let decl = match self.traits.get(trait_name) {
+            Some(decl) => decl,
+            None => {
+                return error(
+                    span,
+                    format!("Cannot find trait `{trait_name}` in this scope"),
+                )
+            }
+        };
+        if !self.impls.insert((trait_name.to_string(), ty.to_string())) {
+            return error(
+                span,
+                format!("Conflicting implementations of trait `{trait_name}` for type `{ty}`"),
+            );
+        }
+
+        let bindings = HashMap::from([("Self".to_string(), EmptyType::Named(ty.to_string()))]);
+        for method in methods {
+            let expected = match decl.methods.iter().find(|m| m.name == method.name) {
+                Some(expected) => expected,
+                None => {
+                    return error(
+                        method.span,
+                        format!(
+                            "Method `{}` is not a member of trait `{trait_name}`",
+                            method.name
+                        ),
+                    )
+                }
+            };
+
+            let expected = signature(expected, &bindings);
+            let found = signature(method, &bindings);
+            if expected != found {
+                return error(
+                    method.span,
+                    format!(
+                        "Method `{}` has an incompatible type for trait `{trait_name}`, expected `{expected}`, found `{found}`",
+                        method.name
+                    ),
+                );
+            }
+        }
+
+        let missing: Vec<String> = decl
+            .methods
+            .iter()
+            .filter(|expected| !methods.iter().any(|m| m.name == expected.name))
+            .map(|expected| format!("`{}`", expected.name))
+            .collect();
+        if !missing.is_empty() {
+            return error(
+                span,
+                format!(
+                    "Not all trait items implemented for `{ty}`, missing: {}",
+                    missing.join(", ")
+                ),
+            );
+        }
+        Ok(())
+    }
+
+    /// The method `name` declared by the trait `trait_name`, if it has one.
+    pub fn method(&self, trait_name: &str, name: &str) -> Option<&ast::FnDecl> {
+        self.traits
+            .get(trait_name)?
+            .methods
+            .iter()
+            .find(|method| method.name == name)
+    }
+
+    /// Whether `ty` implements the trait `trait_name`.
+    pub fn implements(&self, ty: &EmptyType, trait_name: &str) -> bool {
+        match ty.resolved() {
+            EmptyType::Named(name) => self
+                .impls
+                .contains(&(trait_name.to_string(), name.to_string())),
+            _ => false,
+        }
+    }
+}
+
+/// Render the signature of a method for comparison against a trait,
+/// e.g. `fn(&self, i32) -> i32`.
+fn signature(func: &ast::FnDecl, bindings: &HashMap<String, EmptyType>) -> String {
+    let params: Vec<String> = func
+        .args
+        .iter()
+        .map(|arg| match (arg.name.as_str(), arg.t.resolved()) {
+            ("self", EmptyType::Pointer(ptr)) if ptr.mutable => "&mut self".to_string(),
+            ("self", EmptyType::Pointer(_)) => "&self".to_string(),
+            ("self", _) => "self".to_string(),
+            (_, ty) => substitute(ty, bindings).without_aliases().to_string(),
+        })
+        .collect();
+    let type_params = match func.type_params.len() {
+        0 => String::new(),
+        n => format!("<{}>", vec!["_"; n].join(", ")),
+    };
+    format!(
+        "fn{type_params}({}) -> {}",
+        params.join(", "),
+        substitute(&func.ret_type, bindings).without_aliases()
+    )
+}
@@ -0,0 +1,202 @@
+use super::structs::Structs;
+use crate::parser::ast::{Attribute, ComparisonOp, CompoundOp, EmptyType, Span};
+
+// The program after semantic analysis. Every name has been resolved and
+// every expression has a type, so code generation can translate it without
+// looking anything up or reporting errors.
+
+/// The runtime function which reports a null pointer dereference, called by
+/// the null checks of a debug build. No function of the program may have
+/// its name.
+pub(crate) const NULL_CHECK_HOOK: &str = "null_pointer_dereference";
+
+/// A checked program, ready for code generation.
+#[derive(Debug)]
+pub(crate) struct Program {
+    pub structs: Structs,
+    pub functions: Vec<Function>,
+}
+
+/// The index of a local variable in [`Function::locals`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct LocalId(pub usize);
+
+/// A parameter or `let` binding of a function. Shadowing declares a new
+/// local, so each one has a single type.
+#[derive(Debug, Clone)]
+pub(crate) struct Local {
+    pub name: String,
+    pub ty: EmptyType,
+    pub mutable: bool,
+    /// Whether the address of the variable is taken anywhere, in which case
+    /// it must live in memory rather than a register.
+    pub address_taken: bool,
+    /// Where the variable is declared.
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub(crate) struct Function {
+    pub name: String,
+    pub params: Vec<LocalId>,
+    pub ret_type: EmptyType,
+    pub locals: Vec<Local>,
+    pub body: Vec<Stmt>,
+    /// The attributes of the declaration, which set the level of lints
+    /// within the function.
+    pub attributes: Vec<Attribute>,
+    /// The span of the signature.
+    pub span: Span,
+}
+
+impl Function {
+    pub fn local(&self, id: LocalId) -> &Local {
+        &self.locals[id.0]
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Stmt {
+    pub kind: StmtKind,
+    pub span: Span,
+}
+
+impl Stmt {
+    pub fn new(kind: StmtKind, span: Span) -> Self {
+        Self { kind, span }
+    }
+
+    /// Whether control never continues past the statement, because every
+    /// path through it returns from the function.
+    pub fn diverges(&self) -> bool {
+        match &self.kind {
+            StmtKind::Return(_) => true,
+            StmtKind::Let(_, Some(value))
+            | StmtKind::Assign(_, value)
+            | StmtKind::CompoundAssign(_, _, value)
+            | StmtKind::Expr(value) => value.diverges(),
+            StmtKind::Let(_, None) => false,
+            StmtKind::If(if_stmt) => if_stmt.diverges(),
+            StmtKind::Block(block) => block.diverges(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) enum StmtKind {
+    /// A `let`, declaring a new local with its initial value, if it has one.
+    Let(LocalId, Option<Expr>),
+    /// An assignment to a place, such as `x`, `p.x` or `*p`.
+    Assign(Expr, Expr),
+    CompoundAssign(Expr, CompoundOp, Expr),
+    Return(Expr),
+    /// An expression whose value is discarded, such as a call.
+    Expr(Expr),
+    If(If),
+    Block(Block),
+}
+
+/// A block of statements. The value is only present when it is used, a
+/// final expression which is discarded becomes the last statement.
+#[derive(Debug)]
+pub(crate) struct Block {
+    pub stmts: Vec<Stmt>,
+    pub value: Option<Box<Expr>>,
+}
+
+impl Block {
+    pub fn diverges(&self) -> bool {
+        self.value.as_deref().is_some_and(Expr::diverges) || self.stmts.iter().any(Stmt::diverges)
+    }
+}
+
+/// An `if`, whose branches both have a value when it is used as an expression.
+#[derive(Debug)]
+pub(crate) struct If {
+    pub condition: Expr,
+    pub then_body: Block,
+    pub else_body: Option<Block>,
+}
+
+impl If {
+    pub fn diverges(&self) -> bool {
+        match &self.else_body {
+            _ if self.condition.diverges() => true,
+            Some(else_body) => self.then_body.diverges() && else_body.diverges(),
+            None => false,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Expr {
+    pub kind: ExprKind,
+    pub ty: EmptyType,
+    pub span: Span,
+}
+
+impl Expr {
+    pub fn new(kind: ExprKind, ty: EmptyType, span: Span) -> Self {
+        Self { kind, ty, span }
+    }
+
+    /// Whether control never continues past the expression, because it is,
+    /// or is computed from, a block or `if` which returns on every path.
+    pub fn diverges(&self) -> bool {
+        match &self.kind {
+            ExprKind::If(if_expr) => if_expr.diverges(),
+            ExprKind::Block(block) => block.diverges(),
+            ExprKind::Deref(inner) | ExprKind::Field(inner, _) | ExprKind::IsNull(inner) => {
+                inner.diverges()
+            }
+            ExprKind::StructLiteral(fields) => fields.iter().any(|(_, value)| value.diverges()),
+            ExprKind::Call(_, args) => args.iter().any(Expr::diverges),
+            ExprKind::Comparison(_, lhs, rhs) => lhs.diverges() || rhs.diverges(),
+            ExprKind::Int(_)
+            | ExprKind::Float(_)
+            | ExprKind::Null
+            | ExprKind::Local(_)
+            | ExprKind::AddressOf(_) => false,
+        }
+    }
+
+    /// The expressions the value is computed from, in the order they are
+    /// evaluated. Blocks and `if`s are not included, as only some of their
+    /// contents are evaluated.
+    pub fn operands(&self) -> Vec<&Expr> {
+        match &self.kind {
+            ExprKind::Deref(inner) | ExprKind::Field(inner, _) | ExprKind::IsNull(inner) => {
+                vec![inner]
+            }
+            ExprKind::StructLiteral(fields) => fields.iter().map(|(_, value)| value).collect(),
+            ExprKind::Call(_, args) => args.iter().collect(),
+            ExprKind::Comparison(_, lhs, rhs) => vec![lhs, rhs],
+            _ => Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) enum ExprKind {
+    /// An integer constant, including integer literals, named constants and
+    /// `size_of::<T>()`.
+    Int(i64),
+    Float(f64),
+    Null,
+    Local(LocalId),
+    AddressOf(LocalId),
+    /// The value behind a pointer.
+    Deref(Box<Expr>),
+    /// A field of a struct, or of a struct behind a pointer, at the given
+    /// offset from the address of the struct.
+    Field(Box<Expr>, u32),
+    /// A struct built from the value of each field, by offset.
+    StructLiteral(Vec<(u32, Expr)>),
+    /// A call to a function by its symbol name, including methods with the
+    /// receiver as the first argument.
+    Call(String, Vec<Expr>),
+    IsNull(Box<Expr>),
+    Comparison(ComparisonOp, Box<Expr>, Box<Expr>),
+    If(Box<If>),
+    Block(Box<Block>),
+}
@@ -0,0 +1,383 @@
+use super::functions;
+use super::items::Lowered;
+use super::mangle;
+use super::typed::{Block, Expr, ExprKind, Function, If, LocalId, Program, Stmt, StmtKind};
+use crate::lints::{LintReporter, DEAD_CODE, UNUSED_MUT, UNUSED_RESULTS, UNUSED_VARIABLES};
+use crate::parser::ast;
+use std::collections::{HashMap, HashSet};
+
+/// A function as it is declared, before any generic function is
+/// instantiated.
+struct DeclaredFn {
+    name: String,
+    attributes: Vec<ast::Attribute>,
+    span: ast::Span,
+}
+
+/// The functions of a program which may be reported as never used, along
+/// with those which constants are computed by calling.
+pub(crate) struct Declared {
+    functions: Vec<DeclaredFn>,
+    const_calls: HashSet<String>,
+}
+
+impl Declared {
+    /// Record the functions of a program before its constants are
+    /// evaluated. Methods implementing a trait are left out, as they must
+    /// be defined whether or not they are called.
+    pub fn new(lowered: &Lowered) -> Self {
+        let functions = lowered
+            .functions
+            .iter()
+            .filter(|func| !lowered.trait_methods.contains(&func.name))
+            .map(|func| DeclaredFn {
+                name: func.name.clone(),
+                attributes: func.attributes.clone(),
+                span: func.span,
+            })
+            .collect();
+        let mut const_calls = HashSet::new();
+        for decl in &lowered.consts {
+            calls_in_ast_expr(&decl.value, &mut const_calls);
+        }
+        Self {
+            functions,
+            const_calls,
+        }
+    }
+}
+
+/// Report the functions which can never be called, as they are not called
+/// from `main` or a constant, directly or through other functions.
+pub(crate) fn check_dead_code(declared: &Declared, program: &Program, lints: &mut LintReporter) {
+    let by_name: HashMap<&str, &Function> = program
+        .functions
+        .iter()
+        .map(|func| (func.name.as_str(), func))
+        .collect();
+    let mut reached = HashSet::new();
+    let mut queue: Vec<&str> = declared.const_calls.iter().map(String::as_str).collect();
+    queue.push("main");
+    while let Some(name) = queue.pop() {
+        let Some(func) = by_name.get(name) else {
+            // A builtin.
+            continue;
+        };
+        if !reached.insert(name) {
+            continue;
+        }
+        let mut calls = HashSet::new();
+        for stmt in &func.body {
+            calls_in_stmt(stmt, &mut calls);
+        }
+        queue.extend(calls);
+    }
+
+    // A generic function is used if any instance of it is. Like variables,
+    // functions whose names start with an underscore may be unused.
+    let used: HashSet<&str> = reached.into_iter().map(mangle::declared_name).collect();
+    for func in &declared.functions {
+        let path = mangle::call_path(&func.name);
+        let underscored = path
+            .rsplit("::")
+            .next()
+            .is_some_and(|name| name.starts_with('_'));
+        if func.name != "main" && !underscored && !used.contains(func.name.as_str()) {
+            lints.report(
+                &DEAD_CODE,
+                &func.attributes,
+                func.span,
+                format!("Function `{path}` is never used"),
+            );
+        }
+    }
+}
+
+/// Report the variables in each function of a program which are never read,
+/// or are declared `mut` but never modified, and calls whose result is
+/// discarded.
+pub(crate) fn check_unused(program: &Program, lints: &mut LintReporter) {
+    // A struct is passed to a method taking `&mut self` by its address, so
+    // calling the method may modify it.
+    let mut_self_methods: HashSet<&str> = program
+        .functions
+        .iter()
+        .filter(|func| {
+            func.params.first().is_some_and(|&id| {
+                let local = func.local(id);
+                local.name == "self"
+                    && matches!(local.ty.resolved(), ast::EmptyType::Pointer(ptr) if ptr.mutable)
+            })
+        })
+        .map(|func| func.name.as_str())
+        .collect();
+    for func in &program.functions {
+        check_function(func, &mut_self_methods, lints);
+    }
+}
+
+fn check_function(func: &Function, mut_self_methods: &HashSet<&str>, lints: &mut LintReporter) {
+    let mut usage = Usage {
+        mut_self_methods,
+        read: vec![false; func.locals.len()],
+        modified: vec![false; func.locals.len()],
+        discarded: Vec::new(),
+    };
+    for stmt in &func.body {
+        usage.stmt(stmt);
+    }
+
+    for (index, local) in func.locals.iter().enumerate() {
+        // Parameters are part of the signature, so may be unused.
+        let is_param = func.params.contains(&LocalId(index));
+        if !is_param && !usage.read[index] && !local.name.starts_with('_') {
+            lints.report(
+                &UNUSED_VARIABLES,
+                &func.attributes,
+                local.span,
+                format!("Unused variable `{}`", local.name),
+            );
+        }
+        if local.mutable && !usage.modified[index] {
+            lints.report(
+                &UNUSED_MUT,
+                &func.attributes,
+                local.span,
+                format!("Variable `{}` does not need to be mutable", local.name),
+            );
+        }
+    }
+
+    for expr in usage.discarded {
+        if let ExprKind::Call(name, _) = &expr.kind {
+            // Printing is done for its effect, so its result is not needed.
+            if functions::is_printing_builtin(name) {
+                continue;
+            }
+            lints.report(
+                &UNUSED_RESULTS,
+                &func.attributes,
+                expr.span,
+                format!(
+                    "Unused result of type `{}` from `{}`",
+                    expr.ty,
+                    mangle::call_path(name)
+                ),
+            );
+        }
+    }
+}
+
+/// How the variables of a function are used.
+struct Usage<'a> {
+    mut_self_methods: &'a HashSet<&'a str>,
+    read: Vec<bool>,
+    modified: Vec<bool>,
+    /// The expressions whose values are discarded.
+    discarded: Vec<&'a Expr>,
+}
+
+impl<'a> Usage<'a> {
+    fn stmt(&mut self, stmt: &'a Stmt) {
+        match &stmt.kind {
+            StmtKind::Let(_, value) => {
+                if let Some(value) = value {
+                    self.expr(value);
+                }
+            }
+            StmtKind::Assign(place, value) | StmtKind::CompoundAssign(place, _, value) => {
+                self.place(place);
+                self.expr(value);
+            }
+            StmtKind::Return(value) => self.expr(value),
+            StmtKind::Expr(expr) => {
+                self.discarded.push(expr);
+                self.expr(expr);
+            }
+            StmtKind::If(if_stmt) => self.if_expr(if_stmt),
+            StmtKind::Block(block) => self.block(block),
+        }
+    }
+
+    /// Record an assignment to a place, which modifies the variable it is
+    /// part of, unless it is behind a pointer.
+    fn place(&mut self, place: &'a Expr) {
+        match &place.kind {
+            ExprKind::Local(id) => self.modified[id.0] = true,
+            ExprKind::Field(base, _) if matches!(base.ty.resolved(), ast::EmptyType::Named(_)) => {
+                self.place(base)
+            }
+            _ => self.expr(place),
+        }
+    }
+
+    fn block(&mut self, block: &'a Block) {
+        for stmt in &block.stmts {
+            self.stmt(stmt);
+        }
+        if let Some(value) = &block.value {
+            self.expr(value);
+        }
+    }
+
+    fn if_expr(&mut self, if_expr: &'a If) {
+        self.expr(&if_expr.condition);
+        self.block(&if_expr.then_body);
+        if let Some(else_body) = &if_expr.else_body {
+            self.block(else_body);
+        }
+    }
+
+    fn expr(&mut self, expr: &'a Expr) {
+        match &expr.kind {
+            ExprKind::Local(id) => self.read[id.0] = true,
+            // The variable may be modified through its address.
+            ExprKind::AddressOf(id) => {
+                self.read[id.0] = true;
+                self.modified[id.0] = true;
+            }
+            ExprKind::Deref(inner) | ExprKind::Field(inner, _) | ExprKind::IsNull(inner) => {
+                self.expr(inner)
+            }
+            ExprKind::StructLiteral(fields) => {
+                for (_, value) in fields {
+                    self.expr(value);
+                }
+            }
+            ExprKind::Call(name, args) => {
+                let receiver = args.first().filter(|receiver| {
+                    self.mut_self_methods.contains(name.as_str())
+                        && !matches!(receiver.ty.resolved(), ast::EmptyType::Pointer(_))
+                });
+                if let Some(receiver) = receiver {
+                    self.place(receiver);
+                }
+                for arg in args {
+                    self.expr(arg);
+                }
+            }
+            ExprKind::Comparison(_, lhs, rhs) => {
+                self.expr(lhs);
+                self.expr(rhs);
+            }
+            ExprKind::If(if_expr) => self.if_expr(if_expr),
+            ExprKind::Block(block) => self.block(block),
+            ExprKind::Int(_) | ExprKind::Float(_) | ExprKind::Null => {}
+        }
+    }
+}
+
+/// Collect the names of the functions called within a statement.
+fn calls_in_stmt<'a>(stmt: &'a Stmt, calls: &mut HashSet<&'a str>) {
+    match &stmt.kind {
+        StmtKind::Let(_, value) => {
+            if let Some(value) = value {
+                calls_in_expr(value, calls);
+            }
+        }
+        StmtKind::Assign(place, value) | StmtKind::CompoundAssign(place, _, value) => {
+            calls_in_expr(place, calls);
+            calls_in_expr(value, calls);
+        }
+        StmtKind::Return(expr) | StmtKind::Expr(expr) => calls_in_expr(expr, calls),
+        StmtKind::If(if_stmt) => calls_in_if(if_stmt, calls),
+        StmtKind::Block(block) => calls_in_block(block, calls),
+    }
+}
+
+fn calls_in_block<'a>(block: &'a Block, calls: &mut HashSet<&'a str>) {
+    for stmt in &block.stmts {
+        calls_in_stmt(stmt, calls);
+    }
+    if let Some(value) = &block.value {
+        calls_in_expr(value, calls);
+    }
+}
+
+fn calls_in_if<'a>(if_expr: &'a If, calls: &mut HashSet<&'a str>) {
+    calls_in_expr(&if_expr.condition, calls);
+    calls_in_block(&if_expr.then_body, calls);
+    if let Some(else_body) = &if_expr.else_body {
+        calls_in_block(else_body, calls);
+    }
+}
+
+fn calls_in_expr<'a>(expr: &'a Expr, calls: &mut HashSet<&'a str>) {
+    match &expr.kind {
+        ExprKind::Call(name, args) => {
+            calls.insert(name);
+            for arg in args {
+                calls_in_expr(arg, calls);
+            }
+        }
+        ExprKind::Deref(inner) | ExprKind::Field(inner, _) | ExprKind::IsNull(inner) => {
+            calls_in_expr(inner, calls)
+        }
+        ExprKind::StructLiteral(fields) => {
+            for (_, value) in fields {
+                calls_in_expr(value, calls);
+            }
+        }
+        ExprKind::Comparison(_, lhs, rhs) => {
+            calls_in_expr(lhs, calls);
+            calls_in_expr(rhs, calls);
+        }
+        ExprKind::If(if_expr) => calls_in_if(if_expr, calls),
+        ExprKind::Block(block) => calls_in_block(block, calls),
+        ExprKind::Int(_)
+        | ExprKind::Float(_)
+        | ExprKind::Null
+        | ExprKind::Local(_)
+        | ExprKind::AddressOf(_) => {}
+    }
+}
+
+/// Collect the names of the functions called in the initializer of a
+/// constant, which has not been checked and has no typed tree.
+fn calls_in_ast_expr(expr: &ast::Expression, calls: &mut HashSet<String>) {
+    match expr {
+        ast::Expression::Call(call) => {
+            calls.insert(call.func.clone());
+            for arg in &call.args {
+                calls_in_ast_expr(arg, calls);
+            }
+        }
+        ast::Expression::Comparison(comparison) => {
+            calls_in_ast_expr(&comparison.lhs, calls);
+            calls_in_ast_expr(&comparison.rhs, calls);
+        }
+        ast::Expression::If(if_expr) => {
+            calls_in_ast_expr(&if_expr.condition, calls);
+            calls_in_ast_block(&if_expr.then_body, calls);
+            if let Some(else_body) = &if_expr.else_body {
+                calls_in_ast_block(else_body, calls);
+            }
+        }
+        ast::Expression::Block(block) => calls_in_ast_block(block, calls),
+        // Constants cannot use methods, structs or pointers, which is
+        // reported when they are evaluated.
+        _ => {}
+    }
+}
+
+fn calls_in_ast_block(block: &ast::Block, calls: &mut HashSet<String>) {
+    for stmt in &block.stmts {
+        match stmt {
+            ast::Stmt::Assign(ast::Assignment {
+                value: Some(value), ..
+            })
+            | ast::Stmt::ReAssign(ast::Local { value, .. })
+            | ast::Stmt::CompoundAssign(ast::CompoundAssign { value, .. })
+            | ast::Stmt::Return(ast::Return { value, .. }) => calls_in_ast_expr(value, calls),
+            ast::Stmt::Call(call) => calls_in_ast_expr(&ast::Expression::Call(call.clone()), calls),
+            ast::Stmt::If(if_stmt) => {
+                calls_in_ast_expr(&ast::Expression::If(if_stmt.clone()), calls)
+            }
+            ast::Stmt::Block(block) => calls_in_ast_block(block, calls),
+            ast::Stmt::Assign(_) | ast::Stmt::MethodCall(_) => {}
+        }
+    }
+    if let Some(value) = &block.value {
+        calls_in_ast_expr(value, calls);
+    }
+}